@@ -2,6 +2,7 @@ use euclid::vec2;
 use gdnative::prelude::*;
 
 use sim::Message;
+use valkyrie_core::ecs::{Entity, With};
 use valkyrie_core::simulation::*;
 
 mod sim;
@@ -16,6 +17,7 @@ pub struct GoreKill {
     debug_nodes: Vec<Ref<gdnative::prelude::Node2D>>,
     sprite_nodes: Vec<Ref<gdnative::prelude::Node2D>>,
     update_render_state: bool,
+    last_alpha: f32,
     should_exit: bool,
 }
 
@@ -87,12 +89,16 @@ impl GoreKill {
             }
         }
 
+        let mut sim = SimulationExecutor::new(max_engine_msgs, sim_hz, fixed_timestep, config);
+        sim.enable_interpolation();
+
         Self {
             debug_nodes,
             sprite_nodes,
             update_render_state: false,
+            last_alpha: 0.0,
             should_exit: false,
-            sim: SimulationExecutor::new(max_engine_msgs, sim_hz, fixed_timestep, config),
+            sim,
         }
     }
 
@@ -161,10 +167,11 @@ impl GoreKill {
 
 impl GoreKill {
     fn render(&mut self) {
-        // TODO: interpolation?
-
-        if self.update_render_state {
+        // Interpolate between ticks, so it's smooth on displays faster than the sim
+        let alpha = self.sim.alpha();
+        if self.update_render_state || alpha != self.last_alpha {
             self.update_render_state = false;
+            self.last_alpha = alpha;
         } else {
             return;
         }
@@ -173,54 +180,51 @@ impl GoreKill {
 
         // Update render sim
         let sim = self.sim.sim();
+        let previous = self.sim.previous_state();
         let mut debug_shape_index = 0;
         let mut sprite_index = 0;
 
-        for entity in sim.world.entities() {
-            let entity = *entity;
-            let debug = sim.world.get::<components::Debug>(entity);
-            let position = sim.world.get::<components::Position>(entity);
-            let collision_shape = sim.world.get::<components::Collidable>(entity);
-
-            // Draw debug shapes
-            if let Some(debug) = debug {
-                if let Some(position) = position {
-                    if let Some(collision_shape) = collision_shape {
-                        // Only update it if it's valid
-                        if debug_shape_index < self.debug_nodes.len() {
-                            unsafe {
-                                let instance = self.debug_nodes[debug_shape_index].assume_safe();
-                                instance.show();
-                                instance.set(
-                                    "draw_position",
-                                    vec2(position.x as f32, position.y as f32),
-                                );
-                                instance.set("radius", collision_shape.radius);
-                                instance.update();
-                            }
-                            debug_shape_index += 1;
-                        }
-                    }
+        let interpolate = |entity: Entity, position: &components::Position| {
+            let previous = previous
+                .and_then(|previous| previous.0.get(&entity))
+                .unwrap_or(position);
+
+            vec2(
+                previous.x as f32 + (position.x - previous.x) as f32 * alpha,
+                previous.y as f32 + (position.y - previous.y) as f32 * alpha,
+            )
+        };
+
+        // Draw debug shapes
+        for (entity, position, collision_shape) in sim
+            .world
+            .query_ref_filtered::<(Entity, &components::Position, &components::Collidable), With<components::Debug>>()
+        {
+            // Only update it if it's valid
+            if debug_shape_index < self.debug_nodes.len() {
+                unsafe {
+                    let instance = self.debug_nodes[debug_shape_index].assume_safe();
+                    instance.show();
+                    instance.set("draw_position", interpolate(entity, position));
+                    instance.set("radius", collision_shape.radius);
+                    instance.update();
                 }
+                debug_shape_index += 1;
             }
+        }
 
-            {
-                if let Some(position) = position {
-                    if let Some(collision_shape) = collision_shape {
-                        // Only update it if it's valid
-                        if sprite_index < self.sprite_nodes.len() {
-                            unsafe {
-                                let instance = self.sprite_nodes[sprite_index].assume_safe();
-                                instance.show();
-                                instance.set_global_position(vec2(
-                                    position.x as f32,
-                                    position.y as f32,
-                                ));
-                            }
-                            sprite_index += 1;
-                        }
-                    }
+        for (entity, position, _) in sim
+            .world
+            .query_ref::<(Entity, &components::Position, &components::Collidable)>()
+        {
+            // Only update it if it's valid
+            if sprite_index < self.sprite_nodes.len() {
+                unsafe {
+                    let instance = self.sprite_nodes[sprite_index].assume_safe();
+                    instance.show();
+                    instance.set_global_position(interpolate(entity, position));
                 }
+                sprite_index += 1;
             }
         }
 
@@ -1,23 +1,95 @@
-use valkyrie_core::ecs::Component;
+use serde::{Deserialize, Serialize};
+use valkyrie_core::ecs::{
+    self as core_ecs, Component, HashComponent, SerializableComponent, SerializationRegistry,
+    SpatialExtent, SpatialPosition,
+};
+use valkyrie_core::math::Vec3;
 
-#[derive(Copy, Clone, PartialEq, Default, Debug)]
+#[derive(
+    Copy, Clone, PartialEq, Default, Debug, Hash, Component, HashComponent, Serialize, Deserialize,
+)]
 pub struct Debug;
-impl Component for Debug {}
 
-#[derive(Copy, Clone, PartialEq, Default, Debug)]
+impl SerializableComponent for Debug {
+    const NAME: &'static str = "Debug";
+}
+
+#[derive(
+    Copy, Clone, PartialEq, Default, Debug, Hash, Component, HashComponent, Serialize, Deserialize,
+)]
 pub struct Player;
-impl Component for Player {}
 
-#[derive(Copy, Clone, PartialEq, Default, Debug)]
+impl SerializableComponent for Player {
+    const NAME: &'static str = "Player";
+}
+
+#[derive(
+    Copy, Clone, PartialEq, Default, Debug, Hash, Component, HashComponent, Serialize, Deserialize,
+)]
 pub struct Position {
     pub x: i32,
     pub y: i32,
 }
-impl Component for Position {}
+
+impl SerializableComponent for Position {
+    const NAME: &'static str = "Position";
+}
+
+impl SpatialPosition for Position {
+    fn spatial_position(&self) -> Vec3 {
+        Vec3::new(self.x as f32, self.y as f32, 0.)
+    }
+}
 
 // TODO: make an enum?
-#[derive(Copy, Clone, PartialEq, Default, Debug)]
+#[derive(
+    Copy, Clone, PartialEq, Default, Debug, Hash, Component, HashComponent, Serialize, Deserialize,
+)]
 pub struct Collidable {
     pub radius: u32,
 }
-impl Component for Collidable {}
+
+impl SerializableComponent for Collidable {
+    const NAME: &'static str = "Collidable";
+}
+
+impl SpatialExtent for Collidable {
+    fn spatial_radius(&self) -> f32 {
+        self.radius as f32
+    }
+}
+
+/// The input the player has given for the current tick.
+#[derive(
+    Copy, Clone, PartialEq, Default, Debug, Hash, Component, HashComponent, Serialize, Deserialize,
+)]
+pub struct PlayerInput {
+    pub move_x: i32,
+    pub move_y: i32,
+    pub laser_shot: bool,
+    pub shot: bool,
+}
+
+impl SerializableComponent for PlayerInput {
+    const NAME: &'static str = "PlayerInput";
+}
+
+/// Returns a registry containing all components that may be saved or used in prefabs.
+pub fn registry() -> SerializationRegistry {
+    let mut registry = SerializationRegistry::new();
+    registry.register::<Debug>().unwrap();
+    registry.register::<Player>().unwrap();
+    registry.register::<Position>().unwrap();
+    registry.register::<Collidable>().unwrap();
+    registry.register::<PlayerInput>().unwrap();
+    registry
+}
+
+/// Includes all simulation components in the world's checksum. Resources are hashed by `GoreKillSim::checksum()`.
+pub fn register_checksums(world: &mut core_ecs::World) {
+    world.register_checksum::<Debug>();
+    world.register_checksum::<Player>();
+    world.register_checksum::<Position>();
+    world.register_checksum::<Collidable>();
+    world.register_checksum::<PlayerInput>();
+}
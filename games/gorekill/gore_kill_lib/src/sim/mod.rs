@@ -2,6 +2,8 @@ use valkyrie_core::ecs::*;
 use valkyrie_core::simulation::*;
 
 pub mod components;
+pub mod resources;
+mod systems;
 
 pub enum AttackType {
     /// Focused attack that cuts movespeed in half
@@ -24,6 +26,7 @@ pub enum Message {
     Attack(AttackType),
 }
 
+#[derive(Clone)]
 pub struct GoreKillConfig {
     /// The number of starting lives the player begins with
     pub starting_lives: u8,
@@ -92,80 +95,85 @@ pub struct GoreKillSim {
     pub world: World,
     pub dirty: bool,
     player_entity: Entity,
+    schedule: Schedule,
 }
 
 impl Simulation<GoreKillConfig, Message> for GoreKillSim {
     fn new(config: GoreKillConfig) -> Self {
         let mut world = World::new();
 
-        //TODO: This is a super heavy op. Maybe do in a separate thread?
         use components::*;
         world.register::<Collidable>(200);
         world.register::<Position>(200);
         world.register::<Debug>(200);
         world.register::<Player>(1);
-
-        // Add a player entity
-        let player = world.add_entity();
-        match world.add::<Collidable>(player) {
-            Ok(collidable) => {
-                collidable.radius = 200;
-            }
-            _ => {}
-        }
-        world.add::<Debug>(player).unwrap();
-        world.add::<Player>(player).unwrap();
-        match world.add::<Position>(player) {
-            Ok(position) => {
-                position.x = 10;
-                position.y = 10;
-            }
-            Err(_) => {}
-        }
+        world.register::<PlayerInput>(1);
+        components::register_checksums(&mut world);
+
+        world.insert_resource(resources::Score(0));
+        world.insert_resource(resources::Lives(config.starting_lives));
+        world.insert_resource(config);
+        world.register_snapshot_resource::<resources::Score>();
+        world.register_snapshot_resource::<resources::Lives>();
+        world.register_snapshot_resource::<GoreKillConfig>();
+
+        let prefabs = Prefabs::from_ron(&components::registry(), include_str!("prefabs.ron"))
+            .expect("Invalid prefabs");
+        world.insert_resource(prefabs);
+        world.insert_resource(SpatialIndex::<Position, Collidable>::new(32.));
+
+        let player = world.spawn_prefab("player").unwrap();
+
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule
+            .add_system("update", systems::PlayerMovement)
+            .unwrap();
+        schedule
+            .add_system(
+                "update",
+                SpatialIndexUpdate::<Position, Collidable>::new(),
+            )
+            .unwrap()
+            .after("player_movement");
 
         Self {
             tick: 0,
             world,
             dirty: false,
             player_entity: player,
+            schedule,
         }
     }
 
     fn tick(&mut self, delta_t: Duration, messages: &[Message]) -> ControlMessage {
         self.tick += 1;
-        self.dirty = true;
 
         // Do player input
         {
-            let mut move_x = 0;
-            let mut move_y = 0;
-            let mut fire_bomb = false;
-            let mut fire_turbo = false;
-            let mut laser_shot = false;
-            let mut shot = false;
+            let mut input = components::PlayerInput::default();
 
             for msg in messages {
                 match msg {
                     Message::Move(mov) => match mov {
-                        MoveDirection::Up => move_y = 1,
-                        MoveDirection::Down => move_y = -1,
-                        MoveDirection::Left => move_x = -1,
-                        MoveDirection::Right => move_x = 1,
+                        MoveDirection::Up => input.move_y = 1,
+                        MoveDirection::Down => input.move_y = -1,
+                        MoveDirection::Left => input.move_x = -1,
+                        MoveDirection::Right => input.move_x = 1,
                     },
                     Message::Attack(atk) => match atk {
-                        AttackType::LaserAttack => laser_shot = true,
-                        AttackType::ShotAttack => shot = true,
+                        AttackType::LaserAttack => input.laser_shot = true,
+                        AttackType::ShotAttack => input.shot = true,
                     },
                 }
             }
 
             match self
                 .world
-                .get_mut::<components::Position>(self.player_entity)
+                .get_mut::<components::PlayerInput>(self.player_entity)
             {
-                Some(position) => {
-                    position.x += move_x;
-                    position.y += move_y;
+                Some(player_input) => {
+                    *player_input = input;
                 }
                 None => {
                     gdnative::prelude::godot_print!("Nothing?");
@@ -173,6 +181,51 @@ impl Simulation<GoreKillConfig, Message> for GoreKillSim {
             }
         }
 
+        self.schedule.run(&mut self.world);
+
+        // Only re-push render state if something visible changed
+        {
+            use components::*;
+            let changed = self
+                .world
+                .query_ref_filtered::<Entity, Changed<Position>>()
+                .next()
+                .is_some()
+                || self.world.removed_components::<Position>().next().is_some();
+
+            self.dirty |= changed;
+            self.world.clear_trackers();
+            self.world.update_events();
+        }
+
         ControlMessage::Ok
     }
+
+    fn checksum(&self) -> Option<u64> {
+        use std::hash::{Hash, Hasher};
+
+        let mut checksum = Checksum::new();
+        checksum.write_u64(self.world.checksum());
+        self.world.resource::<resources::Score>().hash(&mut checksum);
+        self.world.resource::<resources::Lives>().hash(&mut checksum);
+        Some(checksum.finish())
+    }
+}
+
+/// Positions of all entities before the last tick, used to interpolate rendering.
+#[derive(Default)]
+pub struct PreviousPositions(pub std::collections::HashMap<Entity, components::Position>);
+
+impl Interpolate for GoreKillSim {
+    type RenderState = PreviousPositions;
+
+    fn save_render_state(&self, state: &mut Self::RenderState) {
+        state.0.clear();
+        for (entity, position) in self
+            .world
+            .query_ref::<(Entity, &components::Position)>()
+        {
+            state.0.insert(entity, *position);
+        }
+    }
 }
@@ -0,0 +1,7 @@
+/// The points the player has scored.
+#[derive(Copy, Clone, PartialEq, Default, Debug, Hash)]
+pub struct Score(pub u32);
+
+/// The lives the player has remaining.
+#[derive(Copy, Clone, PartialEq, Default, Debug, Hash)]
+pub struct Lives(pub u8);
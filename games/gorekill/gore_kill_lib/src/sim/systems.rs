@@ -0,0 +1,23 @@
+use valkyrie_core::ecs::*;
+
+use super::components::*;
+
+/// Moves the player based on the input for the tick.
+pub struct PlayerMovement;
+
+impl System for PlayerMovement {
+    fn name(&self) -> &'static str {
+        "player_movement"
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new().query::<(&mut Position, &PlayerInput)>()
+    }
+
+    fn run(&mut self, world: &mut World) {
+        for (position, input) in world.query::<(&mut Position, &PlayerInput)>() {
+            position.x += input.move_x;
+            position.y += input.move_y;
+        }
+    }
+}
@@ -44,18 +44,28 @@ impl Simulation<Cfg, Msg> for Re4magined {
                     valkyrie_core::simulation::WindowMsg::Shutdown => {}
                     valkyrie_core::simulation::WindowMsg::RedrawRequested => {}
                     valkyrie_core::simulation::WindowMsg::Resize { w, h } => {}
-                    valkyrie_core::simulation::WindowMsg::KeyPress(key) => match key {
-                        valkyrie_core::simulation::KeyboardMsg::W => self.up_held = true,
-                        valkyrie_core::simulation::KeyboardMsg::A => self.left_held = true,
-                        valkyrie_core::simulation::KeyboardMsg::S => self.down_held = true,
-                        valkyrie_core::simulation::KeyboardMsg::D => self.right_held = true,
+                    valkyrie_core::simulation::WindowMsg::KeyPress(key) => match key.key {
+                        valkyrie_core::simulation::Key::W => self.up_held = true,
+                        valkyrie_core::simulation::Key::A => self.left_held = true,
+                        valkyrie_core::simulation::Key::S => self.down_held = true,
+                        valkyrie_core::simulation::Key::D => self.right_held = true,
+                        _ => {}
                     },
-                    valkyrie_core::simulation::WindowMsg::KeyRelease(key) => match key {
-                        valkyrie_core::simulation::KeyboardMsg::W => self.up_held = false,
-                        valkyrie_core::simulation::KeyboardMsg::A => self.left_held = false,
-                        valkyrie_core::simulation::KeyboardMsg::S => self.down_held = false,
-                        valkyrie_core::simulation::KeyboardMsg::D => self.right_held = false,
+                    valkyrie_core::simulation::WindowMsg::KeyRelease(key) => match key.key {
+                        valkyrie_core::simulation::Key::W => self.up_held = false,
+                        valkyrie_core::simulation::Key::A => self.left_held = false,
+                        valkyrie_core::simulation::Key::S => self.down_held = false,
+                        valkyrie_core::simulation::Key::D => self.right_held = false,
+                        _ => {}
                     },
+                    // Releases aren't sent while unfocused, so stop moving
+                    valkyrie_core::simulation::WindowMsg::Unfocused => {
+                        self.up_held = false;
+                        self.down_held = false;
+                        self.left_held = false;
+                        self.right_held = false;
+                    }
+                    _ => {}
                 },
             }
         }
@@ -89,12 +99,20 @@ impl Simulation<Cfg, Msg> for Re4magined {
     }
 }
 
+impl Interpolate for Re4magined {
+    type RenderState = Vec3;
+
+    fn save_render_state(&self, state: &mut Self::RenderState) {
+        *state = self.camera_pos;
+    }
+}
+
 impl Renderable for Re4magined {
-    fn render(&self, renderer: &mut Renderer) {
+    fn render(&self, previous: &Vec3, alpha: f32, renderer: &mut Renderer) {
         renderer
             .create_render_pass()
             .add(RenderCommand::UpdateCamera(Camera {
-                eye: self.camera_pos,
+                eye: previous.lerp(self.camera_pos, alpha),
                 target: Vec3::default(),
                 up: None,
             }));
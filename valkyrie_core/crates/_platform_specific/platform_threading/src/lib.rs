@@ -1,7 +1,192 @@
+// Worker pool built on std threads. Threads are created once, up front, and reused for every batch of jobs.
+use std::{
+    any::Any,
+    panic::{self, AssertUnwindSafe},
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+};
+
+pub use core_threading::{Job, ThreadPool};
+
+/// A job with its lifetime erased so it can be sent to a worker.
+struct JobPtr(*mut (dyn FnMut() + Send + 'static));
+
+// Jobs are `Send`, and `execute()` waits for every job to finish before the borrow ends.
+unsafe impl Send for JobPtr {}
+
+enum Message {
+    Run(JobPtr),
+    Exit,
+}
+
+type JobResult = Result<(), Box<dyn Any + Send>>;
+
+/// Runs jobs on a fixed number of worker threads. Jobs should not call `execute()` on the pool they are running on.
+pub struct WorkerPool {
+    workers: Vec<JoinHandle<()>>,
+    jobs: Sender<Message>,
+    /// Only one batch may run at a time, as results are shared between batches.
+    results: Mutex<Receiver<JobResult>>,
+}
+
+impl WorkerPool {
+    /// Creates a new pool with the given number of threads. At least one thread is always created.
+    pub fn new(thread_count: usize) -> Self {
+        let (jobs, job_receiver) = channel::<Message>();
+        let (result_sender, results) = channel::<JobResult>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        let workers = (0..thread_count.max(1))
+            .map(|i| {
+                let job_receiver = job_receiver.clone();
+                let result_sender = result_sender.clone();
+
+                thread::Builder::new()
+                    .name(format!("valkyrie_worker_{}", i))
+                    .spawn(move || worker(job_receiver, result_sender))
+                    .expect("Unable to spawn worker thread")
+            })
+            .collect();
+
+        Self {
+            workers,
+            jobs,
+            results: Mutex::new(results),
+        }
+    }
+
+    /// Creates a new pool with a thread for each available core.
+    pub fn with_available_parallelism() -> Self {
+        Self::new(thread::available_parallelism().map_or(1, |n| n.get()))
+    }
+}
+
+fn worker(jobs: Arc<Mutex<Receiver<Message>>>, results: Sender<JobResult>) {
+    loop {
+        let message = match jobs.lock() {
+            Ok(jobs) => jobs.recv(),
+            Err(_) => return,
+        };
+
+        match message {
+            Ok(Message::Run(job)) => {
+                let result = panic::catch_unwind(AssertUnwindSafe(|| unsafe { (*job.0)() }));
+                if results.send(result).is_err() {
+                    return;
+                }
+            }
+            Ok(Message::Exit) | Err(_) => return,
+        }
+    }
+}
+
+impl ThreadPool for WorkerPool {
+    fn thread_count(&self) -> usize {
+        self.workers.len()
+    }
+
+    fn execute(&self, jobs: &mut [Job]) {
+        // Not worth the round trip to a worker
+        if jobs.len() == 1 {
+            (jobs[0])();
+            return;
+        }
+
+        let results = self.results.lock().unwrap_or_else(|e| e.into_inner());
+
+        for job in jobs.iter_mut() {
+            let job: *mut (dyn FnMut() + Send + '_) = &mut **job;
+            // Safety: all results are waited on below, so the job outlives its use on the worker.
+            let job: *mut (dyn FnMut() + Send + 'static) = unsafe { std::mem::transmute(job) };
+            self.jobs
+                .send(Message::Run(JobPtr(job)))
+                .expect("Worker threads have stopped");
+        }
+
+        let mut panic = None;
+        for _ in 0..jobs.len() {
+            match results.recv().expect("Worker threads have stopped") {
+                Ok(()) => {}
+                Err(e) => panic = Some(e),
+            }
+        }
+
+        if let Some(panic) = panic {
+            panic::resume_unwind(panic);
+        }
+    }
+}
+
+impl Drop for WorkerPool {
+    fn drop(&mut self) {
+        for _ in 0..self.workers.len() {
+            let _ = self.jobs.send(Message::Exit);
+        }
+
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn worker_pool_runs_borrowed_jobs() {
+        let pool = WorkerPool::new(4);
+        assert_eq!(4, pool.thread_count());
+
+        let counter = AtomicUsize::new(0);
+        let mut values = [0; 8];
+        for _ in 0..3 {
+            let mut jobs: Vec<_> = values
+                .iter_mut()
+                .enumerate()
+                .map(|(i, value)| {
+                    let counter = &counter;
+                    move || {
+                        *value += i;
+                        counter.fetch_add(1, Ordering::SeqCst);
+                    }
+                })
+                .collect();
+            let mut jobs: Vec<Job> = jobs.iter_mut().map(|j| j as Job).collect();
+            pool.execute(&mut jobs);
+        }
+
+        assert_eq!(24, counter.load(Ordering::SeqCst));
+        assert_eq!([0, 3, 6, 9, 12, 15, 18, 21], values);
+    }
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn worker_pool_resumes_panics_after_all_jobs() {
+        let pool = WorkerPool::new(2);
+        let counter = AtomicUsize::new(0);
+
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            let mut a = || panic!("job failed");
+            let mut b = || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            };
+            pool.execute(&mut [&mut a, &mut b]);
+        }));
+        assert!(result.is_err());
+        assert_eq!(1, counter.load(Ordering::SeqCst));
+
+        // The pool is still usable afterwards
+        let mut c = || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        };
+        let mut d = || {
+            counter.fetch_add(1, Ordering::SeqCst);
+        };
+        pool.execute(&mut [&mut c, &mut d]);
+        assert_eq!(3, counter.load(Ordering::SeqCst));
     }
 }
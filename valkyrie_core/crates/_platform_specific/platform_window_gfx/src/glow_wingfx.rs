@@ -8,6 +8,7 @@ use core_simulation::{ControlMessage, Input, Simulation, SimulationExecutor, Win
 use core_window::{Renderable, Window};
 
 use crate::glow_render::make;
+use crate::input::InputTranslator;
 
 pub struct OpenGlWindow {
     title: &'static str,
@@ -20,57 +21,33 @@ impl OpenGlWindow {
         Self { title, w, h }
     }
 
-    fn handle_event<T>(event: Event<T>, control_flow: &mut ControlFlow) -> Option<WindowMsg> {
+    fn handle_event<T>(
+        event: Event<T>,
+        control_flow: &mut ControlFlow,
+        input: &mut InputTranslator,
+        msgs: &mut Vec<WindowMsg>,
+    ) {
         match event {
-            Event::LoopDestroyed => None,
-            Event::MainEventsCleared => Some(WindowMsg::RedrawRequested),
+            Event::LoopDestroyed => {}
+            Event::MainEventsCleared => {
+                #[cfg(feature = "gamepad")]
+                input.poll_gamepads(msgs);
+                msgs.push(WindowMsg::RedrawRequested);
+            }
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(physical_size) => Some(WindowMsg::Resize {
+                WindowEvent::Resized(physical_size) => msgs.push(WindowMsg::Resize {
                     w: physical_size.width,
                     h: physical_size.height,
                 }),
                 WindowEvent::CloseRequested => {
                     *control_flow = ControlFlow::Exit;
-                    Some(WindowMsg::Shutdown)
-                }
-                WindowEvent::KeyboardInput { input, .. } => {
-                    let pressed = match input.state {
-                        glutin::event::ElementState::Pressed => true,
-                        glutin::event::ElementState::Released => false,
-                    };
-
-                    let mut key_msg = core_simulation::KeyboardMsg::W;
-
-                    if let Some(keycode) = input.virtual_keycode {
-                        match keycode {
-                            glutin::event::VirtualKeyCode::W => {
-                                key_msg = core_simulation::KeyboardMsg::W;
-                            }
-                            glutin::event::VirtualKeyCode::A => {
-                                key_msg = core_simulation::KeyboardMsg::A;
-                            }
-                            glutin::event::VirtualKeyCode::S => {
-                                key_msg = core_simulation::KeyboardMsg::S;
-                            }
-                            glutin::event::VirtualKeyCode::D => {
-                                key_msg = core_simulation::KeyboardMsg::D;
-                            }
-                            _ => {}
-                        }
-
-                        if pressed {
-                            return Some(WindowMsg::KeyPress(key_msg));
-                        } else {
-                            return Some(WindowMsg::KeyRelease(key_msg));
-                        }
-                    }
-
-                    None
+                    msgs.push(WindowMsg::Shutdown);
                 }
-                _ => None,
+                event => input.window_event(&event, msgs),
             },
-            Event::RedrawRequested(_) => Some(WindowMsg::RedrawRequested),
-            _ => None,
+            Event::DeviceEvent { event, .. } => input.device_event(&event, msgs),
+            Event::RedrawRequested(_) => msgs.push(WindowMsg::RedrawRequested),
+            _ => {}
         }
     }
 }
@@ -97,48 +74,50 @@ where
 
         let mut renderer =
             core_renderer::make_renderer(Box::new(make(self.w, self.h, &windowed_context)));
+        let mut input = InputTranslator::new();
+        let mut msgs = vec![];
         let mut last_frame = u64::MAX;
+        let mut last_alpha = f32::MAX;
+        executor.enable_interpolation();
 
         el.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
 
-            let ev = Self::handle_event(event, control_flow);
+            Self::handle_event(event, control_flow, &mut input, &mut msgs);
 
-            let ev = match &ev {
-                Some(ev) => {
-                    match ev {
-                        WindowMsg::RedrawRequested => {
-                            renderer.dispatch();
-                            windowed_context.swap_buffers().unwrap();
-                        }
-                        WindowMsg::Shutdown => *control_flow = ControlFlow::Exit,
-                        WindowMsg::Resize { w, h } => {
-                            let w = *w;
-                            let h = *h;
-                            windowed_context.resize(glutin::dpi::PhysicalSize::new(w, h));
-                            renderer.resize(w, h);
-                            windowed_context.window().request_redraw();
-                        }
-                        WindowMsg::KeyPress(_) => {}
-                        WindowMsg::KeyRelease(_) => {}
+            for msg in msgs.drain(..) {
+                match msg {
+                    WindowMsg::RedrawRequested => {
+                        renderer.dispatch();
+                        windowed_context.swap_buffers().unwrap();
                     }
-
-                    Some(Input::WindowMsg(*ev))
+                    WindowMsg::Shutdown => *control_flow = ControlFlow::Exit,
+                    WindowMsg::Resize { w, h } => {
+                        windowed_context.resize(glutin::dpi::PhysicalSize::new(w, h));
+                        renderer.resize(w, h);
+                        windowed_context.window().request_redraw();
+                    }
+                    _ => {}
                 }
-                None => None,
-            };
 
-            match executor.tick(ev) {
+                executor.queue(Input::WindowMsg(msg));
+            }
+
+            match executor.tick(None) {
                 ControlMessage::Ok => {}
                 ControlMessage::ExitSim => {
                     *control_flow = ControlFlow::Exit;
                 }
             }
 
-            // If state was changed update render state + request redraw
-            if executor.last_updated_frame() != last_frame {
+            // If state or the interpolation was changed update render state + request redraw
+            let alpha = executor.alpha();
+            if executor.last_updated_frame() != last_frame || alpha != last_alpha {
                 last_frame = executor.last_updated_frame();
-                executor.sim().render(&mut renderer);
+                last_alpha = alpha;
+                if let Some(previous) = executor.previous_state() {
+                    executor.sim().render(previous, alpha, &mut renderer);
+                }
                 windowed_context.window().request_redraw();
                 // Is the following necessary?
                 // TODO: divorce rendering from execution?
@@ -0,0 +1,498 @@
+// Translates winit + gilrs events into the engine's input messages. Shared by both backends,
+// as glutin re-exports the same winit event types.
+// Gamepads require the `gamepad` feature, as gilrs depends on system libraries (such as libudev) that may not be installed.
+#[cfg(feature = "gamepad")]
+use core_simulation::{GamepadAxis, GamepadButton, GamepadId};
+use core_simulation::{Key, KeyboardMsg, Modifiers, MouseButton, MouseScroll, WindowMsg};
+use winit::event::{
+    DeviceEvent, ElementState, ModifiersState, MouseScrollDelta, VirtualKeyCode, WindowEvent,
+};
+
+/// Tracks the state required to translate events, such as the held modifiers + connected gamepads.
+pub struct InputTranslator {
+    modifiers: Modifiers,
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gilrs::Gilrs>,
+}
+
+impl InputTranslator {
+    pub fn new() -> Self {
+        Self {
+            modifiers: Modifiers::default(),
+            // Gamepads are optional; if the platform doesn't support them, only keyboard + mouse are used.
+            #[cfg(feature = "gamepad")]
+            gamepads: gilrs::Gilrs::new().ok(),
+        }
+    }
+
+    /// Translates a window event, pushing any messages onto `msgs`.
+    pub fn window_event(&mut self, event: &WindowEvent, msgs: &mut Vec<WindowMsg>) {
+        match event {
+            WindowEvent::Focused(focused) => {
+                if *focused {
+                    msgs.push(WindowMsg::Focused);
+                } else {
+                    // Releases won't be received while unfocused
+                    self.modifiers = Modifiers::default();
+                    msgs.push(WindowMsg::Unfocused);
+                }
+            }
+            WindowEvent::KeyboardInput { input, .. } => {
+                let key = KeyboardMsg {
+                    key: input.virtual_keycode.map(key).unwrap_or(Key::Unknown),
+                    scancode: input.scancode,
+                    modifiers: self.modifiers,
+                };
+
+                msgs.push(match input.state {
+                    ElementState::Pressed => WindowMsg::KeyPress(key),
+                    ElementState::Released => WindowMsg::KeyRelease(key),
+                });
+            }
+            WindowEvent::ModifiersChanged(state) => {
+                self.modifiers = modifiers(*state);
+                msgs.push(WindowMsg::ModifiersChanged(self.modifiers));
+            }
+            WindowEvent::ReceivedCharacter(c) => msgs.push(WindowMsg::Text(*c)),
+            WindowEvent::CursorMoved { position, .. } => msgs.push(WindowMsg::MouseMove {
+                x: position.x as f32,
+                y: position.y as f32,
+            }),
+            WindowEvent::CursorEntered { .. } => msgs.push(WindowMsg::MouseEntered),
+            WindowEvent::CursorLeft { .. } => msgs.push(WindowMsg::MouseLeft),
+            WindowEvent::MouseInput { state, button, .. } => {
+                let button = mouse_button(*button);
+                msgs.push(match state {
+                    ElementState::Pressed => WindowMsg::MousePress(button),
+                    ElementState::Released => WindowMsg::MouseRelease(button),
+                });
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let scroll = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => MouseScroll::Lines { x: *x, y: *y },
+                    MouseScrollDelta::PixelDelta(position) => MouseScroll::Pixels {
+                        x: position.x as f32,
+                        y: position.y as f32,
+                    },
+                };
+
+                msgs.push(WindowMsg::MouseWheel(scroll));
+            }
+            _ => {}
+        }
+    }
+
+    /// Translates a raw device event, pushing any messages onto `msgs`.
+    pub fn device_event(&mut self, event: &DeviceEvent, msgs: &mut Vec<WindowMsg>) {
+        if let DeviceEvent::MouseMotion { delta } = event {
+            msgs.push(WindowMsg::MouseMotion {
+                dx: delta.0 as f32,
+                dy: delta.1 as f32,
+            });
+        }
+    }
+
+    /// Polls all pending gamepad events, pushing any messages onto `msgs`.
+    #[cfg(feature = "gamepad")]
+    pub fn poll_gamepads(&mut self, msgs: &mut Vec<WindowMsg>) {
+        let gamepads = match self.gamepads.as_mut() {
+            Some(gamepads) => gamepads,
+            None => return,
+        };
+
+        while let Some(gilrs::Event { id, event, .. }) = gamepads.next_event() {
+            let gamepad = GamepadId(usize::from(id) as u32);
+
+            let msg = match event {
+                gilrs::EventType::Connected => WindowMsg::GamepadConnected(gamepad),
+                gilrs::EventType::Disconnected => WindowMsg::GamepadDisconnected(gamepad),
+                gilrs::EventType::ButtonPressed(button, _) => WindowMsg::GamepadPress {
+                    gamepad,
+                    button: gamepad_button(button),
+                },
+                gilrs::EventType::ButtonReleased(button, _) => WindowMsg::GamepadRelease {
+                    gamepad,
+                    button: gamepad_button(button),
+                },
+                gilrs::EventType::AxisChanged(axis, value, _) => WindowMsg::GamepadAxis {
+                    gamepad,
+                    axis: gamepad_axis(axis),
+                    value,
+                },
+                // Analog triggers are reported as button values
+                gilrs::EventType::ButtonChanged(gilrs::Button::LeftTrigger2, value, _) => {
+                    WindowMsg::GamepadAxis {
+                        gamepad,
+                        axis: GamepadAxis::LeftTrigger,
+                        value,
+                    }
+                }
+                gilrs::EventType::ButtonChanged(gilrs::Button::RightTrigger2, value, _) => {
+                    WindowMsg::GamepadAxis {
+                        gamepad,
+                        axis: GamepadAxis::RightTrigger,
+                        value,
+                    }
+                }
+                _ => continue,
+            };
+
+            msgs.push(msg);
+        }
+    }
+}
+
+fn modifiers(state: ModifiersState) -> Modifiers {
+    Modifiers {
+        shift: state.shift(),
+        ctrl: state.ctrl(),
+        alt: state.alt(),
+        logo: state.logo(),
+    }
+}
+
+fn mouse_button(button: winit::event::MouseButton) -> MouseButton {
+    match button {
+        winit::event::MouseButton::Left => MouseButton::Left,
+        winit::event::MouseButton::Right => MouseButton::Right,
+        winit::event::MouseButton::Middle => MouseButton::Middle,
+        winit::event::MouseButton::Other(button) => MouseButton::Other(button),
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn gamepad_button(button: gilrs::Button) -> GamepadButton {
+    use gilrs::Button;
+    match button {
+        Button::South => GamepadButton::South,
+        Button::East => GamepadButton::East,
+        Button::North => GamepadButton::North,
+        Button::West => GamepadButton::West,
+        Button::LeftTrigger => GamepadButton::LeftBumper,
+        Button::LeftTrigger2 => GamepadButton::LeftTrigger,
+        Button::RightTrigger => GamepadButton::RightBumper,
+        Button::RightTrigger2 => GamepadButton::RightTrigger,
+        Button::Select => GamepadButton::Select,
+        Button::Start => GamepadButton::Start,
+        Button::Mode => GamepadButton::Mode,
+        Button::LeftThumb => GamepadButton::LeftStick,
+        Button::RightThumb => GamepadButton::RightStick,
+        Button::DPadUp => GamepadButton::DPadUp,
+        Button::DPadDown => GamepadButton::DPadDown,
+        Button::DPadLeft => GamepadButton::DPadLeft,
+        Button::DPadRight => GamepadButton::DPadRight,
+        _ => GamepadButton::Unknown,
+    }
+}
+
+#[cfg(feature = "gamepad")]
+fn gamepad_axis(axis: gilrs::Axis) -> GamepadAxis {
+    use gilrs::Axis;
+    match axis {
+        Axis::LeftStickX => GamepadAxis::LeftStickX,
+        Axis::LeftStickY => GamepadAxis::LeftStickY,
+        Axis::RightStickX => GamepadAxis::RightStickX,
+        Axis::RightStickY => GamepadAxis::RightStickY,
+        Axis::LeftZ => GamepadAxis::LeftTrigger,
+        Axis::RightZ => GamepadAxis::RightTrigger,
+        Axis::DPadX => GamepadAxis::DPadX,
+        Axis::DPadY => GamepadAxis::DPadY,
+        _ => GamepadAxis::Unknown,
+    }
+}
+
+fn key(keycode: VirtualKeyCode) -> Key {
+    use VirtualKeyCode as Vk;
+    match keycode {
+        Vk::Key1 => Key::Num1,
+        Vk::Key2 => Key::Num2,
+        Vk::Key3 => Key::Num3,
+        Vk::Key4 => Key::Num4,
+        Vk::Key5 => Key::Num5,
+        Vk::Key6 => Key::Num6,
+        Vk::Key7 => Key::Num7,
+        Vk::Key8 => Key::Num8,
+        Vk::Key9 => Key::Num9,
+        Vk::Key0 => Key::Num0,
+
+        Vk::A => Key::A,
+        Vk::B => Key::B,
+        Vk::C => Key::C,
+        Vk::D => Key::D,
+        Vk::E => Key::E,
+        Vk::F => Key::F,
+        Vk::G => Key::G,
+        Vk::H => Key::H,
+        Vk::I => Key::I,
+        Vk::J => Key::J,
+        Vk::K => Key::K,
+        Vk::L => Key::L,
+        Vk::M => Key::M,
+        Vk::N => Key::N,
+        Vk::O => Key::O,
+        Vk::P => Key::P,
+        Vk::Q => Key::Q,
+        Vk::R => Key::R,
+        Vk::S => Key::S,
+        Vk::T => Key::T,
+        Vk::U => Key::U,
+        Vk::V => Key::V,
+        Vk::W => Key::W,
+        Vk::X => Key::X,
+        Vk::Y => Key::Y,
+        Vk::Z => Key::Z,
+
+        Vk::Escape => Key::Escape,
+        Vk::F1 => Key::F1,
+        Vk::F2 => Key::F2,
+        Vk::F3 => Key::F3,
+        Vk::F4 => Key::F4,
+        Vk::F5 => Key::F5,
+        Vk::F6 => Key::F6,
+        Vk::F7 => Key::F7,
+        Vk::F8 => Key::F8,
+        Vk::F9 => Key::F9,
+        Vk::F10 => Key::F10,
+        Vk::F11 => Key::F11,
+        Vk::F12 => Key::F12,
+        Vk::F13 => Key::F13,
+        Vk::F14 => Key::F14,
+        Vk::F15 => Key::F15,
+        Vk::F16 => Key::F16,
+        Vk::F17 => Key::F17,
+        Vk::F18 => Key::F18,
+        Vk::F19 => Key::F19,
+        Vk::F20 => Key::F20,
+        Vk::F21 => Key::F21,
+        Vk::F22 => Key::F22,
+        Vk::F23 => Key::F23,
+        Vk::F24 => Key::F24,
+
+        Vk::Snapshot | Vk::Sysrq => Key::PrintScreen,
+        Vk::Scroll => Key::ScrollLock,
+        Vk::Pause => Key::Pause,
+
+        Vk::Insert => Key::Insert,
+        Vk::Home => Key::Home,
+        Vk::Delete => Key::Delete,
+        Vk::End => Key::End,
+        Vk::PageDown => Key::PageDown,
+        Vk::PageUp => Key::PageUp,
+
+        Vk::Left => Key::Left,
+        Vk::Up => Key::Up,
+        Vk::Right => Key::Right,
+        Vk::Down => Key::Down,
+
+        Vk::Back => Key::Backspace,
+        Vk::Return => Key::Enter,
+        Vk::Space => Key::Space,
+        Vk::Tab => Key::Tab,
+        Vk::Capital => Key::CapsLock,
+
+        Vk::Numlock => Key::NumLock,
+        Vk::Numpad0 => Key::Numpad0,
+        Vk::Numpad1 => Key::Numpad1,
+        Vk::Numpad2 => Key::Numpad2,
+        Vk::Numpad3 => Key::Numpad3,
+        Vk::Numpad4 => Key::Numpad4,
+        Vk::Numpad5 => Key::Numpad5,
+        Vk::Numpad6 => Key::Numpad6,
+        Vk::Numpad7 => Key::Numpad7,
+        Vk::Numpad8 => Key::Numpad8,
+        Vk::Numpad9 => Key::Numpad9,
+        Vk::NumpadAdd => Key::NumpadAdd,
+        Vk::NumpadSubtract => Key::NumpadSubtract,
+        Vk::NumpadMultiply => Key::NumpadMultiply,
+        Vk::NumpadDivide => Key::NumpadDivide,
+        Vk::NumpadDecimal => Key::NumpadDecimal,
+        Vk::NumpadComma => Key::NumpadComma,
+        Vk::NumpadEnter => Key::NumpadEnter,
+        Vk::NumpadEquals => Key::NumpadEquals,
+
+        Vk::Apostrophe => Key::Apostrophe,
+        Vk::Backslash => Key::Backslash,
+        Vk::Comma => Key::Comma,
+        Vk::Equals => Key::Equals,
+        Vk::Grave => Key::Grave,
+        Vk::LBracket => Key::LBracket,
+        Vk::Minus => Key::Minus,
+        Vk::Period => Key::Period,
+        Vk::RBracket => Key::RBracket,
+        Vk::Semicolon => Key::Semicolon,
+        Vk::Slash => Key::Slash,
+
+        Vk::LAlt => Key::LAlt,
+        Vk::LControl => Key::LControl,
+        Vk::LShift => Key::LShift,
+        Vk::LWin => Key::LLogo,
+        Vk::RAlt => Key::RAlt,
+        Vk::RControl => Key::RControl,
+        Vk::RShift => Key::RShift,
+        Vk::RWin => Key::RLogo,
+        Vk::Apps => Key::Menu,
+
+        Vk::Mute => Key::Mute,
+        Vk::VolumeDown => Key::VolumeDown,
+        Vk::VolumeUp => Key::VolumeUp,
+        Vk::PlayPause => Key::PlayPause,
+        Vk::MediaStop => Key::MediaStop,
+        Vk::NextTrack => Key::NextTrack,
+        Vk::PrevTrack => Key::PrevTrack,
+
+        Vk::Copy => Key::Copy,
+        Vk::Cut => Key::Cut,
+        Vk::Paste => Key::Paste,
+
+        _ => Key::Unknown,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translator() -> InputTranslator {
+        // Skip gilrs so tests don't depend on the platform's gamepad support
+        InputTranslator {
+            modifiers: Modifiers::default(),
+            #[cfg(feature = "gamepad")]
+            gamepads: None,
+        }
+    }
+
+    #[allow(deprecated)]
+    fn key_press(keycode: VirtualKeyCode, scancode: u32) -> WindowEvent<'static> {
+        WindowEvent::KeyboardInput {
+            device_id: unsafe { winit::event::DeviceId::dummy() },
+            input: winit::event::KeyboardInput {
+                scancode,
+                state: ElementState::Pressed,
+                virtual_keycode: Some(keycode),
+                modifiers: ModifiersState::empty(),
+            },
+            is_synthetic: false,
+        }
+    }
+
+    #[test]
+    fn key_maps_virtual_keycodes() {
+        assert_eq!(Key::Num1, key(VirtualKeyCode::Key1));
+        assert_eq!(Key::A, key(VirtualKeyCode::A));
+        assert_eq!(Key::F24, key(VirtualKeyCode::F24));
+        assert_eq!(Key::PrintScreen, key(VirtualKeyCode::Snapshot));
+        assert_eq!(Key::PrintScreen, key(VirtualKeyCode::Sysrq));
+        assert_eq!(Key::Backspace, key(VirtualKeyCode::Back));
+        assert_eq!(Key::Enter, key(VirtualKeyCode::Return));
+        assert_eq!(Key::CapsLock, key(VirtualKeyCode::Capital));
+        assert_eq!(Key::NumpadEnter, key(VirtualKeyCode::NumpadEnter));
+        assert_eq!(Key::LLogo, key(VirtualKeyCode::LWin));
+        assert_eq!(Key::RLogo, key(VirtualKeyCode::RWin));
+        assert_eq!(Key::Menu, key(VirtualKeyCode::Apps));
+        assert_eq!(Key::Unknown, key(VirtualKeyCode::Kana));
+    }
+
+    #[test]
+    fn mouse_button_maps_buttons() {
+        use winit::event::MouseButton as WinitButton;
+        assert_eq!(MouseButton::Left, mouse_button(WinitButton::Left));
+        assert_eq!(MouseButton::Right, mouse_button(WinitButton::Right));
+        assert_eq!(MouseButton::Middle, mouse_button(WinitButton::Middle));
+        assert_eq!(MouseButton::Other(4), mouse_button(WinitButton::Other(4)));
+    }
+
+    #[test]
+    #[cfg(feature = "gamepad")]
+    fn gamepad_button_maps_by_position() {
+        use gilrs::Button;
+        assert_eq!(GamepadButton::South, gamepad_button(Button::South));
+        assert_eq!(
+            GamepadButton::LeftBumper,
+            gamepad_button(Button::LeftTrigger)
+        );
+        assert_eq!(
+            GamepadButton::LeftTrigger,
+            gamepad_button(Button::LeftTrigger2)
+        );
+        assert_eq!(
+            GamepadButton::RightBumper,
+            gamepad_button(Button::RightTrigger)
+        );
+        assert_eq!(
+            GamepadButton::RightTrigger,
+            gamepad_button(Button::RightTrigger2)
+        );
+        assert_eq!(GamepadButton::LeftStick, gamepad_button(Button::LeftThumb));
+        assert_eq!(GamepadButton::DPadRight, gamepad_button(Button::DPadRight));
+        assert_eq!(GamepadButton::Unknown, gamepad_button(Button::C));
+    }
+
+    #[test]
+    #[cfg(feature = "gamepad")]
+    fn gamepad_axis_maps_axes() {
+        use gilrs::Axis;
+        assert_eq!(GamepadAxis::LeftStickX, gamepad_axis(Axis::LeftStickX));
+        assert_eq!(GamepadAxis::RightStickY, gamepad_axis(Axis::RightStickY));
+        assert_eq!(GamepadAxis::LeftTrigger, gamepad_axis(Axis::LeftZ));
+        assert_eq!(GamepadAxis::RightTrigger, gamepad_axis(Axis::RightZ));
+        assert_eq!(GamepadAxis::DPadY, gamepad_axis(Axis::DPadY));
+        assert_eq!(GamepadAxis::Unknown, gamepad_axis(Axis::Unknown));
+    }
+
+    #[test]
+    fn window_event_tracks_modifiers() {
+        let mut translator = translator();
+        let mut msgs = vec![];
+
+        translator.window_event(
+            &WindowEvent::ModifiersChanged(ModifiersState::SHIFT | ModifiersState::CTRL),
+            &mut msgs,
+        );
+        translator.window_event(&key_press(VirtualKeyCode::W, 17), &mut msgs);
+
+        let held = Modifiers {
+            shift: true,
+            ctrl: true,
+            alt: false,
+            logo: false,
+        };
+        assert_eq!(
+            vec![
+                WindowMsg::ModifiersChanged(held),
+                WindowMsg::KeyPress(KeyboardMsg {
+                    key: Key::W,
+                    scancode: 17,
+                    modifiers: held,
+                }),
+            ],
+            msgs
+        );
+    }
+
+    #[test]
+    fn window_event_resets_modifiers_when_unfocused() {
+        let mut translator = translator();
+        let mut msgs = vec![];
+
+        translator.window_event(
+            &WindowEvent::ModifiersChanged(ModifiersState::ALT),
+            &mut msgs,
+        );
+        translator.window_event(&WindowEvent::Focused(false), &mut msgs);
+        translator.window_event(&WindowEvent::Focused(true), &mut msgs);
+        translator.window_event(&key_press(VirtualKeyCode::A, 30), &mut msgs);
+
+        assert_eq!(
+            &[
+                WindowMsg::Unfocused,
+                WindowMsg::Focused,
+                WindowMsg::KeyPress(KeyboardMsg {
+                    key: Key::A,
+                    scancode: 30,
+                    modifiers: Modifiers::default(),
+                }),
+            ],
+            &msgs[1..]
+        );
+    }
+}
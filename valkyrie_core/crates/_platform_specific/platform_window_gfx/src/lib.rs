@@ -5,4 +5,5 @@ pub use glow_wingfx::OpenGlWindow;
 pub use wgpu_wingfx::WgpuWindow;
 
 mod glow_render;
+mod input;
 mod wgpu_render;
@@ -9,6 +9,8 @@ use core_renderer::{BackendRenderer, Renderer};
 use core_simulation::{ControlMessage, Input, Simulation, SimulationExecutor, WindowMsg};
 use core_window::{Renderable, Window};
 
+use crate::input::InputTranslator;
+
 pub struct WgpuWindow {
     title: &'static str,
     w: u32,
@@ -20,23 +22,33 @@ impl WgpuWindow {
         Self { title, w, h }
     }
 
-    fn handle_event<T>(event: Event<T>, control_flow: &mut ControlFlow) -> Option<WindowMsg> {
+    fn handle_event<T>(
+        event: Event<T>,
+        control_flow: &mut ControlFlow,
+        input: &mut InputTranslator,
+        msgs: &mut Vec<WindowMsg>,
+    ) {
         match event {
-            Event::LoopDestroyed => None,
-            Event::MainEventsCleared => Some(WindowMsg::RedrawRequested),
+            Event::LoopDestroyed => {}
+            Event::MainEventsCleared => {
+                #[cfg(feature = "gamepad")]
+                input.poll_gamepads(msgs);
+                msgs.push(WindowMsg::RedrawRequested);
+            }
             Event::WindowEvent { event, .. } => match event {
-                WindowEvent::Resized(physical_size) => Some(WindowMsg::Resize {
+                WindowEvent::Resized(physical_size) => msgs.push(WindowMsg::Resize {
                     w: physical_size.width,
                     h: physical_size.height,
                 }),
                 WindowEvent::CloseRequested => {
                     *control_flow = ControlFlow::Exit;
-                    Some(WindowMsg::Shutdown)
+                    msgs.push(WindowMsg::Shutdown);
                 }
-                _ => None,
+                event => input.window_event(&event, msgs),
             },
-            Event::RedrawRequested(_) => Some(WindowMsg::RedrawRequested),
-            _ => None,
+            Event::DeviceEvent { event, .. } => input.device_event(&event, msgs),
+            Event::RedrawRequested(_) => msgs.push(WindowMsg::RedrawRequested),
+            _ => {}
         }
     }
 }
@@ -57,45 +69,49 @@ where
 
         let mut renderer = core_renderer::make_renderer(Box::new(wgpu_render::make()));
 
+        let mut input = InputTranslator::new();
+        let mut msgs = vec![];
         let mut last_frame = u64::MAX;
+        let mut last_alpha = f32::MAX;
+        executor.enable_interpolation();
 
         event_loop.run(move |event, _, control_flow| {
             *control_flow = ControlFlow::Poll;
 
-            let ev = Self::handle_event(event, control_flow);
+            Self::handle_event(event, control_flow, &mut input, &mut msgs);
 
-            let ev = match &ev {
-                Some(ev) => {
-                    match ev {
-                        WindowMsg::RedrawRequested => {
-                            renderer.dispatch();
-                            window.request_redraw();
-                        }
-                        WindowMsg::Shutdown => *control_flow = ControlFlow::Exit,
-                        WindowMsg::Resize { w, h } => {
-                            //windowed_context.resize(glutin::dpi::PhysicalSize::new(*w, *h));
-                            //windowed_context.window().request_redraw();
-                        }
-                        WindowMsg::KeyPress(_) => {}
-                        WindowMsg::KeyRelease(_) => {}
+            for msg in msgs.drain(..) {
+                match msg {
+                    WindowMsg::RedrawRequested => {
+                        renderer.dispatch();
+                        window.request_redraw();
                     }
-
-                    Some(Input::WindowMsg(*ev))
+                    WindowMsg::Shutdown => *control_flow = ControlFlow::Exit,
+                    WindowMsg::Resize { w, h } => {
+                        //windowed_context.resize(glutin::dpi::PhysicalSize::new(w, h));
+                        //windowed_context.window().request_redraw();
+                    }
+                    _ => {}
                 }
-                None => None,
-            };
 
-            match executor.tick(ev) {
+                executor.queue(Input::WindowMsg(msg));
+            }
+
+            match executor.tick(None) {
                 ControlMessage::Ok => {}
                 ControlMessage::ExitSim => {
                     *control_flow = ControlFlow::Exit;
                 }
             }
 
-            // If state was changed update render state + request redraw
-            if executor.last_updated_frame() != last_frame {
+            // If state or the interpolation was changed update render state + request redraw
+            let alpha = executor.alpha();
+            if executor.last_updated_frame() != last_frame || alpha != last_alpha {
                 last_frame = executor.last_updated_frame();
-                executor.sim().render(&mut renderer);
+                last_alpha = alpha;
+                if let Some(previous) = executor.previous_state() {
+                    executor.sim().render(previous, alpha, &mut renderer);
+                }
             }
         });
     }
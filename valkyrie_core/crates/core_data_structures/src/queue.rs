@@ -32,6 +32,11 @@ impl<T> Queue<T> {
         }
     }
 
+    /// Removes all items from the queue, returning them in order.
+    pub fn drain(&mut self) -> std::vec::Drain<'_, T> {
+        self.items.drain(..)
+    }
+
     /// Clears the collection.
     pub fn clear(&mut self) {
         self.items.clear();
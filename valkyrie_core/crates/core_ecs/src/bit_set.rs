@@ -0,0 +1,122 @@
+// Fixed size bitset, used to track membership + changes for tag components. One bit per entity id.
+// The buffer is allocated up front. Only the words up to the highest bit set since the last clear are touched when
+// clearing or copying, so large, mostly empty, sets stay cheap to reset every tick.
+
+const WORD_BITS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct BitSet {
+    words: Vec<u64>,
+    /// The number of words that may have bits set.
+    used: usize,
+}
+
+impl BitSet {
+    /// Creates a new bitset with room for the given number of bits.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            words: vec![0; capacity.div_ceil(WORD_BITS)],
+            used: 0,
+        }
+    }
+
+    /// Returns whether the given bit is set.
+    pub fn contains(&self, index: usize) -> bool {
+        match self.words.get(index / WORD_BITS) {
+            Some(word) => word & (1 << (index % WORD_BITS)) != 0,
+            None => false,
+        }
+    }
+
+    /// Sets the given bit. Returns whether it was newly set. Panics if the index is past the capacity.
+    pub fn insert(&mut self, index: usize) -> bool {
+        let word_index = index / WORD_BITS;
+        let bit = 1 << (index % WORD_BITS);
+        let word = &mut self.words[word_index];
+        let inserted = *word & bit == 0;
+        *word |= bit;
+
+        self.used = self.used.max(word_index + 1);
+        inserted
+    }
+
+    /// Clears the given bit. Returns whether it was set.
+    pub fn remove(&mut self, index: usize) -> bool {
+        match self.words.get_mut(index / WORD_BITS) {
+            Some(word) => {
+                let bit = 1 << (index % WORD_BITS);
+                let removed = *word & bit != 0;
+                *word &= !bit;
+                removed
+            }
+            None => false,
+        }
+    }
+
+    /// Sets every bit that is set in the other bitset.
+    pub fn union_with(&mut self, other: &BitSet) {
+        for (word, other) in self.words[0..other.used].iter_mut().zip(other.words.iter()) {
+            *word |= other;
+        }
+
+        self.used = self.used.max(other.used);
+    }
+
+    /// Clears all bits, keeping the buffer.
+    pub fn clear(&mut self) {
+        for word in self.words[0..self.used].iter_mut() {
+            *word = 0;
+        }
+
+        self.used = 0;
+    }
+
+    /// Copies the other bitset into this one, reusing the buffer. Both must have the same capacity.
+    pub fn copy_from(&mut self, other: &BitSet) {
+        let used = self.used.max(other.used);
+        self.words[0..used].copy_from_slice(&other.words[0..used]);
+        self.used = other.used;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_set_insert_remove() {
+        let mut set = BitSet::new(300);
+        assert!(!set.contains(3));
+        assert!(set.insert(3));
+        assert!(!set.insert(3));
+        assert!(set.contains(3));
+
+        assert!(set.insert(200));
+        assert!(set.contains(200));
+        assert!(!set.contains(199));
+        assert!(!set.contains(10_000));
+
+        let mut other = BitSet::new(300);
+        other.insert(5);
+        other.union_with(&set);
+        assert!(other.contains(3) && other.contains(5) && other.contains(200));
+
+        assert!(set.remove(3));
+        assert!(!set.remove(3));
+        assert!(!set.remove(10_000));
+        set.clear();
+        assert!(!set.contains(200));
+
+        // Copying clears bits the other set doesn't have
+        other.copy_from(&set);
+        assert!(!other.contains(5));
+        assert!(!other.contains(200));
+    }
+
+    #[test]
+    #[should_panic]
+    fn bit_set_does_not_grow() {
+        let mut set = BitSet::new(64);
+        set.insert(64);
+    }
+}
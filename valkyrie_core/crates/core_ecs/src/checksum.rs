@@ -0,0 +1,229 @@
+// Checksums of world state, used to detect when lockstep peers or replays desync.
+// Components opt in by implementing `HashComponent`, usually through `#[derive(HashComponent)]`, and being registered
+// with `World::register_checksum()`.
+// Each entity is hashed on its own with its components in registration order, then entity hashes are summed so the
+// result doesn't depend on the order entities are stored in. Hashing is FNV-1a with little endian integers so
+// results match across platforms.
+use std::hash::Hasher;
+
+use crate::{Component, Entity, ResourceId, World};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// A component that can be included in a world checksum.
+pub trait HashComponent: Component {
+    /// Feeds the component into the checksum. Must give the same result on every platform, so floats should be hashed
+    /// through `to_bits()`.
+    fn hash_component(&self, checksum: &mut Checksum);
+}
+
+/// A deterministic, platform independent, hasher.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Checksum {
+    hash: u64,
+}
+
+impl Checksum {
+    /// Creates a new checksum.
+    pub fn new() -> Self {
+        Self { hash: FNV_OFFSET }
+    }
+}
+
+impl Default for Checksum {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for Checksum {
+    fn finish(&self) -> u64 {
+        self.hash
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.hash ^= *byte as u64;
+            self.hash = self.hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_u128(&mut self, i: u128) {
+        self.write(&i.to_le_bytes());
+    }
+
+    fn write_usize(&mut self, i: usize) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i16(&mut self, i: i16) {
+        self.write_u16(i as u16);
+    }
+
+    fn write_i32(&mut self, i: i32) {
+        self.write_u32(i as u32);
+    }
+
+    fn write_i64(&mut self, i: i64) {
+        self.write_u64(i as u64);
+    }
+
+    fn write_i128(&mut self, i: i128) {
+        self.write_u128(i as u128);
+    }
+
+    fn write_isize(&mut self, i: isize) {
+        self.write_u64(i as u64);
+    }
+}
+
+pub(crate) type ChecksumFn = fn(&World, Entity, &mut Checksum);
+
+fn hash_component<C>(world: &World, entity: Entity, checksum: &mut Checksum)
+where
+    C: HashComponent,
+{
+    match world.get::<C>(entity) {
+        Some(component) => {
+            checksum.write_u8(1);
+            component.hash_component(checksum);
+        }
+        None => checksum.write_u8(0),
+    }
+}
+
+impl World {
+    /// Includes the given component in checksums. Peers must register components in the same order.
+    /// Registering the same component twice does nothing.
+    pub fn register_checksum<C>(&mut self)
+    where
+        C: HashComponent,
+    {
+        let id = ResourceId::from::<C>();
+        if !self.checksums.iter().any(|(existing, _)| *existing == id) {
+            self.checksums.push((id, hash_component::<C>));
+        }
+    }
+
+    /// Returns a checksum of all alive entities + their registered components.
+    pub fn checksum(&self) -> u64 {
+        let mut total: u64 = 0;
+        for entity in self.entities() {
+            let mut checksum = Checksum::new();
+            checksum.write_u32(entity.id());
+            checksum.write_u8(entity.generation());
+            for (_, hash) in self.checksums.iter() {
+                hash(self, *entity, &mut checksum);
+            }
+
+            total = total.wrapping_add(checksum.finish());
+        }
+
+        total
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::HashComponent;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Hash, HashComponent)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position(f32);
+    impl Component for Position {}
+    impl HashComponent for Position {
+        fn hash_component(&self, checksum: &mut Checksum) {
+            checksum.write_u32(self.0.to_bits());
+        }
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Health>(4);
+        world.register::<Position>(4);
+        world.register_checksum::<Health>();
+        world.register_checksum::<Position>();
+        world
+    }
+
+    #[test]
+    fn checksum_is_stable() {
+        // FNV-1a of "a" is a known value
+        let mut checksum = Checksum::new();
+        checksum.write(b"a");
+        assert_eq!(0xaf63_dc4c_8601_ec8c, checksum.finish());
+
+        let mut checksum = Checksum::new();
+        checksum.write_u32(1);
+        let mut expected = Checksum::new();
+        expected.write(&[1, 0, 0, 0]);
+        assert_eq!(expected, checksum);
+    }
+
+    #[test]
+    fn world_checksum_detects_changes() {
+        let mut a = world();
+        let mut b = world();
+        assert_eq!(a.checksum(), b.checksum());
+
+        for world in [&mut a, &mut b] {
+            let e = world.add_entity();
+            world.insert(e, Health(3)).unwrap();
+            world.insert(e, Position(1.5)).unwrap();
+            world.add_entity();
+        }
+        assert_eq!(a.checksum(), b.checksum());
+
+        let e = b.entities()[0];
+        b.get_mut::<Position>(e).unwrap().0 = 1.25;
+        assert_ne!(a.checksum(), b.checksum());
+
+        b.get_mut::<Position>(e).unwrap().0 = 1.5;
+        assert_eq!(a.checksum(), b.checksum());
+
+        // Moving a component to another entity is a change
+        let other = b.entities()[1];
+        b.remove::<Health>(e);
+        b.insert(other, Health(3)).unwrap();
+        assert_ne!(a.checksum(), b.checksum());
+    }
+
+    #[test]
+    fn world_checksum_ignores_storage_order() {
+        let mut a = world();
+        let mut b = world();
+        let (a1, a2) = (a.add_entity(), a.add_entity());
+        let (b1, b2) = (b.add_entity(), b.add_entity());
+
+        a.insert(a1, Health(1)).unwrap();
+        a.insert(a2, Health(2)).unwrap();
+        b.insert(b2, Health(2)).unwrap();
+        b.insert(b1, Health(1)).unwrap();
+        assert_eq!(a.checksum(), b.checksum());
+
+        // Unregistered components aren't included
+        let mut c = World::new();
+        c.register::<Health>(4);
+        let (c1, c2) = (c.add_entity(), c.add_entity());
+        c.insert(c1, Health(1)).unwrap();
+        c.insert(c2, Health(2)).unwrap();
+        assert_ne!(a.checksum(), c.checksum());
+    }
+}
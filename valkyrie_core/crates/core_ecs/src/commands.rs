@@ -0,0 +1,183 @@
+// Command buffers record changes to the world so they can be applied later, such as after iterating a query.
+// Entities are reserved when spawned, so the handles can be used immediately and stored in components.
+use crate::{entity_manager::EntityReserver, Component, Entity, World, WorldError};
+
+type AddCommand = Box<dyn FnOnce(&mut World) -> Result<(), WorldError>>;
+
+enum Command {
+    Kill(Entity),
+    Add(AddCommand),
+    Remove(fn(&mut World, Entity), Entity),
+}
+
+/// A buffer of deferred changes to the world. Created through `World::commands()`.
+pub struct Commands {
+    reserver: EntityReserver,
+    commands: Vec<Command>,
+}
+
+impl Commands {
+    /// Creates a new command buffer.
+    pub(crate) fn new(reserver: EntityReserver) -> Self {
+        Self {
+            reserver,
+            commands: vec![],
+        }
+    }
+
+    /// Spawns a new entity. The entity becomes alive when the buffer is applied, but the handle is valid straight away.
+    pub fn spawn(&mut self) -> Entity {
+        self.reserver.reserve()
+    }
+
+    /// Kills the given entity.
+    pub fn kill(&mut self, entity: Entity) {
+        self.commands.push(Command::Kill(entity));
+    }
+
+    /// Adds the component to the given entity.
+    pub fn add<C>(&mut self, entity: Entity, component: C)
+    where
+        C: Component,
+    {
+        self.commands.push(Command::Add(Box::new(move |world| {
+            world.insert(entity, component)?;
+            Ok(())
+        })));
+    }
+
+    /// Removes the component from the given entity.
+    pub fn remove<C>(&mut self, entity: Entity)
+    where
+        C: Component,
+    {
+        self.commands
+            .push(Command::Remove(World::remove::<C>, entity));
+    }
+
+    /// Returns the number of queued commands.
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    /// Returns whether there are no queued commands.
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Applies all commands to the world in the order they were recorded, leaving the buffer empty so it may be reused.
+    /// Failed commands don't stop the rest from applying; the first error is returned.
+    pub fn apply(&mut self, world: &mut World) -> Result<(), WorldError> {
+        world.flush_reserved();
+
+        let mut result = Ok(());
+        for command in self.commands.drain(..) {
+            let command_result = match command {
+                Command::Kill(entity) => {
+                    world.kill(entity);
+                    Ok(())
+                }
+                Command::Add(add) => add(world),
+                Command::Remove(remove, entity) => {
+                    remove(world, entity);
+                    Ok(())
+                }
+            };
+
+            if result.is_ok() {
+                result = command_result;
+            }
+        }
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Bullet {
+        owner: Option<Entity>,
+    }
+    impl Component for Bullet {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Bullet>(100);
+        world.register::<Health>(100);
+        world
+    }
+
+    #[test]
+    fn commands_spawn_and_kill_while_iterating() {
+        let mut world = world();
+        for _ in 0..3 {
+            let ship = world.add_entity();
+            world.add::<Health>(ship).unwrap().0 = 1;
+        }
+
+        let mut commands = world.commands();
+        let mut bullets = vec![];
+        for (ship, health) in world.query::<(Entity, &mut Health)>() {
+            // Handles may be used straight away
+            let bullet = commands.spawn();
+            commands.add(bullet, Bullet { owner: Some(ship) });
+            bullets.push((bullet, ship));
+
+            health.0 -= 1;
+            if health.0 == 0 {
+                commands.remove::<Health>(ship);
+                commands.kill(ship);
+            }
+        }
+
+        assert_eq!(3, world.entity_len());
+        assert!(!world.is_alive(bullets[0].0));
+
+        commands.apply(&mut world).unwrap();
+        assert!(commands.is_empty());
+        assert_eq!(3, world.entity_len());
+        for (bullet, ship) in bullets {
+            assert!(world.is_alive(bullet));
+            assert!(!world.is_alive(ship));
+            assert_eq!(Some(ship), world.get::<Bullet>(bullet).unwrap().owner);
+        }
+    }
+
+    #[test]
+    fn commands_reserve_alongside_world() {
+        let mut world = world();
+        let mut commands = world.commands();
+
+        let reserved = commands.spawn();
+        let added = world.add_entity();
+        assert_ne!(reserved.id(), added.id());
+
+        // Adding entities to the world flushes the reserved ones
+        assert!(world.is_alive(reserved));
+        assert_eq!(vec![reserved, added], world.entities().to_vec());
+
+        // Dead entities and unregistered components return errors, but don't stop the rest
+        #[derive(Copy, Clone, PartialEq, Default, Debug)]
+        struct Unregistered;
+        impl Component for Unregistered {}
+
+        commands.kill(added);
+        commands.add(added, Health(1));
+        commands.add(reserved, Unregistered);
+        commands.add(reserved, Health(2));
+        assert_eq!(4, commands.len());
+        assert!(matches!(
+            commands.apply(&mut world),
+            Err(WorldError::EntityNotAlive)
+        ));
+        assert_eq!(Some(&Health(2)), world.get::<Health>(reserved));
+        assert!(!world.is_alive(added));
+    }
+}
@@ -1,27 +1,95 @@
 // Based on http://bitsquid.blogspot.com/2014/09/building-data-oriented-entity-system.html
-use crate::{entity::MAX_ENTITIES, entity_manager::EntityManager, Entity};
+// Stored as a sparse set: a paged sparse array maps entity ids to dense indexes, while the dense arrays hold the
+// components + a back reference to the owning entity. This keeps add + remove O(1) and memory proportional to use.
+// Each component also tracks the tick it was added + last mutably accessed on, for change detection.
+// Zero sized components, such as markers, are tags: they only need to know which entities have them, so membership
+// is kept in a bitset and the dense arrays aren't used. Instead of per entity ticks, they track which entities were
+// added + changed since trackers were last cleared in two more bitsets. All three are sized for every entity id up
+// front, so adding a tag never allocates.
+use crate::{
+    bit_set::BitSet, entity::MAX_ENTITIES, sparse_array::SparseArray, Component, Entity, EntityMap,
+    Reflect,
+};
 
 #[derive(PartialEq, Debug)]
 pub enum ComponentStoreError {
     BufferOverflow,
 }
 
-pub trait BackingComponentStore: std::fmt::Debug {
+pub trait BackingComponentStore: std::fmt::Debug + Send + Sync {
+    fn contains(&self, entity: Entity) -> bool;
+    /// Returns how many more components can be added before the store is full.
+    fn available(&self) -> usize;
     fn destroy(&mut self, entity_to_destroy: Entity);
+    fn copy_entity(
+        &self,
+        entity: Entity,
+        target: &mut dyn BackingComponentStore,
+        target_entity: Entity,
+        map: &EntityMap,
+    ) -> Result<(), ComponentStoreError>;
+    fn clear_trackers(&mut self, change_tick: u32);
+    fn clone_store(&self) -> Box<dyn BackingComponentStore>;
+    fn copy_from(&mut self, other: &dyn BackingComponentStore);
+    fn clear(&mut self);
+    fn reflect(&self, entity: Entity) -> Option<&dyn Reflect>;
+    fn debug(&self, entity: Entity) -> Option<&dyn std::fmt::Debug>;
+    fn type_name(&self) -> &'static str;
+    fn reflect_mut(&mut self, entity: Entity) -> Option<&mut dyn Reflect>;
     fn as_any(&self) -> &dyn core::any::Any;
     fn as_any_mut(&mut self) -> &mut dyn core::any::Any;
 }
 
-impl<Component> BackingComponentStore for ComponentStore<Component>
+impl<C> BackingComponentStore for ComponentStore<C>
 where
-    Component: Sized + Default + Clone + std::fmt::Debug + 'static,
+    C: Component,
 {
+    fn contains(&self, entity: Entity) -> bool {
+        ComponentStore::contains(self, entity)
+    }
+
+    fn available(&self) -> usize {
+        self.capacity - self.active_components
+    }
+
+    /// Copies the entity's component, if it has one, to the target store of the same type.
+    fn copy_entity(
+        &self,
+        entity: Entity,
+        target: &mut dyn BackingComponentStore,
+        target_entity: Entity,
+        map: &EntityMap,
+    ) -> Result<(), ComponentStoreError> {
+        let mut component = match self.get(entity) {
+            Some(component) => component.clone(),
+            None => return Ok(()),
+        };
+        component.map_entities(map);
+
+        if let Some(target) = target.as_any_mut().downcast_mut::<ComponentStore<C>>() {
+            *target.add(target_entity)? = component;
+        }
+
+        Ok(())
+    }
+
     /// Destroys a component for a given entity.
     fn destroy(&mut self, entity_to_destroy: Entity) {
         if self.active_components == 0 {
             return;
         }
 
+        if Self::IS_TAG {
+            let id = entity_to_destroy.id() as usize;
+            if self.tags.remove(id) {
+                self.added_tags.remove(id);
+                self.changed_tags.remove(id);
+                self.active_components -= 1;
+                self.removed.push(entity_to_destroy);
+            }
+            return;
+        }
+
         // Take this element
         let index_to_remove = match self.component_index(entity_to_destroy) {
             Some(i) => i,
@@ -33,21 +101,106 @@ where
         // Swap with last
         let last_element_index = self.active_components - 1;
         self.components.swap(index_to_remove, last_element_index);
+        self.entities.swap(index_to_remove, last_element_index);
+        self.ticks.swap(index_to_remove, last_element_index);
 
         // Update the last element to point to the new index
-        for i in 0..self.entity_map.len() {
-            if let Some((entity_to_keep, index)) = self.entity_map[i] {
-                if index == last_element_index {
-                    // Found it, so update it to point to the swapped index and break out.
-                    self.entity_map[i] = Some((entity_to_keep, index_to_remove));
-                    break;
-                }
-            }
+        if index_to_remove != last_element_index {
+            let entity_to_keep = self.entities[index_to_remove];
+            self.sparse
+                .insert(entity_to_keep.id() as usize, index_to_remove);
         }
 
         // Decrease active components + clear this map
-        self.entity_map[entity_to_destroy.id() as usize] = None;
+        self.sparse.remove(entity_to_destroy.id() as usize);
         self.active_components -= 1;
+        self.removed.push(entity_to_destroy);
+    }
+
+    /// Moves on to the given tick, forgetting any removed components.
+    fn clear_trackers(&mut self, change_tick: u32) {
+        self.change_tick = change_tick;
+        self.removed.clear();
+        self.added_tags.clear();
+        self.changed_tags.clear();
+    }
+
+    /// Returns a copy of the store.
+    fn clone_store(&self) -> Box<dyn BackingComponentStore> {
+        Box::new(self.clone())
+    }
+
+    /// Copies the contents of the other store into this one, reusing the buffers. Does nothing if the stores are different types.
+    fn copy_from(&mut self, other: &dyn BackingComponentStore) {
+        let other = match other.as_any().downcast_ref::<Self>() {
+            Some(other) => other,
+            None => return,
+        };
+
+        if Self::IS_TAG {
+            self.tags.copy_from(&other.tags);
+            self.added_tags.copy_from(&other.added_tags);
+            self.changed_tags.copy_from(&other.changed_tags);
+            self.active_components = other.active_components;
+            self.change_tick = other.change_tick;
+            self.removed.clone_from(&other.removed);
+            return;
+        }
+
+        let active_components = other.active_components;
+        if active_components > self.components.len() {
+            *self = other.clone();
+            return;
+        }
+
+        for index in 0..self.active_components {
+            self.sparse.remove(self.entities[index].id() as usize);
+        }
+
+        self.components[0..active_components]
+            .clone_from_slice(&other.components[0..active_components]);
+        self.entities[0..active_components].copy_from_slice(other.entities());
+        self.ticks[0..active_components].copy_from_slice(&other.ticks[0..active_components]);
+
+        for (index, entity) in other.entities().iter().enumerate() {
+            self.sparse.insert(entity.id() as usize, index);
+        }
+
+        self.active_components = active_components;
+        self.change_tick = other.change_tick;
+        self.removed.clone_from(&other.removed);
+    }
+
+    /// Removes all components without tracking them as removed.
+    fn clear(&mut self) {
+        if Self::IS_TAG {
+            self.tags.clear();
+            self.added_tags.clear();
+            self.changed_tags.clear();
+        }
+
+        for index in 0..self.entities().len() {
+            self.sparse.remove(self.entities[index].id() as usize);
+        }
+
+        self.active_components = 0;
+        self.removed.clear();
+    }
+
+    fn reflect(&self, entity: Entity) -> Option<&dyn Reflect> {
+        self.get(entity)?.as_reflect()
+    }
+
+    fn reflect_mut(&mut self, entity: Entity) -> Option<&mut dyn Reflect> {
+        self.get_mut(entity)?.as_reflect_mut()
+    }
+
+    fn debug(&self, entity: Entity) -> Option<&dyn std::fmt::Debug> {
+        self.get(entity).map(|c| c as &dyn std::fmt::Debug)
+    }
+
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<C>()
     }
 
     fn as_any(&self) -> &dyn std::any::Any {
@@ -59,43 +212,140 @@ where
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct ComponentStore<Component>
 where
     Component: Sized + Default + std::fmt::Debug + Clone,
 {
-    entity_map: Vec<Option<(Entity, usize)>>, // An option here would be using a hashmap instead of a Vec
+    sparse: SparseArray,
+    entities: Vec<Entity>,
     components: Vec<Component>,
+    /// Shares indexes with `components`. Unused for tags.
+    ticks: Vec<ComponentTicks>,
+    /// Membership for tags, indexed by entity id.
+    tags: BitSet,
+    /// Tags added since trackers were last cleared, indexed by entity id.
+    added_tags: BitSet,
+    /// Tags added or mutably accessed since trackers were last cleared, indexed by entity id.
+    changed_tags: BitSet,
+    capacity: usize,
     active_components: usize,
+    change_tick: u32,
+    removed: Vec<Entity>,
+}
+
+/// The ticks a component was added + last mutably accessed on.
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    /// Returns whether the component was added after the given tick.
+    pub fn is_added(&self, since_tick: u32) -> bool {
+        self.added > since_tick
+    }
+
+    /// Returns whether the component was added or mutably accessed after the given tick.
+    pub fn is_changed(&self, since_tick: u32) -> bool {
+        self.changed > since_tick
+    }
 }
 
 impl<Component> ComponentStore<Component>
 where
     Component: Sized + Default + std::fmt::Debug + Clone,
 {
+    /// Whether the component is zero sized, and so stored as a tag.
+    const IS_TAG: bool = std::mem::size_of::<Component>() == 0;
+
     /// Creates a new component store.
     pub fn new(component_capacity: usize) -> Self {
+        // Tags don't use the dense arrays. Their components are zero sized, so the component slots cost nothing and
+        // `components()` can still return one per entity.
+        let (dense_capacity, tag_capacity) = match Self::IS_TAG {
+            true => (0, MAX_ENTITIES),
+            false => (component_capacity, 0),
+        };
+
         Self {
-            entity_map: vec![None; MAX_ENTITIES],
+            sparse: SparseArray::new(),
+            entities: vec![Entity::new(0, 0); dense_capacity],
             components: vec![Component::default(); component_capacity],
+            ticks: vec![ComponentTicks::default(); dense_capacity],
+            tags: BitSet::new(tag_capacity),
+            added_tags: BitSet::new(tag_capacity),
+            changed_tags: BitSet::new(tag_capacity),
+            capacity: component_capacity,
             active_components: 0,
+            change_tick: 0,
+            removed: Vec::with_capacity(component_capacity),
         }
     }
 
-    /// Returns the collection of entities
-    pub fn entities(&self) -> impl Iterator<Item = Option<&Entity>> {
-        self.entity_map.iter().map(|e| match e {
-            Some((e, _i)) => Some(e),
-            None => None,
-        })
+    /// Sets the tick that adds + mutable accesses are recorded on.
+    pub(crate) fn set_change_tick(&mut self, change_tick: u32) {
+        self.change_tick = change_tick;
+    }
+
+    /// Returns the entities that currently have a component. Shares indexes with `components()`.
+    /// Tags don't keep a list of entities, so this is empty for them.
+    pub fn entities(&self) -> &[Entity] {
+        &self.entities[0..self.entities.len().min(self.active_components)]
+    }
+
+    /// Returns the entities a query should check, or `None` if every entity should be checked.
+    pub(crate) fn candidates(&self) -> Option<&[Entity]> {
+        match Self::IS_TAG {
+            true => None,
+            false => Some(self.entities()),
+        }
+    }
+
+    /// Returns the number of entities that have a component.
+    pub fn len(&self) -> usize {
+        self.active_components
+    }
+
+    /// Returns whether no entities have a component.
+    pub fn is_empty(&self) -> bool {
+        self.active_components == 0
+    }
+
+    /// Returns whether the given entity has a component in this store.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.component_index(entity).is_some()
     }
 
     /// Returns the component index for the given entity
-    fn component_index(&self, entity: Entity) -> Option<usize> {
-        let index = entity.id() as usize;
-        match self.entity_map[index] {
-            Some((_, index)) => Some(index),
-            None => None,
+    pub(crate) fn component_index(&self, entity: Entity) -> Option<usize> {
+        if Self::IS_TAG {
+            return match self.tags.contains(entity.id() as usize) {
+                true => Some(0),
+                false => None,
+            };
+        }
+
+        self.sparse.get(entity.id() as usize)
+    }
+
+    /// Returns the component + tick index for the given entity. For tags the tick index is the entity id.
+    pub(crate) fn indexes(&self, entity: Entity) -> Option<(usize, usize)> {
+        let index = self.component_index(entity)?;
+        match Self::IS_TAG {
+            true => Some((index, entity.id() as usize)),
+            false => Some((index, index)),
+        }
+    }
+
+    /// Marks the component at the given tick index as changed.
+    fn mark_changed(&mut self, tick_index: usize) {
+        match Self::IS_TAG {
+            true => {
+                self.changed_tags.insert(tick_index);
+            }
+            false => self.ticks[tick_index].changed = self.change_tick,
         }
     }
 
@@ -107,44 +357,120 @@ where
         }
     }
 
-    /// Returns a mutable reference to the given component.
+    /// Returns a mutable reference to the given component. Marks the component as changed.
     pub fn get_mut(&mut self, entity: Entity) -> Option<&mut Component> {
-        match self.component_index(entity) {
-            Some(index) => Some(&mut self.components[index]),
+        match self.indexes(entity) {
+            Some((index, tick_index)) => {
+                self.mark_changed(tick_index);
+                Some(&mut self.components[index])
+            }
             None => None,
         }
     }
 
+    /// Returns the change ticks for the given entity's component.
+    /// Tags only know what happened since trackers were last cleared, so anything earlier reports as tick 0.
+    pub fn ticks(&self, entity: Entity) -> Option<ComponentTicks> {
+        let (_, tick_index) = self.indexes(entity)?;
+        if !Self::IS_TAG {
+            return Some(self.ticks[tick_index]);
+        }
+
+        let tick = |tags: &BitSet| match tags.contains(tick_index) {
+            true => self.change_tick,
+            false => 0,
+        };
+
+        Some(ComponentTicks {
+            added: tick(&self.added_tags),
+            changed: tick(&self.changed_tags),
+        })
+    }
+
+    /// Returns the entities that had their component removed since trackers were last cleared.
+    pub fn removed(&self) -> &[Entity] {
+        &self.removed
+    }
+
     /// Adds a new component for the given entity.
     pub fn add(&mut self, entity: Entity) -> Result<&mut Component, ComponentStoreError> {
         let id = entity.id() as usize;
 
-        let mut component_index = 0;
-
         // If it doesn't exist, add it. Can just return existing one if attempting to add again.
-        if self.component_index(entity).is_none() {
-            if self.active_components == self.components.capacity() {
-                return Err(ComponentStoreError::BufferOverflow);
-            }
-            component_index = self.active_components;
-            self.active_components += 1;
+        let (component_index, tick_index) = match self.indexes(entity) {
+            Some(indexes) => indexes,
+            None => {
+                if self.active_components == self.capacity {
+                    return Err(ComponentStoreError::BufferOverflow);
+                }
 
-            self.entity_map[id] = Some((entity, component_index));
-        }
+                self.active_components += 1;
+                if Self::IS_TAG {
+                    self.tags.insert(id);
+                    self.added_tags.insert(id);
 
+                    (0, id)
+                } else {
+                    let component_index = self.active_components - 1;
+
+                    self.sparse.insert(id, component_index);
+                    self.entities[component_index] = entity;
+                    self.ticks[component_index].added = self.change_tick;
+
+                    (component_index, component_index)
+                }
+            }
+        };
+
+        self.mark_changed(tick_index);
         Ok(&mut self.components[component_index])
     }
 
     /// Returns the slice for the active components. Not tied to entities.
+    /// For tags this is the same zero sized component repeated `len()` times.
     pub fn components(&self) -> &[Component] {
         &self.components[0..self.active_components]
     }
 
-    /// Returns a mutable list of the components
+    /// Returns a mutable list of the components. Marks all components as changed.
     pub fn components_mut(&mut self) -> impl Iterator<Item = &mut Component> {
+        if Self::IS_TAG {
+            self.changed_tags.union_with(&self.tags);
+        }
+
+        let tracked = self.ticks.len().min(self.active_components);
+        for ticks in self.ticks[0..tracked].iter_mut() {
+            ticks.changed = self.change_tick;
+        }
+
         self.components[0..self.active_components].iter_mut()
     }
 
+    /// Marks the component at the given tick index from `indexes()` as changed, without borrowing the whole store.
+    /// Used by queries that hand out references to multiple components.
+    ///
+    /// # Safety
+    /// The store must be valid, and nothing else may be accessing its change trackers.
+    pub(crate) unsafe fn mark_changed_raw(store: *mut Self, tick_index: usize) {
+        let change_tick = (*store).change_tick;
+        match Self::IS_TAG {
+            true => {
+                let changed_tags = &mut *std::ptr::addr_of_mut!((*store).changed_tags);
+                changed_tags.insert(tick_index);
+            }
+            false => {
+                let ticks = &mut *std::ptr::addr_of_mut!((*store).ticks);
+                ticks[tick_index].changed = change_tick;
+            }
+        }
+    }
+
+    /// Returns a raw pointer to the start of the component buffer. Used by queries to hand out
+    /// references to multiple components without borrowing the whole store.
+    pub(crate) fn components_ptr(&mut self) -> *mut Component {
+        self.components.as_mut_ptr()
+    }
+
     /*
     /// Removes all entities that aren't alive
     pub fn garbage_collection(&mut self, entity_manager: &EntityManager) {
@@ -165,6 +491,8 @@ where
 mod tests {
     use super::*;
 
+    impl Component for u8 {}
+
     #[test]
     fn new() {
         let max_components = 129;
@@ -172,7 +500,8 @@ mod tests {
         assert_eq!(0, store.active_components);
         assert_eq!(max_components, store.components.capacity());
         assert_eq!(vec![u8::default(); max_components], store.components);
-        assert_eq!(vec![None; MAX_ENTITIES], store.entity_map);
+        assert_eq!(0, store.entities().len());
+        assert_eq!(0, store.sparse.allocated_pages());
     }
 
     /*
@@ -262,14 +591,15 @@ mod tests {
         assert_eq!(1, store.active_components);
 
         // Make sure it was destroyed properly
-        assert_eq!(None, store.entity_map[entity1.id() as usize]);
+        assert_eq!(None, store.component_index(entity1));
 
         // Make sure it was shifted so last active is new active
         assert_eq!(entity2_value, store.components[0]);
         assert_eq!(entity1_value, store.components[1]);
 
         // Make sure last entity is now pointing to the removed one
-        assert_eq!(Some((entity2, 0)), store.entity_map[entity2.id() as usize]);
+        assert_eq!(Some(0), store.component_index(entity2));
+        assert_eq!(&[entity2], store.entities());
 
         // Some random chaos testing
         store.destroy(entity1);
@@ -365,12 +695,22 @@ mod tests {
             let entity = Entity::new(12, 0);
             let component = store.add(entity);
             assert_eq!(true, component.is_ok());
-            assert_eq!(Some((entity, 0)), store.entity_map[entity.id() as usize]);
+            assert_eq!(Some(0), store.component_index(entity));
+            assert_eq!(entity, store.entities()[0]);
 
             let entity = Entity::new(13, 0);
             let component = store.add(entity);
             assert_eq!(true, component.is_ok());
-            assert_eq!(Some((entity, 1)), store.entity_map[entity.id() as usize]);
+            assert_eq!(Some(1), store.component_index(entity));
+            assert_eq!(entity, store.entities()[1]);
+        }
+
+        // Adding again returns the existing component
+        {
+            let entity = Entity::new(13, 0);
+            *store.add(entity).unwrap() = 42;
+            assert_eq!(Some(&42), store.get(entity));
+            assert_eq!(Some(&0), store.get(Entity::new(12, 0)));
         }
 
         // Would overflow
@@ -379,4 +719,122 @@ mod tests {
         assert_eq!(true, component.is_err());
         assert_eq!(ComponentStoreError::BufferOverflow, component.unwrap_err());
     }
+
+    #[test]
+    fn change_ticks() {
+        let mut store: ComponentStore<u8> = ComponentStore::new(5);
+        store.set_change_tick(1);
+
+        let a = Entity::new(1, 0);
+        let b = Entity::new(2, 0);
+        store.add(a).unwrap();
+        store.add(b).unwrap();
+        assert_eq!(
+            Some(ComponentTicks {
+                added: 1,
+                changed: 1
+            }),
+            store.ticks(a)
+        );
+
+        store.clear_trackers(2);
+        *store.get_mut(b).unwrap() = 3;
+        let ticks = store.ticks(b).unwrap();
+        assert!(!ticks.is_added(1));
+        assert!(ticks.is_changed(1));
+        assert!(!store.ticks(a).unwrap().is_changed(1));
+
+        // Ticks follow the component when it is swapped on destroy
+        store.destroy(a);
+        assert_eq!(&[a], store.removed());
+        assert!(store.ticks(b).unwrap().is_changed(1));
+
+        store.clear_trackers(3);
+        assert_eq!(0, store.removed().len());
+    }
+
+    #[test]
+    fn copy_from() {
+        let mut store: ComponentStore<u8> = ComponentStore::new(5);
+        *store.add(Entity::new(1, 0)).unwrap() = 1;
+        *store.add(Entity::new(2, 0)).unwrap() = 2;
+        let snapshot = store.clone_store();
+
+        store.destroy(Entity::new(1, 0));
+        *store.add(Entity::new(3, 0)).unwrap() = 3;
+        *store.get_mut(Entity::new(2, 0)).unwrap() = 20;
+
+        store.copy_from(snapshot.as_ref());
+        assert_eq!(&[Entity::new(1, 0), Entity::new(2, 0)], store.entities());
+        assert_eq!(&[1, 2], store.components());
+        assert_eq!(Some(&1), store.get(Entity::new(1, 0)));
+        assert_eq!(None, store.get(Entity::new(3, 0)));
+        assert_eq!(0, store.removed().len());
+
+        store.clear();
+        assert_eq!(0, store.entities().len());
+        assert_eq!(None, store.get(Entity::new(2, 0)));
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Tag;
+    impl Component for Tag {}
+
+    #[test]
+    fn tag_store() {
+        let mut store: ComponentStore<Tag> = ComponentStore::new(2);
+        assert_eq!(0, store.components().len());
+        assert_eq!(None, store.candidates());
+        store.set_change_tick(1);
+
+        let (a, b, c) = (
+            Entity::new(1, 0),
+            Entity::new((MAX_ENTITIES - 1) as u32, 0),
+            Entity::new(2, 0),
+        );
+        store.add(a).unwrap();
+        store.add(a).unwrap();
+        store.add(b).unwrap();
+        assert_eq!(
+            Err(ComponentStoreError::BufferOverflow),
+            store.add(c).map(|_| ())
+        );
+        assert_eq!(2, store.len());
+        assert_eq!(Some(&Tag), store.get(a));
+        assert_eq!(Some(&mut Tag), store.get_mut(b));
+        assert_eq!(None, store.get(c));
+        assert_eq!(0, store.entities().len());
+        assert_eq!(&[Tag, Tag], store.components());
+        assert_eq!(2, store.components_mut().count());
+
+        // Changes are tracked per entity until trackers are cleared
+        assert!(store.ticks(b).unwrap().is_added(0));
+        store.clear_trackers(2);
+        assert!(!store.ticks(b).unwrap().is_added(0));
+        store.get_mut(a).unwrap();
+        assert_eq!(
+            Some(ComponentTicks {
+                added: 0,
+                changed: 2
+            }),
+            store.ticks(a)
+        );
+        assert!(!store.ticks(b).unwrap().is_changed(1));
+        assert_eq!(None, store.ticks(c));
+
+        let snapshot = store.clone_store();
+        store.destroy(a);
+        store.destroy(a);
+        assert_eq!(&[a], store.removed());
+        assert!(!store.contains(a));
+        assert_eq!(1, store.len());
+
+        store.copy_from(snapshot.as_ref());
+        assert!(store.contains(a) && store.contains(b));
+        assert_eq!(2, store.len());
+
+        store.clear();
+        assert!(store.is_empty());
+        assert!(!store.contains(b));
+    }
 }
@@ -0,0 +1,382 @@
+// Text dumps of world state for debugging, such as printing the sim from a test or writing it to a file.
+// Entities are sorted by id + components by name, so two dumps of the same state are always identical and can be diffed.
+// Component values use their `Debug` impl, which every component has.
+// Dumps allocate, so they're meant for tools + tests rather than every tick.
+use std::fmt;
+
+use crate::{Entity, ResourceId, World};
+
+/// A snapshot of a world as text.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WorldDump {
+    pub entities: Vec<EntityDump>,
+}
+
+/// An entity + its component values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct EntityDump {
+    pub entity: Entity,
+    pub components: Vec<ComponentDump>,
+}
+
+/// A component's name + value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComponentDump {
+    pub name: String,
+    pub value: String,
+}
+
+/// A single difference between two dumps.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DumpChange {
+    EntityAdded(Entity),
+    EntityRemoved(Entity),
+    ComponentAdded {
+        entity: Entity,
+        component: ComponentDump,
+    },
+    ComponentRemoved {
+        entity: Entity,
+        component: ComponentDump,
+    },
+    ComponentChanged {
+        entity: Entity,
+        name: String,
+        before: String,
+        after: String,
+    },
+}
+
+/// All differences between two dumps, in entity order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DumpDiff {
+    pub changes: Vec<DumpChange>,
+}
+
+impl World {
+    /// Dumps all alive entities + their components.
+    pub fn dump(&self) -> WorldDump {
+        self.dump_components(None)
+    }
+
+    /// Dumps only the given components. Entities without any of them are skipped.
+    pub fn dump_filtered(&self, components: &[ResourceId]) -> WorldDump {
+        self.dump_components(Some(components))
+    }
+
+    fn dump_components(&self, filter: Option<&[ResourceId]>) -> WorldDump {
+        let mut stores: Vec<(String, &ResourceId)> = self
+            .components
+            .iter()
+            .filter(|(id, _)| filter.is_none_or(|f| f.contains(id)))
+            .map(|(id, store)| (short_name(store.type_name()), id))
+            .collect();
+        stores.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let mut entities: Vec<Entity> = self.entities().to_vec();
+        entities.sort_by_key(|e| (e.id(), e.generation()));
+
+        let mut dump = WorldDump::default();
+        for entity in entities {
+            let components: Vec<ComponentDump> = stores
+                .iter()
+                .filter_map(|(name, id)| {
+                    let value = self.components.get(id)?.debug(entity)?;
+                    Some(ComponentDump {
+                        name: name.clone(),
+                        value: format!("{:?}", value),
+                    })
+                })
+                .collect();
+
+            if filter.is_some() && components.is_empty() {
+                continue;
+            }
+
+            dump.entities.push(EntityDump { entity, components });
+        }
+
+        dump
+    }
+}
+
+impl WorldDump {
+    /// Returns the dump for the given entity.
+    pub fn entity(&self, entity: Entity) -> Option<&EntityDump> {
+        self.entities.iter().find(|e| e.entity == entity)
+    }
+
+    /// Returns what changed to go from this dump to the other one.
+    pub fn diff(&self, other: &WorldDump) -> DumpDiff {
+        let mut changes = vec![];
+        let (mut before, mut after) = (
+            self.entities.iter().peekable(),
+            other.entities.iter().peekable(),
+        );
+
+        // Both lists are sorted, so walk them together
+        loop {
+            let order = match (before.peek(), after.peek()) {
+                (Some(b), Some(a)) => sort_key(b.entity).cmp(&sort_key(a.entity)),
+                (Some(_), None) => std::cmp::Ordering::Less,
+                (None, Some(_)) => std::cmp::Ordering::Greater,
+                (None, None) => break,
+            };
+
+            match order {
+                std::cmp::Ordering::Less => {
+                    let b = before.next().unwrap();
+                    changes.push(DumpChange::EntityRemoved(b.entity));
+                }
+                std::cmp::Ordering::Greater => {
+                    let a = after.next().unwrap();
+                    changes.push(DumpChange::EntityAdded(a.entity));
+                    for component in a.components.iter() {
+                        changes.push(DumpChange::ComponentAdded {
+                            entity: a.entity,
+                            component: component.clone(),
+                        });
+                    }
+                }
+                std::cmp::Ordering::Equal => {
+                    let (b, a) = (before.next().unwrap(), after.next().unwrap());
+                    diff_components(b, a, &mut changes);
+                }
+            }
+        }
+
+        DumpDiff { changes }
+    }
+
+    /// Writes the dump to the given file.
+    pub fn write_to_file<P>(&self, path: P) -> std::io::Result<()>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl DumpDiff {
+    /// Returns whether the dumps were the same.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+fn sort_key(entity: Entity) -> (u32, u8) {
+    (entity.id(), entity.generation())
+}
+
+fn diff_components(before: &EntityDump, after: &EntityDump, changes: &mut Vec<DumpChange>) {
+    let entity = after.entity;
+    for b in before.components.iter() {
+        match after.components.iter().find(|a| a.name == b.name) {
+            Some(a) if a.value != b.value => changes.push(DumpChange::ComponentChanged {
+                entity,
+                name: b.name.clone(),
+                before: b.value.clone(),
+                after: a.value.clone(),
+            }),
+            Some(_) => {}
+            None => changes.push(DumpChange::ComponentRemoved {
+                entity,
+                component: b.clone(),
+            }),
+        }
+    }
+
+    for a in after.components.iter() {
+        if !before.components.iter().any(|b| b.name == a.name) {
+            changes.push(DumpChange::ComponentAdded {
+                entity,
+                component: a.clone(),
+            });
+        }
+    }
+}
+
+/// Strips module paths from a type name, so `gorekill::sim::components::Position` becomes `Position`.
+fn short_name(type_name: &str) -> String {
+    let mut name = String::new();
+    let mut segment_start = 0;
+    for (i, c) in type_name.char_indices() {
+        if c == '<' || c == '>' || c == ',' || c == ' ' {
+            name.push_str(last_segment(&type_name[segment_start..i]));
+            name.push(c);
+            segment_start = i + 1;
+        }
+    }
+    name.push_str(last_segment(&type_name[segment_start..]));
+    name
+}
+
+fn last_segment(path: &str) -> &str {
+    path.rsplit("::").next().unwrap_or(path)
+}
+
+fn fmt_entity(entity: Entity) -> String {
+    format!("Entity {}v{}", entity.id(), entity.generation())
+}
+
+impl fmt::Display for WorldDump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entity in self.entities.iter() {
+            writeln!(f, "{}", fmt_entity(entity.entity))?;
+            for component in entity.components.iter() {
+                writeln!(f, "  {}: {}", component.name, component.value)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for DumpDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in self.changes.iter() {
+            match change {
+                DumpChange::EntityAdded(e) => writeln!(f, "+ {}", fmt_entity(*e))?,
+                DumpChange::EntityRemoved(e) => writeln!(f, "- {}", fmt_entity(*e))?,
+                DumpChange::ComponentAdded { entity, component } => writeln!(
+                    f,
+                    "+ {} {}: {}",
+                    fmt_entity(*entity),
+                    component.name,
+                    component.value
+                )?,
+                DumpChange::ComponentRemoved { entity, component } => writeln!(
+                    f,
+                    "- {} {}: {}",
+                    fmt_entity(*entity),
+                    component.name,
+                    component.value
+                )?,
+                DumpChange::ComponentChanged {
+                    entity,
+                    name,
+                    before,
+                    after,
+                } => writeln!(
+                    f,
+                    "~ {} {}: {} -> {}",
+                    fmt_entity(*entity),
+                    name,
+                    before,
+                    after
+                )?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Component;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position {
+        x: i32,
+    }
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Player;
+    impl Component for Player {}
+
+    #[test]
+    fn world_dump_tree() {
+        let mut world = World::new();
+        world.register::<Position>(4);
+        world.register::<Player>(4);
+        let a = world.add_entity();
+        let b = world.add_entity();
+        world.insert(a, Position { x: 1 }).unwrap();
+        world.insert(a, Player).unwrap();
+        world.insert(b, Position { x: 2 }).unwrap();
+        world.add_entity();
+
+        assert_eq!(
+            "Entity 0v0\n  Player: Player\n  Position: Position { x: 1 }\nEntity 1v0\n  Position: Position { x: 2 }\nEntity 2v0\n",
+            world.dump().to_string()
+        );
+
+        let players = world.dump_filtered(&[ResourceId::from::<Player>()]);
+        assert_eq!("Entity 0v0\n  Player: Player\n", players.to_string());
+
+        assert_eq!(
+            "Vec<Option<Position>>",
+            short_name("alloc::vec::Vec<core::option::Option<a::Position>>")
+        );
+    }
+
+    #[test]
+    fn world_dump_diff() {
+        let mut world = World::new();
+        world.register::<Position>(4);
+        world.register::<Player>(4);
+        let a = world.add_entity();
+        let b = world.add_entity();
+        world.insert(a, Position { x: 1 }).unwrap();
+        world.insert(b, Player).unwrap();
+        let before = world.dump();
+        assert!(before.diff(&world.dump()).is_empty());
+
+        world.get_mut::<Position>(a).unwrap().x = 5;
+        world.remove::<Player>(b);
+        world.insert(b, Position { x: 3 }).unwrap();
+        world.kill(a);
+        let c = world.add_entity();
+        world.insert(c, Player).unwrap();
+        let after = world.dump();
+
+        let diff = before.diff(&after);
+        assert_eq!(
+            vec![
+                DumpChange::EntityRemoved(a),
+                DumpChange::ComponentRemoved {
+                    entity: b,
+                    component: ComponentDump {
+                        name: "Player".into(),
+                        value: "Player".into()
+                    }
+                },
+                DumpChange::ComponentAdded {
+                    entity: b,
+                    component: ComponentDump {
+                        name: "Position".into(),
+                        value: "Position { x: 3 }".into()
+                    }
+                },
+                DumpChange::EntityAdded(c),
+                DumpChange::ComponentAdded {
+                    entity: c,
+                    component: ComponentDump {
+                        name: "Player".into(),
+                        value: "Player".into()
+                    }
+                },
+            ],
+            diff.changes
+        );
+        assert_eq!(
+            "- Entity 0v0\n- Entity 1v0 Player: Player\n+ Entity 1v0 Position: Position { x: 3 }\n+ Entity 2v0\n+ Entity 2v0 Player: Player\n",
+            diff.to_string()
+        );
+
+        let mut changed = after.clone();
+        changed.entities[0].components[0].value = "Position { x: 4 }".into();
+        assert_eq!(
+            vec![DumpChange::ComponentChanged {
+                entity: b,
+                name: "Position".into(),
+                before: "Position { x: 3 }".into(),
+                after: "Position { x: 4 }".into(),
+            }],
+            after.diff(&changed).changes
+        );
+    }
+}
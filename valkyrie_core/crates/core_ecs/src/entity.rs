@@ -15,7 +15,12 @@ const ENTITY_INDEX_MASK: EntityId =
 const ENTITY_GENERATION_MASK: EntityId = !ENTITY_INDEX_MASK;
 
 /// An entity in the system.
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(
+    feature = "serialize",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(transparent)
+)]
 pub struct Entity {
     entity: EntityId,
 }
@@ -50,7 +55,7 @@ impl Entity {
 
     /// Returns the id of the entity
     pub fn id(&self) -> EntityId {
-        self.entity & ENTITY_INDEX_MASK
+        (self.entity & ENTITY_INDEX_MASK) >> ENTITY_GENERATION_BITS
     }
 
     /// Returns the generation of the entity
@@ -100,12 +105,17 @@ mod tests {
         let e = 0b1111_1111_1111_1111_1111_1111_1111_1111;
         let entity = Entity { entity: e };
 
-        assert_eq!(0b1111_1111_1111_1111_1111_1111_0000_0000, entity.id());
+        assert_eq!(0b1111_1111_1111_1111_1111_1111, entity.id());
 
         let e = 0b1001_1111_1001_1100_1111_1111_1111_1111;
         let entity = Entity { entity: e };
 
-        assert_eq!(0b1001_1111_1001_1100_1111_1111_0000_0000, entity.id());
+        assert_eq!(0b1001_1111_1001_1100_1111_1111, entity.id());
+
+        // Round trips through new
+        let entity = Entity::new(1234, 5);
+        assert_eq!(1234, entity.id());
+        assert_eq!(5, entity.generation());
     }
 
     #[test]
@@ -1,3 +1,5 @@
+use std::sync::{Arc, Mutex, MutexGuard};
+
 use crate::{
     entity::{EntityId, Generation, MAX_ENTITIES},
     Entity,
@@ -5,14 +7,101 @@ use crate::{
 
 use core_data_structures::queue::Queue;
 
-const MINIMUM_FREE_INDICES: usize = 1024;
+/// The number of ids that must be freed before they start getting reused. Prevents generations from wrapping too quickly.
+pub(crate) const MINIMUM_FREE_INDICES: usize = 1024;
+
+/// The part of the entity manager that hands out ids. It is shared with any `EntityReserver`s
+/// so entities can be reserved without mutable access to the world.
+#[derive(Debug)]
+struct EntityAllocator {
+    free_entity_ids: Queue<Entity>,
+    next_id: EntityId,
+    used_ids: usize,
+    reserved: Vec<Entity>,
+}
+
+impl EntityAllocator {
+    /// Returns the next entity to use, reusing freed ids once there are enough of them.
+    fn allocate(&mut self) -> Entity {
+        if self.free_entity_ids.len() > MINIMUM_FREE_INDICES {
+            if let Some(entity) = self.free_entity_ids.pop() {
+                return entity;
+            }
+        }
+
+        let id = self.next_id;
+        self.next_id += 1;
+        if self.next_id as usize >= MAX_ENTITIES {
+            self.next_id = 0;
+        }
+        self.used_ids = self.used_ids.max(id as usize + 1);
+
+        Entity::new(id, 0)
+    }
+
+    /// Copies the state of the other allocator, reusing this allocators buffers.
+    fn copy_from(&mut self, other: &EntityAllocator) {
+        self.free_entity_ids.clear();
+        for entity in other.free_entity_ids.items() {
+            self.free_entity_ids.push(*entity);
+        }
+
+        self.next_id = other.next_id;
+        self.used_ids = other.used_ids;
+        self.reserved.clone_from(&other.reserved);
+    }
+}
+
+/// A copy of the state of an `EntityManager`. Only the generations for ids that have been used are stored.
+#[derive(Debug)]
+pub struct EntityManagerSnapshot {
+    generations: Vec<Generation>,
+    allocator: EntityAllocator,
+}
+
+impl EntityManagerSnapshot {
+    /// Creates a new, empty, snapshot.
+    pub fn new() -> Self {
+        Self {
+            generations: vec![],
+            allocator: EntityAllocator {
+                next_id: 0,
+                used_ids: 0,
+                // Grown as ids are copied in, so empty snapshots stay small.
+                free_entity_ids: Queue::new(0),
+                reserved: vec![],
+            },
+        }
+    }
+}
 
 #[derive(Debug)]
 /// Class that manages creating and deletion of entities.
 pub struct EntityManager {
     generations: Vec<Generation>,
-    free_entity_ids: Queue<EntityId>,
-    next_id: EntityId,
+    allocator: Arc<Mutex<EntityAllocator>>,
+}
+
+/// Handle that reserves entities from an `EntityManager`. Reserved entities are valid handles straight away,
+/// but only become alive once the manager flushes them.
+#[derive(Clone, Debug)]
+pub struct EntityReserver {
+    allocator: Arc<Mutex<EntityAllocator>>,
+}
+
+impl EntityReserver {
+    /// Reserves a new entity.
+    pub fn reserve(&self) -> Entity {
+        let mut allocator = lock(&self.allocator);
+        let entity = allocator.allocate();
+        allocator.reserved.push(entity);
+        entity
+    }
+}
+
+fn lock(allocator: &Mutex<EntityAllocator>) -> MutexGuard<'_, EntityAllocator> {
+    // The allocator is never left in an invalid state, so a panic elsewhere shouldn't prevent using it.
+    allocator.lock().unwrap_or_else(|e| e.into_inner())
 }
 
 impl EntityManager {
@@ -20,32 +109,63 @@ impl EntityManager {
     pub fn new() -> Self {
         // Populate all generations to 0
         Self {
-            next_id: 0,
             generations: vec![0; MAX_ENTITIES],
-            free_entity_ids: Queue::new(MAX_ENTITIES),
+            allocator: Arc::new(Mutex::new(EntityAllocator {
+                next_id: 0,
+                used_ids: 0,
+                free_entity_ids: Queue::new(MAX_ENTITIES),
+                reserved: vec![],
+            })),
         }
     }
 
     /// Creates a new entity.
     pub fn create(&mut self) -> Entity {
-        let entity_id = if self.free_entity_ids.len() > MINIMUM_FREE_INDICES {
-            let index = match self.free_entity_ids.pop() {
-                Some(e) => e,
-                None => 0,
-            };
-
-            index
-        } else {
-            let id = self.next_id;
-            self.next_id += 1;
-            if self.next_id as usize >= MAX_ENTITIES {
-                self.next_id = 0;
-            }
+        lock(&self.allocator).allocate()
+    }
 
-            id
-        };
+    /// Returns a reserver that may be used to create entities without mutable access to the manager.
+    pub fn reserver(&self) -> EntityReserver {
+        EntityReserver {
+            allocator: self.allocator.clone(),
+        }
+    }
 
-        Entity::new(entity_id, self.generations[entity_id as usize])
+    /// Passes all reserved entities, in the order they were reserved, to the given function.
+    pub fn flush_reserved<F>(&mut self, mut f: F)
+    where
+        F: FnMut(Entity),
+    {
+        let mut allocator = lock(&self.allocator);
+        for entity in allocator.reserved.drain(..) {
+            f(entity);
+        }
+    }
+
+    /// Copies the state of the manager into the snapshot, reusing the snapshots buffers.
+    pub fn snapshot_into(&self, snapshot: &mut EntityManagerSnapshot) {
+        let allocator = lock(&self.allocator);
+        snapshot.allocator.copy_from(&allocator);
+        snapshot.generations.clear();
+        snapshot
+            .generations
+            .extend_from_slice(&self.generations[0..allocator.used_ids]);
+    }
+
+    /// Restores the state of the manager from the snapshot. Any reserved entities are replaced by the ones from the snapshot.
+    pub fn restore(&mut self, snapshot: &EntityManagerSnapshot) {
+        let mut allocator = lock(&self.allocator);
+
+        // Ids used after the snapshot was taken go back to being unused
+        let used_ids = snapshot.allocator.used_ids;
+        self.generations[0..used_ids].copy_from_slice(&snapshot.generations);
+        if allocator.used_ids > used_ids {
+            for generation in self.generations[used_ids..allocator.used_ids].iter_mut() {
+                *generation = 0;
+            }
+        }
+
+        allocator.copy_from(&snapshot.allocator);
     }
 
     /// Returns whether the given entity is classified as 'alive'.
@@ -68,7 +188,9 @@ impl EntityManager {
             self.generations[id] = self.generations[id].wrapping_add(1);
 
             // Mark it as a 'free' entity to use later on.
-            self.free_entity_ids.push(entity.id());
+            lock(&self.allocator)
+                .free_entity_ids
+                .push(Entity::new(entity.id(), self.generations[id]));
         }
     }
 }
@@ -86,7 +208,7 @@ mod tests {
             let i = i as u32;
             let entity = manager.create();
             assert_eq!(Entity::new(i, 0), entity);
-            assert_eq!(i + 1, manager.next_id);
+            assert_eq!(i + 1, lock(&manager.allocator).next_id);
         }
 
         // Now check for wrapping add.
@@ -95,7 +217,7 @@ mod tests {
         let entity = manager.create();
         let id = (MAX_ENTITIES - 1) as u32;
         assert_eq!(Entity::new(id, 0), entity);
-        assert_eq!(0, manager.next_id);
+        assert_eq!(0, lock(&manager.allocator).next_id);
 
         // Now check for generational
         for entity in 0..MAX_ENTITIES {
@@ -116,21 +238,78 @@ mod tests {
 
         let id = entity.id() as usize;
         assert_eq!(1, manager.generations[id]);
-        assert_eq!(entity.id(), manager.free_entity_ids.items()[0]);
+        assert_eq!(
+            entity.id(),
+            lock(&manager.allocator).free_entity_ids.items()[0].id()
+        );
 
         // Only free entities that have a matching generation; if they don't match do nothing
         manager.destroy(entity);
         assert_eq!(1, manager.generations[id]);
-        assert_eq!(entity.id(), manager.free_entity_ids.items()[0]);
+        assert_eq!(
+            entity.id(),
+            lock(&manager.allocator).free_entity_ids.items()[0].id()
+        );
 
         // Now check that it wraps the generation if it goes over the max generations
         manager.generations[id] = Generation::MAX;
-        manager.free_entity_ids.clear();
+        lock(&manager.allocator).free_entity_ids.clear();
 
         let entity = Entity::new(entity.id(), Generation::MAX);
         manager.destroy(entity);
         assert_eq!(0, manager.generations[id]);
-        assert_eq!(entity.id(), manager.free_entity_ids.items()[0]);
+        assert_eq!(
+            entity.id(),
+            lock(&manager.allocator).free_entity_ids.items()[0].id()
+        );
+    }
+
+    #[test]
+    fn entity_manager_reuses_ids_after_minimum_free_indices() {
+        let mut manager = EntityManager::new();
+
+        let entities: Vec<Entity> = (0..MINIMUM_FREE_INDICES + 2)
+            .map(|_| manager.create())
+            .collect();
+
+        for entity in entities.iter() {
+            manager.destroy(*entity);
+            assert!(!manager.is_alive(*entity));
+        }
+
+        // Ids are reused in the order they were freed, with a bumped generation
+        let reused = manager.create();
+        assert_eq!(Entity::new(0, 1), reused);
+        assert!(manager.is_alive(reused));
+        assert!(!manager.is_alive(entities[0]));
+
+        let reused = manager.create();
+        assert_eq!(Entity::new(1, 1), reused);
+
+        // Once at the minimum, new ids are used again
+        let new = manager.create();
+        assert_eq!(Entity::new((MINIMUM_FREE_INDICES + 2) as EntityId, 0), new);
+    }
+
+    #[test]
+    fn entity_manager_reserve_matches_create_order() {
+        let mut manager = EntityManager::new();
+        let reserver = manager.reserver();
+
+        let a = reserver.reserve();
+        let b = manager.create();
+        let c = reserver.clone().reserve();
+        assert_eq!(Entity::new(0, 0), a);
+        assert_eq!(Entity::new(1, 0), b);
+        assert_eq!(Entity::new(2, 0), c);
+
+        // Only reserved entities are flushed
+        let mut flushed = vec![];
+        manager.flush_reserved(|e| flushed.push(e));
+        assert_eq!(vec![a, c], flushed);
+
+        manager.flush_reserved(|e| flushed.push(e));
+        assert_eq!(2, flushed.len());
     }
 
     #[test]
@@ -164,6 +343,6 @@ mod tests {
         }
 
         assert_eq!(generations, manager.generations);
-        assert_eq!(0, manager.free_entity_ids.len());
+        assert_eq!(0, lock(&manager.allocator).free_entity_ids.len());
     }
 }
@@ -0,0 +1,40 @@
+// Maps entities from one set of handles to another, such as the handles in a save file to the ones in a world.
+use crate::Entity;
+use core_data_structures::hashmap::HashMap;
+
+/// A mapping from old entity handles to new ones.
+#[derive(Debug)]
+pub struct EntityMap {
+    map: HashMap<Entity, Entity>,
+}
+
+impl EntityMap {
+    /// Creates a new, empty, map.
+    pub fn new() -> Self {
+        Self {
+            map: HashMap::new(),
+        }
+    }
+
+    /// Maps the old entity to the new one.
+    pub fn insert(&mut self, from: Entity, to: Entity) {
+        self.map.insert(from, to);
+    }
+
+    /// Returns the new entity for the old one, if mapped.
+    pub fn get(&self, from: Entity) -> Option<Entity> {
+        self.map.get(&from).copied()
+    }
+
+    /// Returns the new entity for an optional old one. Entities that aren't mapped become `None`,
+    /// so references never point at an unrelated entity.
+    pub fn map_option(&self, entity: Option<Entity>) -> Option<Entity> {
+        entity.and_then(|e| self.get(e))
+    }
+}
+
+impl Default for EntityMap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,297 @@
+// Typed events for systems to tell each other things, such as 'bullet hit player'.
+// Events are retained for exactly one tick: `World::update_events()` drops everything sent since it was last called,
+// so call it once at the end of each tick. Readers only see events sent earlier in the same tick, so they should
+// run after the systems that send them (e.g. in a later stage).
+// Each reader keeps its own cursor so events are only read once per reader.
+// Buffers are allocated when the event is registered and never grow.
+use std::{any::Any, fmt, marker::PhantomData};
+
+use crate::{ResourceId, World, WorldError};
+
+/// A fixed capacity queue of the events sent this tick.
+pub struct Events<E> {
+    events: Vec<E>,
+    /// The id of the first event sent this tick.
+    start: usize,
+    capacity: usize,
+}
+
+impl<E> Events<E> {
+    /// Creates a new queue that may hold `capacity` events per tick.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            events: Vec::with_capacity(capacity),
+            start: 0,
+            capacity,
+        }
+    }
+
+    /// Sends the event. Returns an error if the queue is full for this tick.
+    pub fn send(&mut self, event: E) -> Result<(), WorldError> {
+        if self.events.len() >= self.capacity {
+            return Err(WorldError::EventQueueFull);
+        }
+
+        self.events.push(event);
+        Ok(())
+    }
+
+    /// Returns all events the reader hasn't seen yet, oldest first.
+    pub fn read<'a>(&'a self, reader: &mut EventReader<E>) -> EventIter<'a, E> {
+        let skip = reader
+            .next_event
+            .saturating_sub(self.start)
+            .min(self.events.len());
+
+        reader.next_event = self.start + self.events.len();
+
+        EventIter {
+            events: self.events[skip..].iter(),
+        }
+    }
+
+    /// Returns the number of events being retained.
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns whether there are no events being retained.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the events from this tick and starts a new one.
+    pub fn update(&mut self) {
+        self.start += self.events.len();
+        self.events.clear();
+    }
+}
+
+impl<E> fmt::Debug for Events<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Events")
+            .field("events", &self.events.len())
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+/// A cursor into an event queue. Each system that reads events should keep its own reader.
+pub struct EventReader<E> {
+    next_event: usize,
+    phantom: PhantomData<fn() -> E>,
+}
+
+impl<E> EventReader<E> {
+    /// Creates a new reader that will read all events still being retained.
+    pub fn new() -> Self {
+        Self {
+            next_event: 0,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E> Default for EventReader<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E> Clone for EventReader<E> {
+    fn clone(&self) -> Self {
+        Self {
+            next_event: self.next_event,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<E> fmt::Debug for EventReader<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("EventReader")
+            .field("next_event", &self.next_event)
+            .finish()
+    }
+}
+
+/// Iterator over unread events.
+pub struct EventIter<'a, E> {
+    events: std::slice::Iter<'a, E>,
+}
+
+impl<'a, E> EventIter<'a, E> {
+    fn empty() -> Self {
+        Self { events: [].iter() }
+    }
+}
+
+impl<'a, E> Iterator for EventIter<'a, E> {
+    type Item = &'a E;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.events.next()
+    }
+}
+
+/// Type erased event queue, so all queues can be updated at once.
+pub(crate) trait BackingEvents: fmt::Debug + Send {
+    fn update(&mut self);
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl<E> BackingEvents for Events<E>
+where
+    E: Send + 'static,
+{
+    fn update(&mut self) {
+        Events::update(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl World {
+    /// Registers the given event with the world, allowing up to `capacity` events to be sent per tick.
+    /// If the event has already been registered, does nothing.
+    pub fn register_event<E>(&mut self, capacity: usize)
+    where
+        E: Send + 'static,
+    {
+        let id = ResourceId::from::<E>();
+        if self.events.get(&id).is_none() {
+            self.events.insert(id, Box::new(Events::<E>::new(capacity)));
+        }
+    }
+
+    /// Sends the given event.
+    pub fn send<E>(&mut self, event: E) -> Result<(), WorldError>
+    where
+        E: 'static,
+    {
+        self.events_mut::<E>()
+            .ok_or(WorldError::EventNotRegistered)?
+            .send(event)
+    }
+
+    /// Returns all events of the given type the reader hasn't seen yet. Unregistered events return nothing.
+    pub fn read<'w, E>(&'w self, reader: &mut EventReader<E>) -> EventIter<'w, E>
+    where
+        E: 'static,
+    {
+        match self.events::<E>() {
+            Some(events) => events.read(reader),
+            None => EventIter::empty(),
+        }
+    }
+
+    /// Returns the queue for the given event.
+    pub fn events<E>(&self) -> Option<&Events<E>>
+    where
+        E: 'static,
+    {
+        self.events
+            .get(&ResourceId::from::<E>())
+            .and_then(|e| e.as_any().downcast_ref::<Events<E>>())
+    }
+
+    /// Returns the mutable queue for the given event.
+    pub fn events_mut<E>(&mut self) -> Option<&mut Events<E>>
+    where
+        E: 'static,
+    {
+        self.events
+            .get_mut(&ResourceId::from::<E>())
+            .and_then(|e| e.as_any_mut().downcast_mut::<Events<E>>())
+    }
+
+    /// Drops the events sent this tick for all event types. Should be called once at the end of each tick.
+    pub fn update_events(&mut self) {
+        for events in self.events.values_mut() {
+            events.update();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    struct Hit(u32);
+
+    #[test]
+    fn events_retained_for_one_tick() {
+        let mut world = World::new();
+        world.register_event::<Hit>(4);
+
+        let mut early = EventReader::<Hit>::new();
+        let mut late = EventReader::<Hit>::new();
+
+        // Tick 1: the early reader runs before the events are sent, so misses them
+        assert_eq!(0, world.read(&mut early).count());
+        world.send(Hit(1)).unwrap();
+        world.send(Hit(2)).unwrap();
+        assert_eq!(
+            vec![Hit(1), Hit(2)],
+            world.read(&mut late).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(0, world.read(&mut late).count());
+        world.update_events();
+
+        // Tick 2: events from tick 1 have been dropped
+        assert!(world.events::<Hit>().unwrap().is_empty());
+        assert_eq!(0, world.read(&mut early).count());
+        world.send(Hit(3)).unwrap();
+        assert_eq!(
+            vec![Hit(3)],
+            world.read(&mut early).copied().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Hit(3)],
+            world.read(&mut late).copied().collect::<Vec<_>>()
+        );
+        world.update_events();
+
+        // Tick 3: new readers only see events sent this tick
+        world.send(Hit(4)).unwrap();
+        assert_eq!(
+            vec![Hit(4)],
+            world
+                .read(&mut EventReader::<Hit>::new())
+                .copied()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn events_fixed_capacity() {
+        let mut world = World::new();
+        assert!(matches!(
+            world.send(Hit(0)),
+            Err(WorldError::EventNotRegistered)
+        ));
+        assert_eq!(0, world.read(&mut EventReader::<Hit>::new()).count());
+
+        world.register_event::<Hit>(2);
+        world.send(Hit(0)).unwrap();
+        world.send(Hit(1)).unwrap();
+        assert!(matches!(
+            world.send(Hit(2)),
+            Err(WorldError::EventQueueFull)
+        ));
+
+        // The capacity is per tick
+        world.update_events();
+        world.send(Hit(2)).unwrap();
+        world.send(Hit(3)).unwrap();
+        assert_eq!(2, world.events::<Hit>().unwrap().len());
+    }
+}
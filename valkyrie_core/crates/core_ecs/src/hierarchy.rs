@@ -0,0 +1,415 @@
+// Parent/child relationships + transforms.
+// Based on http://bitsquid.blogspot.com/2014/10/building-data-oriented-entity-system.html
+// Instead of each parent owning a list of children, the links are stored intrusively (first child + siblings)
+// so no allocations are needed when building up a hierarchy.
+use core_math::Mat4;
+
+use crate::{
+    query::{Query, QuerySource},
+    Component, Entity, EntityMap, System, SystemAccess, World, WorldError,
+};
+
+/// The links for an entity in the hierarchy.
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Hierarchy {
+    parent: Option<Entity>,
+    first_child: Option<Entity>,
+    next_sibling: Option<Entity>,
+    prev_sibling: Option<Entity>,
+}
+
+impl Component for Hierarchy {
+    fn map_entities(&mut self, map: &EntityMap) {
+        self.parent = map.map_option(self.parent);
+        self.first_child = map.map_option(self.first_child);
+        self.next_sibling = map.map_option(self.next_sibling);
+        self.prev_sibling = map.map_option(self.prev_sibling);
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl crate::SerializableComponent for Hierarchy {
+    const NAME: &'static str = "core_ecs::Hierarchy";
+}
+
+impl Hierarchy {
+    /// Returns the parent for the entity.
+    pub fn parent(&self) -> Option<Entity> {
+        self.parent
+    }
+}
+
+/// The transform of an entity relative to its parent.
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct LocalTransform(pub Mat4);
+
+impl Default for LocalTransform {
+    fn default() -> Self {
+        Self(Mat4::identity())
+    }
+}
+
+/// The transform of an entity in world space. Calculated from the `LocalTransform`s of the entity + its parents.
+#[derive(Copy, Clone, PartialEq, Debug, Component)]
+pub struct WorldTransform(pub Mat4);
+
+impl Default for WorldTransform {
+    fn default() -> Self {
+        Self(Mat4::identity())
+    }
+}
+
+/// Iterator over the direct children of an entity.
+pub struct Children<'w> {
+    world: &'w World,
+    next: Option<Entity>,
+}
+
+impl<'w> Iterator for Children<'w> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let child = self.next?;
+        self.next = self
+            .world
+            .get::<Hierarchy>(child)
+            .and_then(|h| h.next_sibling);
+        Some(child)
+    }
+}
+
+/// System that calculates the `WorldTransform` for all entities.
+pub struct TransformPropagation;
+
+impl System for TransformPropagation {
+    fn name(&self) -> &'static str {
+        "transform_propagation"
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .read::<Hierarchy>()
+            .read::<LocalTransform>()
+            .write::<WorldTransform>()
+    }
+
+    fn run(&mut self, world: &mut World) {
+        world.propagate_transforms();
+    }
+}
+
+type TransformQuery<'w> = (&'w LocalTransform, &'w mut WorldTransform);
+type TransformState<'w> = <TransformQuery<'w> as Query<'w>>::State;
+type HierarchyState<'w> = Option<<&'w Hierarchy as Query<'w>>::State>;
+
+impl World {
+    /// Returns the parent of the given entity.
+    pub fn parent(&self, entity: Entity) -> Option<Entity> {
+        self.get::<Hierarchy>(entity)?.parent
+    }
+
+    /// Returns the direct children of the given entity.
+    pub fn children(&self, entity: Entity) -> Children<'_> {
+        Children {
+            world: self,
+            next: self.get::<Hierarchy>(entity).and_then(|h| h.first_child),
+        }
+    }
+
+    /// Attaches the child to the given parent, detaching it from any previous parent. Requires `Hierarchy` to be registered.
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) -> Result<(), WorldError> {
+        // Prevent cycles
+        let mut ancestor = Some(parent);
+        while let Some(a) = ancestor {
+            if a == child {
+                return Err(WorldError::InvalidParent);
+            }
+            ancestor = self.parent(a);
+        }
+
+        self.add::<Hierarchy>(parent)?;
+        self.add::<Hierarchy>(child)?;
+        self.remove_parent(child);
+
+        // Append to the end so children stay in the order they were added
+        let mut last_child = None;
+        let mut next = self.get::<Hierarchy>(parent).and_then(|h| h.first_child);
+        while let Some(c) = next {
+            last_child = Some(c);
+            next = self.get::<Hierarchy>(c).and_then(|h| h.next_sibling);
+        }
+
+        match last_child {
+            Some(last_child) => {
+                if let Some(h) = self.get_mut::<Hierarchy>(last_child) {
+                    h.next_sibling = Some(child);
+                }
+            }
+            None => {
+                if let Some(h) = self.get_mut::<Hierarchy>(parent) {
+                    h.first_child = Some(child);
+                }
+            }
+        }
+
+        if let Some(h) = self.get_mut::<Hierarchy>(child) {
+            h.parent = Some(parent);
+            h.prev_sibling = last_child;
+            h.next_sibling = None;
+        }
+
+        Ok(())
+    }
+
+    /// Detaches the entity from its parent, making it a root.
+    pub fn remove_parent(&mut self, child: Entity) {
+        let links = match self.get::<Hierarchy>(child) {
+            Some(h) => *h,
+            None => return,
+        };
+
+        let parent = match links.parent {
+            Some(parent) => parent,
+            None => return,
+        };
+
+        match links.prev_sibling {
+            Some(prev) => {
+                if let Some(h) = self.get_mut::<Hierarchy>(prev) {
+                    h.next_sibling = links.next_sibling;
+                }
+            }
+            None => {
+                if let Some(h) = self.get_mut::<Hierarchy>(parent) {
+                    h.first_child = links.next_sibling;
+                }
+            }
+        }
+
+        if let Some(next) = links.next_sibling {
+            if let Some(h) = self.get_mut::<Hierarchy>(next) {
+                h.prev_sibling = links.prev_sibling;
+            }
+        }
+
+        if let Some(h) = self.get_mut::<Hierarchy>(child) {
+            h.parent = None;
+            h.next_sibling = None;
+            h.prev_sibling = None;
+        }
+    }
+
+    /// Kills the given entity and all of its descendants.
+    pub fn kill_recursive(&mut self, entity: Entity) {
+        // Killing a child detaches it, so the first child advances each time
+        while let Some(child) = self.get::<Hierarchy>(entity).and_then(|h| h.first_child) {
+            self.kill_recursive(child);
+        }
+
+        self.kill(entity);
+    }
+
+    /// Unlinks the entity from the hierarchy, orphaning any children. Called when killing an entity.
+    pub(crate) fn detach_hierarchy(&mut self, entity: Entity) {
+        self.remove_parent(entity);
+
+        while let Some(child) = self.get::<Hierarchy>(entity).and_then(|h| h.first_child) {
+            self.remove_parent(child);
+        }
+    }
+
+    /// Calculates the `WorldTransform` for all entities that have a `LocalTransform`.
+    /// Roots without transforms, such as group nodes, act as the identity for their children.
+    pub fn propagate_transforms(&mut self) {
+        unsafe {
+            let source = QuerySource::world(self);
+            let mut transforms = match TransformQuery::init(&source) {
+                Some(state) => state,
+                None => return,
+            };
+            let hierarchy = <&Hierarchy as Query>::init(&source);
+            let candidates = TransformQuery::candidates(&transforms).unwrap_or(&[]);
+
+            for root in candidates {
+                let root = *root;
+
+                // Children are calculated when their parent is visited.
+                let is_root = match &hierarchy {
+                    Some(h) => (**h).get(root).and_then(|h| h.parent).is_none(),
+                    None => true,
+                };
+
+                if is_root && TransformQuery::matches(&transforms, root) {
+                    propagate(&mut transforms, hierarchy, root, Mat4::identity());
+                }
+            }
+
+            // Roots without transforms aren't candidates above, so start from any that have children.
+            if let Some(h) = hierarchy {
+                for root in (*h).entities() {
+                    let root = *root;
+                    let is_root = (*h)
+                        .get(root)
+                        .is_some_and(|h| h.parent.is_none() && h.first_child.is_some());
+
+                    if is_root && !TransformQuery::matches(&transforms, root) {
+                        propagate(&mut transforms, hierarchy, root, Mat4::identity());
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Calculates the world transform for the entity, then recurses down to its children.
+///
+/// # Safety
+/// Each entity may only be visited once, which the cycle checks in `set_parent()` guarantee.
+unsafe fn propagate<'w>(
+    transforms: &mut TransformState<'w>,
+    hierarchy: HierarchyState<'w>,
+    entity: Entity,
+    parent_transform: Mat4,
+) {
+    // Entities without transforms pass their parents transform down
+    let transform = match TransformQuery::fetch(transforms, entity) {
+        Some((local, world)) => {
+            world.0 = parent_transform * local.0;
+            world.0
+        }
+        None => parent_transform,
+    };
+
+    if let Some(hierarchy) = hierarchy {
+        let mut next = (*hierarchy).get(entity).and_then(|h| h.first_child);
+        while let Some(child) = next {
+            propagate(transforms, Some(hierarchy), child, transform);
+            next = (*hierarchy).get(child).and_then(|h| h.next_sibling);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core_math::Vec3;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Hierarchy>(10);
+        world.register::<LocalTransform>(10);
+        world.register::<WorldTransform>(10);
+        world
+    }
+
+    fn spawn(world: &mut World, x: f32) -> Entity {
+        let entity = world.add_entity();
+        *world.add::<LocalTransform>(entity).unwrap() =
+            LocalTransform(Mat4::from_translation(Vec3::new(x, 0., 0.)));
+        world.add::<WorldTransform>(entity).unwrap();
+        entity
+    }
+
+    #[test]
+    fn hierarchy_set_parent() {
+        let mut world = world();
+        let parent = world.add_entity();
+        let a = world.add_entity();
+        let b = world.add_entity();
+        let c = world.add_entity();
+
+        world.set_parent(a, parent).unwrap();
+        world.set_parent(b, parent).unwrap();
+        world.set_parent(c, parent).unwrap();
+        assert_eq!(Some(parent), world.parent(a));
+        assert_eq!(vec![a, b, c], world.children(parent).collect::<Vec<_>>());
+
+        world.remove_parent(b);
+        assert_eq!(None, world.parent(b));
+        assert_eq!(vec![a, c], world.children(parent).collect::<Vec<_>>());
+
+        // Reparenting moves it
+        world.set_parent(c, a).unwrap();
+        assert_eq!(vec![a], world.children(parent).collect::<Vec<_>>());
+        assert_eq!(vec![c], world.children(a).collect::<Vec<_>>());
+
+        // Cycles are rejected
+        assert!(world.set_parent(parent, c).is_err());
+        assert!(world.set_parent(a, a).is_err());
+    }
+
+    #[test]
+    fn hierarchy_kill() {
+        let mut world = world();
+        let parent = world.add_entity();
+        let child = world.add_entity();
+        let grandchild = world.add_entity();
+        world.set_parent(child, parent).unwrap();
+        world.set_parent(grandchild, child).unwrap();
+
+        // Killing orphans the children
+        world.kill(child);
+        assert_eq!(0, world.children(parent).count());
+        assert_eq!(None, world.parent(grandchild));
+        assert_eq!(2, world.entity_len());
+
+        let child = world.add_entity();
+        world.set_parent(child, parent).unwrap();
+        world.set_parent(grandchild, child).unwrap();
+
+        world.kill_recursive(parent);
+        assert_eq!(0, world.entity_len());
+    }
+
+    #[test]
+    fn propagate_transforms() {
+        let mut world = world();
+        let character = spawn(&mut world, 1.);
+        let weapon = spawn(&mut world, 2.);
+        let sight = spawn(&mut world, 3.);
+        let camera = spawn(&mut world, 10.);
+
+        // Non transform entities pass through their parent
+        let socket = world.add_entity();
+
+        world.set_parent(weapon, character).unwrap();
+        world.set_parent(socket, weapon).unwrap();
+        world.set_parent(sight, socket).unwrap();
+
+        world.propagate_transforms();
+
+        let translation =
+            |world: &World, e| world.get::<WorldTransform>(e).unwrap().0.translation();
+        assert_eq!(Vec3::new(1., 0., 0.), translation(&world, character));
+        assert_eq!(Vec3::new(3., 0., 0.), translation(&world, weapon));
+        assert_eq!(Vec3::new(6., 0., 0.), translation(&world, sight));
+        assert_eq!(Vec3::new(10., 0., 0.), translation(&world, camera));
+
+        // Attach the camera + rerun through a schedule
+        world.set_parent(camera, character).unwrap();
+        let mut schedule = crate::Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule.add_system("update", TransformPropagation).unwrap();
+        schedule.run(&mut world);
+
+        assert_eq!(Vec3::new(11., 0., 0.), translation(&world, camera));
+    }
+
+    #[test]
+    fn propagate_transforms_untransformed_root() {
+        let mut world = world();
+        let group = world.add_entity();
+        let a = spawn(&mut world, 1.);
+        let b = spawn(&mut world, 2.);
+
+        world.set_parent(a, group).unwrap();
+        world.set_parent(b, a).unwrap();
+        world.propagate_transforms();
+
+        let translation =
+            |world: &World, e| world.get::<WorldTransform>(e).unwrap().0.translation();
+        assert_eq!(Vec3::new(1., 0., 0.), translation(&world, a));
+        assert_eq!(Vec3::new(3., 0., 0.), translation(&world, b));
+    }
+}
@@ -1,10 +1,57 @@
 // Based on http://bitsquid.blogspot.com/2014/08/building-data-oriented-entity-system.html
 
+// Allows the derive macros to refer to `core_ecs` from within this crate.
+extern crate self as core_ecs;
+
+mod bit_set;
+mod checksum;
+mod commands;
 mod component_store;
+mod dump;
 mod entity;
 mod entity_manager;
+mod entity_map;
+mod events;
+mod hierarchy;
+mod merge;
+#[cfg(feature = "serialize")]
+mod prefab;
+mod query;
+mod reflect;
+mod schedule;
+#[cfg(feature = "serialize")]
+mod serialization;
+mod snapshot;
+mod sparse_array;
+mod spatial;
+mod system_world;
 
-pub use component_store::{ComponentStore, ComponentStoreError};
+pub use checksum::{Checksum, HashComponent};
+pub use commands::Commands;
+pub use component_store::{ComponentStore, ComponentStoreError, ComponentTicks};
+pub use core_ecs_derive::{Component, HashComponent};
+pub use dump::{ComponentDump, DumpChange, DumpDiff, EntityDump, WorldDump};
 pub use entity::Entity;
+pub use entity_map::EntityMap;
+pub use events::{EventIter, EventReader, Events};
 mod world;
-pub use world::{Component, World, WorldError};
+pub use hierarchy::{Children, Hierarchy, LocalTransform, TransformPropagation, WorldTransform};
+#[cfg(feature = "serialize")]
+pub use prefab::{PrefabError, Prefabs};
+pub use query::{
+    Added, Changed, ComponentAccess, Query, QueryFilter, QueryIter, QuerySource, ReadOnlyQuery,
+    RemovedComponents, With, Without,
+};
+pub use reflect::{Field, Reflect, ReflectError};
+pub use schedule::{
+    FnSystem, ParallelFnSystem, ParallelSystem, Schedule, ScheduleError, System, SystemAccess,
+    SystemConfig,
+};
+#[cfg(feature = "serialize")]
+pub use serialization::{
+    SerializableComponent, SerializationError, SerializationRegistry, FORMAT_VERSION,
+};
+pub use snapshot::WorldSnapshot;
+pub use spatial::{SpatialExtent, SpatialIndex, SpatialIndexUpdate, SpatialPosition};
+pub use system_world::SystemWorld;
+pub use world::{Component, ComponentHook, ResourceId, World, WorldError};
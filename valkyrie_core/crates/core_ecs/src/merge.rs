@@ -0,0 +1,240 @@
+// Moving entities between worlds, such as streaming in a level chunk that was built in a staging world,
+// or unloading a region into its own world. Moved entities are given new handles in the target world,
+// and components are updated through `Component::map_entities()`. References to entities that weren't moved become `None`.
+// The source world fires `on_remove` hooks as the entities are killed, the target fires `on_add` hooks once everything is moved.
+use crate::{
+    world::HookKind, ComponentStoreError, Entity, EntityMap, Hierarchy, World, WorldError,
+};
+
+impl World {
+    /// Moves all entities from the staging world into this world, returning the handles they were given.
+    /// The staging world is left empty, but keeps its registered components so it may be reused.
+    pub fn merge(&mut self, staging: &mut World) -> Result<EntityMap, WorldError> {
+        let entities = staging.entities().to_vec();
+        staging.move_entities(&entities, self)
+    }
+
+    /// Moves the given entities into the target world, returning the handles they were given.
+    /// Parent/child links to entities that aren't moved are removed.
+    pub fn extract(
+        &mut self,
+        entities: &[Entity],
+        target: &mut World,
+    ) -> Result<EntityMap, WorldError> {
+        self.move_entities(entities, target)
+    }
+
+    fn move_entities(
+        &mut self,
+        entities: &[Entity],
+        target: &mut World,
+    ) -> Result<EntityMap, WorldError> {
+        // Check everything can be moved before changing either world
+        for entity in entities.iter() {
+            if !self.is_alive(*entity) {
+                return Err(WorldError::EntityNotAlive);
+            }
+        }
+        for (id, store) in self.components.iter() {
+            let used = entities.iter().filter(|e| store.contains(**e)).count();
+            match target.components.get(id) {
+                Some(target_store) if used > target_store.available() => {
+                    return Err(ComponentStoreError::BufferOverflow.into());
+                }
+                None if used > 0 => return Err(WorldError::ComponentNotRegistered),
+                _ => {}
+            }
+        }
+
+        let mut map = EntityMap::new();
+        for entity in entities.iter() {
+            map.insert(*entity, target.add_entity());
+        }
+
+        self.detach_unmoved(entities, &map);
+
+        for (id, store) in self.components.iter() {
+            if let Some(target_store) = target.components.get_mut(id) {
+                for entity in entities.iter() {
+                    if let Some(new_entity) = map.get(*entity) {
+                        store.copy_entity(*entity, target_store.as_mut(), new_entity, &map)?;
+                    }
+                }
+            }
+        }
+
+        for entity in entities.iter() {
+            if let Some(new_entity) = map.get(*entity) {
+                target.run_entity_hooks(HookKind::Add, new_entity);
+            }
+            self.kill(*entity);
+        }
+
+        Ok(map)
+    }
+
+    /// Removes parent/child links between moved + unmoved entities, so neither world points into the other.
+    fn detach_unmoved(&mut self, entities: &[Entity], map: &EntityMap) {
+        if self.store::<Hierarchy>().is_none() {
+            return;
+        }
+
+        for entity in entities.iter() {
+            if let Some(parent) = self.parent(*entity) {
+                if map.get(parent).is_none() {
+                    self.remove_parent(*entity);
+                }
+            }
+
+            while let Some(child) = self.children(*entity).find(|c| map.get(*c).is_none()) {
+                self.remove_parent(child);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Component, ComponentStoreError, Entity, EntityMap, World, WorldError};
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    /// Points at another entity.
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Target(Option<Entity>);
+    impl Component for Target {
+        fn map_entities(&mut self, map: &EntityMap) {
+            self.0 = map.map_option(self.0);
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct Added(Vec<(Entity, Option<i32>)>);
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Position>(8);
+        world.register::<Target>(8);
+        world.register::<crate::Hierarchy>(8);
+        world
+    }
+
+    #[test]
+    fn world_merge_remaps_entities() {
+        let mut live = world();
+        let existing = live.add_entity();
+        live.insert(existing, Position(-1)).unwrap();
+        live.insert_resource(Added::default());
+        live.on_add::<Position>(|world, entity| {
+            let position = world.get::<Position>(entity).map(|p| p.0);
+            let added = world.resource_mut::<Added>().unwrap();
+            added.0.push((entity, position));
+        });
+
+        // Build the chunk on another thread
+        let (staging, a, b) = std::thread::spawn(|| {
+            let mut staging = world();
+            let a = staging.add_entity();
+            let b = staging.add_entity();
+            staging.insert(a, Position(1)).unwrap();
+            staging.insert(a, Target(Some(b))).unwrap();
+            staging.insert(b, Position(2)).unwrap();
+            staging.set_parent(b, a).unwrap();
+            (staging, a, b)
+        })
+        .join()
+        .unwrap();
+        let mut staging = staging;
+
+        let map = live.merge(&mut staging).unwrap();
+        let (new_a, new_b) = (map.get(a).unwrap(), map.get(b).unwrap());
+        assert_eq!(0, staging.entity_len());
+        assert_eq!(3, live.entity_len());
+
+        assert_eq!(Some(&Position(1)), live.get::<Position>(new_a));
+        assert_eq!(Some(&Target(Some(new_b))), live.get::<Target>(new_a));
+        assert_eq!(Some(new_a), live.parent(new_b));
+        assert_eq!(Some(&Position(-1)), live.get::<Position>(existing));
+        assert_eq!(
+            vec![(new_a, Some(1)), (new_b, Some(2))],
+            live.resource::<Added>().unwrap().0
+        );
+
+        // The staging world can be reused
+        let c = staging.add_entity();
+        staging.insert(c, Position(3)).unwrap();
+        let map = live.merge(&mut staging).unwrap();
+        assert_eq!(
+            Some(&Position(3)),
+            live.get::<Position>(map.get(c).unwrap())
+        );
+    }
+
+    #[test]
+    fn world_extract_region() {
+        let mut live = world();
+        let root = live.add_entity();
+        let a = live.add_entity();
+        let b = live.add_entity();
+        let c = live.add_entity();
+        live.set_parent(a, root).unwrap();
+        live.set_parent(b, a).unwrap();
+        live.set_parent(c, a).unwrap();
+        live.insert(a, Target(Some(root))).unwrap();
+        live.insert(b, Target(Some(a))).unwrap();
+        live.insert(c, Position(3)).unwrap();
+
+        let mut unloaded = world();
+        let map = live.extract(&[a, b], &mut unloaded).unwrap();
+        let (new_a, new_b) = (map.get(a).unwrap(), map.get(b).unwrap());
+
+        // Links inside the region are kept, links out of it are dropped
+        assert_eq!(2, unloaded.entity_len());
+        assert_eq!(None, unloaded.parent(new_a));
+        assert_eq!(Some(new_a), unloaded.parent(new_b));
+        assert_eq!(vec![new_b], unloaded.children(new_a).collect::<Vec<_>>());
+        assert_eq!(Some(&Target(None)), unloaded.get::<Target>(new_a));
+        assert_eq!(Some(&Target(Some(new_a))), unloaded.get::<Target>(new_b));
+
+        assert!(!live.is_alive(a) && !live.is_alive(b));
+        assert_eq!(0, live.children(root).count());
+        assert_eq!(None, live.parent(c));
+        assert_eq!(Some(&Position(3)), live.get::<Position>(c));
+    }
+
+    #[test]
+    fn world_merge_errors() {
+        let mut live = World::new();
+        let mut staging = world();
+        let a = staging.add_entity();
+        staging.insert(a, Position(1)).unwrap();
+
+        assert!(matches!(
+            live.merge(&mut staging),
+            Err(WorldError::ComponentNotRegistered)
+        ));
+        assert_eq!(0, live.entity_len());
+        assert_eq!(1, staging.entity_len());
+
+        // Nothing is moved unless everything fits
+        live.register::<Position>(1);
+        let b = staging.add_entity();
+        staging.insert(b, Position(2)).unwrap();
+        assert!(matches!(
+            live.merge(&mut staging),
+            Err(WorldError::ComponentStoreError(
+                ComponentStoreError::BufferOverflow
+            ))
+        ));
+        assert_eq!(0, live.entity_len());
+        assert_eq!(2, staging.entity_len());
+
+        staging.kill(a);
+        assert!(matches!(
+            staging.extract(&[a], &mut live),
+            Err(WorldError::EntityNotAlive)
+        ));
+    }
+}
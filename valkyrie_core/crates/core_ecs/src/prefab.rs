@@ -0,0 +1,425 @@
+// Named entity templates loaded from JSON or RON. Each prefab lists components by their `SerializableComponent::NAME`
+// along with the fields to set; fields that are left out use the components default value.
+// Prefabs may inherit from another prefab, overriding individual fields or removing inherited components:
+//
+// {
+//     "actor": { "components": { "Position": { "x": 0, "y": 0 }, "Debug": null } },
+//     "player": { "inherits": "actor", "components": { "Position": { "x": 10 } }, "remove": ["Debug"] }
+// }
+//
+// Everything is resolved + parsed when the prefabs are loaded, so spawning only clones components.
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+    Component, Entity, ResourceId, SerializationError, SerializationRegistry, World, WorldError,
+};
+
+#[derive(Debug)]
+pub enum PrefabError {
+    UnknownPrefab(String),
+    InheritanceCycle(String),
+    NoPrefabs,
+    SerializationError(SerializationError),
+    WorldError(WorldError),
+}
+
+impl From<SerializationError> for PrefabError {
+    fn from(error: SerializationError) -> Self {
+        Self::SerializationError(error)
+    }
+}
+
+impl From<WorldError> for PrefabError {
+    fn from(error: WorldError) -> Self {
+        Self::WorldError(error)
+    }
+}
+
+/// A prefab as it is written in the file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PrefabDocument {
+    #[serde(default)]
+    inherits: Option<String>,
+    #[serde(default)]
+    components: BTreeMap<String, Value>,
+    #[serde(default)]
+    remove: Vec<String>,
+}
+
+/// A component that is cloned onto entities spawned from a prefab.
+pub(crate) trait PrefabComponent: Send + Sync {
+    fn insert(&self, world: &mut World, entity: Entity) -> Result<(), WorldError>;
+}
+
+impl<C> PrefabComponent for C
+where
+    C: Component,
+{
+    fn insert(&self, world: &mut World, entity: Entity) -> Result<(), WorldError> {
+        world.insert(entity, self.clone())?;
+        Ok(())
+    }
+}
+
+/// A resolved prefab, with all inherited components.
+struct Prefab {
+    components: Vec<Box<dyn PrefabComponent>>,
+}
+
+/// A collection of named prefabs. May be inserted as a resource so entities can be spawned with `World::spawn_prefab()`.
+pub struct Prefabs {
+    prefabs: BTreeMap<String, Prefab>,
+}
+
+impl Prefabs {
+    /// Loads the prefabs from JSON. Components are looked up in the given registry.
+    pub fn from_json(registry: &SerializationRegistry, json: &str) -> Result<Self, PrefabError> {
+        let documents =
+            serde_json::from_str(json).map_err(|e| SerializationError::Json(e.to_string()))?;
+
+        Self::load(registry, documents)
+    }
+
+    /// Loads the prefabs from RON. Components are looked up in the given registry.
+    pub fn from_ron(registry: &SerializationRegistry, ron: &str) -> Result<Self, PrefabError> {
+        let documents = ron::from_str(ron).map_err(|e| SerializationError::Ron(e.to_string()))?;
+
+        Self::load(registry, documents)
+    }
+
+    /// Returns whether there is a prefab with the given name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.prefabs.contains_key(name)
+    }
+
+    /// Returns the names of all prefabs.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(|name| name.as_str())
+    }
+
+    /// Creates a new entity with all the components of the given prefab.
+    pub fn spawn(&self, world: &mut World, name: &str) -> Result<Entity, PrefabError> {
+        let prefab = self
+            .prefabs
+            .get(name)
+            .ok_or_else(|| PrefabError::UnknownPrefab(name.to_string()))?;
+
+        let entity = world.add_entity();
+        for component in prefab.components.iter() {
+            if let Err(error) = component.insert(world, entity) {
+                world.kill(entity);
+                return Err(error.into());
+            }
+        }
+
+        Ok(entity)
+    }
+
+    fn load(
+        registry: &SerializationRegistry,
+        documents: BTreeMap<String, PrefabDocument>,
+    ) -> Result<Self, PrefabError> {
+        let mut prefabs = BTreeMap::new();
+        for name in documents.keys() {
+            let mut components = vec![];
+            for (component, value) in resolve(&documents, name, &mut vec![])? {
+                let (default_value, prototype) = registry
+                    .prefab_fns(&component)
+                    .ok_or(SerializationError::UnknownComponent(component))?;
+
+                let mut full_value = default_value()?;
+                merge(&mut full_value, value);
+                components.push(prototype(full_value)?);
+            }
+
+            prefabs.insert(name.clone(), Prefab { components });
+        }
+
+        Ok(Self { prefabs })
+    }
+}
+
+/// Returns the components of the prefab, including inherited ones.
+fn resolve<'a>(
+    documents: &'a BTreeMap<String, PrefabDocument>,
+    name: &'a str,
+    visiting: &mut Vec<&'a str>,
+) -> Result<BTreeMap<String, Value>, PrefabError> {
+    if visiting.contains(&name) {
+        return Err(PrefabError::InheritanceCycle(name.to_string()));
+    }
+
+    let document = documents
+        .get(name)
+        .ok_or_else(|| PrefabError::UnknownPrefab(name.to_string()))?;
+
+    visiting.push(name);
+    let mut components = match &document.inherits {
+        Some(parent) => resolve(documents, parent, visiting)?,
+        None => BTreeMap::new(),
+    };
+    visiting.pop();
+
+    for removed in document.remove.iter() {
+        components.remove(removed);
+    }
+
+    for (component, value) in document.components.iter() {
+        match components.get_mut(component) {
+            Some(existing) => merge(existing, value.clone()),
+            None => {
+                components.insert(component.clone(), value.clone());
+            }
+        }
+    }
+
+    Ok(components)
+}
+
+/// Merges the overrides into the value. Objects are merged field by field, `null` keeps the existing value
+/// and anything else is replaced.
+fn merge(value: &mut Value, overrides: Value) {
+    match (value, overrides) {
+        (Value::Object(value), Value::Object(overrides)) => merge_objects(value, overrides),
+        (value, Value::Null) if !value.is_null() => {}
+        (value, overrides) => *value = overrides,
+    }
+}
+
+fn merge_objects(value: &mut Map<String, Value>, overrides: Map<String, Value>) {
+    for (field, field_override) in overrides.into_iter() {
+        match value.get_mut(&field) {
+            Some(existing) => merge(existing, field_override),
+            None => {
+                value.insert(field, field_override);
+            }
+        }
+    }
+}
+
+impl World {
+    /// Spawns the given prefab from the `Prefabs` resource.
+    pub fn spawn_prefab(&mut self, name: &str) -> Result<Entity, PrefabError> {
+        // Take the prefabs out while spawning so the world may be mutated, then put the same allocation back.
+        let id = ResourceId::from::<Prefabs>();
+        let prefabs = self.resources.remove(&id).ok_or(PrefabError::NoPrefabs)?;
+        let result = match prefabs.downcast_ref::<Prefabs>() {
+            Some(p) => p.spawn(self, name),
+            None => Err(PrefabError::NoPrefabs),
+        };
+        self.resources.insert(id, prefabs);
+
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SerializableComponent;
+    use serde::Serialize;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+    impl Component for Position {}
+    impl SerializableComponent for Position {
+        const NAME: &'static str = "Position";
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+    struct Collidable {
+        radius: u32,
+        solid: bool,
+    }
+    impl Component for Collidable {}
+    impl SerializableComponent for Collidable {
+        const NAME: &'static str = "Collidable";
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+    struct Player;
+    impl Component for Player {}
+    impl SerializableComponent for Player {
+        const NAME: &'static str = "Player";
+    }
+
+    fn registry() -> SerializationRegistry {
+        let mut registry = SerializationRegistry::new();
+        registry.register::<Position>().unwrap();
+        registry.register::<Collidable>().unwrap();
+        registry.register::<Player>().unwrap();
+        registry
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Position>(10);
+        world.register::<Collidable>(10);
+        world.register::<Player>(10);
+        world
+    }
+
+    #[test]
+    fn prefab_inheritance_and_overrides() {
+        let json = r#"{
+            "actor": {
+                "components": {
+                    "Position": { "x": 1, "y": 2 },
+                    "Collidable": { "radius": 5, "solid": true }
+                }
+            },
+            "player": {
+                "inherits": "actor",
+                "components": { "Position": { "x": 10 }, "Player": null }
+            },
+            "ghost": {
+                "inherits": "player",
+                "components": { "Position": { "y": 20 } },
+                "remove": ["Collidable"]
+            }
+        }"#;
+        let prefabs = Prefabs::from_json(&registry(), json).unwrap();
+        assert_eq!(
+            vec!["actor", "ghost", "player"],
+            prefabs.names().collect::<Vec<_>>()
+        );
+
+        let mut world = world();
+        world.insert_resource(prefabs);
+
+        let actor = world.spawn_prefab("actor").unwrap();
+        assert_eq!(Some(&Position { x: 1, y: 2 }), world.get::<Position>(actor));
+        assert_eq!(None, world.get::<Player>(actor));
+
+        let player = world.spawn_prefab("player").unwrap();
+        assert_eq!(
+            Some(&Position { x: 10, y: 2 }),
+            world.get::<Position>(player)
+        );
+        assert_eq!(
+            Some(&Collidable {
+                radius: 5,
+                solid: true
+            }),
+            world.get::<Collidable>(player)
+        );
+        assert_eq!(Some(&Player), world.get::<Player>(player));
+
+        let ghost = world.spawn_prefab("ghost").unwrap();
+        assert_eq!(
+            Some(&Position { x: 10, y: 20 }),
+            world.get::<Position>(ghost)
+        );
+        assert_eq!(None, world.get::<Collidable>(ghost));
+
+        // Spawned components are copies
+        world.get_mut::<Position>(player).unwrap().x = 99;
+        let other = world.spawn_prefab("player").unwrap();
+        assert_eq!(
+            Some(&Position { x: 10, y: 2 }),
+            world.get::<Position>(other)
+        );
+    }
+
+    #[test]
+    fn prefab_from_ron() {
+        let ron = r#"{
+            "actor": (
+                components: {
+                    "Position": (),
+                },
+            ),
+            "player": (
+                inherits: Some("actor"),
+                components: {
+                    "Position": { "x": 3 },
+                    "Collidable": { "radius": 200 },
+                    "Player": (),
+                },
+            ),
+        }"#;
+        let prefabs = Prefabs::from_ron(&registry(), ron).unwrap();
+
+        let mut world = world();
+        let player = prefabs.spawn(&mut world, "player").unwrap();
+        assert_eq!(
+            Some(&Position { x: 3, y: 0 }),
+            world.get::<Position>(player)
+        );
+        assert_eq!(
+            Some(&Collidable {
+                radius: 200,
+                solid: false
+            }),
+            world.get::<Collidable>(player)
+        );
+        assert_eq!(Some(&Player), world.get::<Player>(player));
+
+        let actor = prefabs.spawn(&mut world, "actor").unwrap();
+        assert_eq!(Some(&Position::default()), world.get::<Position>(actor));
+    }
+
+    #[test]
+    fn prefab_errors() {
+        let registry = registry();
+
+        let cycle = r#"{ "a": { "inherits": "b" }, "b": { "inherits": "a" } }"#;
+        assert!(matches!(
+            Prefabs::from_json(&registry, cycle),
+            Err(PrefabError::InheritanceCycle(_))
+        ));
+
+        let missing_parent = r#"{ "a": { "inherits": "b" } }"#;
+        assert!(matches!(
+            Prefabs::from_json(&registry, missing_parent),
+            Err(PrefabError::UnknownPrefab(name)) if name == "b"
+        ));
+
+        let unknown_component = r#"{ "a": { "components": { "Velocity": {} } } }"#;
+        assert!(matches!(
+            Prefabs::from_json(&registry, unknown_component),
+            Err(PrefabError::SerializationError(
+                SerializationError::UnknownComponent(_)
+            ))
+        ));
+
+        let invalid_field = r#"{ "a": { "components": { "Position": { "x": "left" } } } }"#;
+        assert!(matches!(
+            Prefabs::from_json(&registry, invalid_field),
+            Err(PrefabError::SerializationError(SerializationError::Json(_)))
+        ));
+
+        let mut world = world();
+        assert!(matches!(
+            world.spawn_prefab("a"),
+            Err(PrefabError::NoPrefabs)
+        ));
+
+        world.insert_resource(Prefabs::from_json(&registry, "{}").unwrap());
+        assert!(matches!(
+            world.spawn_prefab("a"),
+            Err(PrefabError::UnknownPrefab(_))
+        ));
+        assert_eq!(0, world.entity_len());
+
+        // Components that aren't registered with the world leave nothing behind
+        let mut unregistered = World::new();
+        let prefabs = Prefabs::from_json(
+            &registry,
+            r#"{ "a": { "components": { "Player": null } } }"#,
+        )
+        .unwrap();
+        assert!(matches!(
+            prefabs.spawn(&mut unregistered, "a"),
+            Err(PrefabError::WorldError(WorldError::ComponentNotRegistered))
+        ));
+        assert_eq!(0, unregistered.entity_len());
+    }
+}
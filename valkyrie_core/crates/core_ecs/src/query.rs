@@ -0,0 +1,908 @@
+// Queries join multiple component stores. Instead of looking up + downcasting each store for every entity,
+// the stores are resolved once when the query is created and the smallest dense entity list drives iteration.
+use std::marker::PhantomData;
+
+use crate::{
+    system_world::SystemStores, world::ResourceId, Component, ComponentStore, Entity, World,
+};
+
+/// The type of access a query requires for a component.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ComponentAccess {
+    Read(ResourceId),
+    Write(ResourceId),
+}
+
+impl ComponentAccess {
+    /// Returns the component or resource that is accessed.
+    pub fn resource_id(&self) -> ResourceId {
+        match self {
+            ComponentAccess::Read(id) => *id,
+            ComponentAccess::Write(id) => *id,
+        }
+    }
+
+    /// Returns whether the two accesses may not be performed at the same time.
+    pub fn conflicts_with(&self, other: &ComponentAccess) -> bool {
+        match (self, other) {
+            (ComponentAccess::Read(_), ComponentAccess::Read(_)) => false,
+            _ => self.resource_id() == other.resource_id(),
+        }
+    }
+}
+
+/// Where a query resolves its stores from. Either the whole world, or only the stores a system declared.
+pub struct QuerySource<'w> {
+    source: Source<'w>,
+}
+
+enum Source<'w> {
+    World(*mut World),
+    /// Resolved on the scheduling thread, so parallel systems never borrow the world itself.
+    System(&'w SystemStores),
+}
+
+impl<'w> QuerySource<'w> {
+    /// Resolves stores from the world.
+    ///
+    /// # Safety
+    /// `world` must be valid for `'w`.
+    pub(crate) unsafe fn world(world: *mut World) -> Self {
+        Self {
+            source: Source::World(world),
+        }
+    }
+
+    /// Resolves stores from those a system declared.
+    pub(crate) fn system(stores: &'w SystemStores) -> Self {
+        Self {
+            source: Source::System(stores),
+        }
+    }
+
+    /// Returns the store for the given component, if available.
+    ///
+    /// # Safety
+    /// The store must be valid for `'w`.
+    unsafe fn store<C>(&self) -> Option<*const ComponentStore<C>>
+    where
+        C: Component,
+    {
+        match self.source {
+            Source::World(world) => (*world)
+                .store::<C>()
+                .map(|store| store as *const ComponentStore<C>),
+            Source::System(stores) => stores.store::<C>(),
+        }
+    }
+
+    /// Returns the mutable store for the given component, if available.
+    ///
+    /// # Safety
+    /// The store must be valid for `'w` and must have been derived from a mutable reference.
+    unsafe fn store_mut<C>(&self) -> Option<*mut ComponentStore<C>>
+    where
+        C: Component,
+    {
+        match self.source {
+            Source::World(world) => (*world)
+                .store_mut::<C>()
+                .map(|store| store as *mut ComponentStore<C>),
+            Source::System(stores) => stores.store_mut::<C>(),
+        }
+    }
+
+    /// Returns all alive entities.
+    ///
+    /// # Safety
+    /// The entities must be valid for `'w`.
+    unsafe fn entities(&self) -> &'w [Entity] {
+        match self.source {
+            Source::World(world) => (*world).entities(),
+            Source::System(stores) => stores.entities(),
+        }
+    }
+
+    /// Returns the tick trackers were last cleared on.
+    ///
+    /// # Safety
+    /// The world must be valid for `'w`.
+    unsafe fn last_change_tick(&self) -> u32 {
+        match self.source {
+            Source::World(world) => (*world).last_change_tick(),
+            Source::System(stores) => stores.last_change_tick(),
+        }
+    }
+}
+
+/// Something that can be fetched for an entity by a query. Implemented for `Entity`, `&C`, `&mut C`, `Option<Q>` and tuples of queries.
+///
+/// # Safety
+/// `access()` must list every component the query reads or writes, as it is used to prevent handing out aliased mutable references.
+pub unsafe trait Query<'w> {
+    /// The value returned for each entity.
+    type Item;
+    /// The resolved stores for the query.
+    type State;
+
+    /// Adds the components this query accesses.
+    fn access(access: &mut Vec<ComponentAccess>);
+
+    /// Resolves the stores for the query. Returns `None` if nothing can match.
+    ///
+    /// # Safety
+    /// `source` must be valid for `'w`, and must have been derived from a mutable reference if the query writes.
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State>;
+
+    /// Returns the entities that are guaranteed to contain all matches, if the query can provide them.
+    ///
+    /// # Safety
+    /// `state` must have been created by `init()`.
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]>;
+
+    /// Returns whether the given entity can be fetched. Has no side effects, so tuples can check every member before fetching any.
+    ///
+    /// # Safety
+    /// `state` must have been created by `init()`.
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool;
+
+    /// Fetches the item for the given entity. Mutable fetches mark the component as changed.
+    ///
+    /// # Safety
+    /// `state` must have been created by `init()` and the same entity may not be fetched twice while the items are alive.
+    unsafe fn fetch(state: &mut Self::State, entity: Entity) -> Option<Self::Item>;
+}
+
+/// Marker for queries that only read from the world.
+///
+/// # Safety
+/// Implementors may not write to any component.
+pub unsafe trait ReadOnlyQuery<'w>: Query<'w> {}
+
+/// Restricts which entities a query returns without fetching anything. Implemented for `With<C>`, `Without<C>`, `()` and tuples of filters.
+///
+/// # Safety
+/// Filters may only read from the world, and `access()` must list every component read.
+pub unsafe trait QueryFilter<'w> {
+    /// The resolved stores for the filter.
+    type State;
+
+    /// Adds the components this filter accesses.
+    fn access(access: &mut Vec<ComponentAccess>);
+
+    /// Resolves the stores for the filter. Returns `None` if nothing can match.
+    ///
+    /// # Safety
+    /// `source` must be valid for `'w`.
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State>;
+
+    /// Returns the entities that are guaranteed to contain all matches, if the filter can provide them.
+    ///
+    /// # Safety
+    /// `state` must have been created by `init()`.
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]>;
+
+    /// Returns whether the given entity passes the filter.
+    ///
+    /// # Safety
+    /// `state` must have been created by `init()`.
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool;
+}
+
+/// Filter that only returns entities with the given component.
+pub struct With<C>(PhantomData<C>);
+
+/// Filter that only returns entities without the given component.
+pub struct Without<C>(PhantomData<C>);
+
+/// Filter that only returns entities that had the given component added since trackers were last cleared.
+pub struct Added<C>(PhantomData<C>);
+
+/// Filter that only returns entities that had the given component added or mutably accessed since trackers were last cleared.
+pub struct Changed<C>(PhantomData<C>);
+
+/// Iterator over the entities that had the given component removed since trackers were last cleared.
+pub struct RemovedComponents<'w, C> {
+    entities: std::slice::Iter<'w, Entity>,
+    component: PhantomData<C>,
+}
+
+impl<'w, C> RemovedComponents<'w, C> {
+    pub(crate) fn new(entities: &'w [Entity]) -> Self {
+        Self {
+            entities: entities.iter(),
+            component: PhantomData,
+        }
+    }
+}
+
+impl<'w, C> Iterator for RemovedComponents<'w, C> {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.entities.next().copied()
+    }
+}
+
+/// Returns the smaller of the two candidate lists.
+fn smallest<'w>(a: Option<&'w [Entity]>, b: Option<&'w [Entity]>) -> Option<&'w [Entity]> {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            if a.len() <= b.len() {
+                Some(a)
+            } else {
+                Some(b)
+            }
+        }
+        (Some(a), None) => Some(a),
+        (None, b) => b,
+    }
+}
+
+unsafe impl<'w> Query<'w> for Entity {
+    type Item = Entity;
+    type State = ();
+
+    fn access(_access: &mut Vec<ComponentAccess>) {}
+
+    unsafe fn init(_source: &QuerySource<'w>) -> Option<Self::State> {
+        Some(())
+    }
+
+    unsafe fn candidates(_state: &Self::State) -> Option<&'w [Entity]> {
+        None
+    }
+
+    unsafe fn matches(_state: &Self::State, _entity: Entity) -> bool {
+        true
+    }
+
+    unsafe fn fetch(_state: &mut Self::State, entity: Entity) -> Option<Self::Item> {
+        Some(entity)
+    }
+}
+
+unsafe impl<'w> ReadOnlyQuery<'w> for Entity {}
+
+unsafe impl<'w, C> Query<'w> for &'w C
+where
+    C: Component,
+{
+    type Item = &'w C;
+    type State = *const ComponentStore<C>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        access.push(ComponentAccess::Read(ResourceId::from::<C>()));
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        source.store::<C>()
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+        (**state).candidates()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        (**state).contains(entity)
+    }
+
+    unsafe fn fetch(state: &mut Self::State, entity: Entity) -> Option<Self::Item> {
+        (**state).get(entity)
+    }
+}
+
+unsafe impl<'w, C> ReadOnlyQuery<'w> for &'w C where C: Component {}
+
+/// Resolved store for a mutable component query.
+pub struct WriteState<C>
+where
+    C: Component,
+{
+    store: *mut ComponentStore<C>,
+    components: *mut C,
+}
+
+unsafe impl<'w, C> Query<'w> for &'w mut C
+where
+    C: Component,
+{
+    type Item = &'w mut C;
+    type State = WriteState<C>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        access.push(ComponentAccess::Write(ResourceId::from::<C>()));
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        let store = &mut *source.store_mut::<C>()?;
+
+        // Grab the buffer before the store is shared so that references can be handed out per component.
+        let components = store.components_ptr();
+
+        Some(WriteState {
+            store: store as *mut ComponentStore<C>,
+            components,
+        })
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+        (*state.store).candidates()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        (*state.store).contains(entity)
+    }
+
+    unsafe fn fetch(state: &mut Self::State, entity: Entity) -> Option<Self::Item> {
+        let (index, tick_index) = (*state.store).indexes(entity)?;
+        ComponentStore::mark_changed_raw(state.store, tick_index);
+        Some(&mut *state.components.add(index))
+    }
+}
+
+unsafe impl<'w, Q> Query<'w> for Option<Q>
+where
+    Q: Query<'w>,
+{
+    type Item = Option<Q::Item>;
+    type State = Option<Q::State>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        Q::access(access);
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        Some(Q::init(source))
+    }
+
+    unsafe fn candidates(_state: &Self::State) -> Option<&'w [Entity]> {
+        None
+    }
+
+    unsafe fn matches(_state: &Self::State, _entity: Entity) -> bool {
+        true
+    }
+
+    unsafe fn fetch(state: &mut Self::State, entity: Entity) -> Option<Self::Item> {
+        match state {
+            Some(state) => Some(Q::fetch(state, entity)),
+            None => Some(None),
+        }
+    }
+}
+
+unsafe impl<'w, Q> ReadOnlyQuery<'w> for Option<Q> where Q: ReadOnlyQuery<'w> {}
+
+unsafe impl<'w> QueryFilter<'w> for () {
+    type State = ();
+
+    fn access(_access: &mut Vec<ComponentAccess>) {}
+
+    unsafe fn init(_source: &QuerySource<'w>) -> Option<Self::State> {
+        Some(())
+    }
+
+    unsafe fn candidates(_state: &Self::State) -> Option<&'w [Entity]> {
+        None
+    }
+
+    unsafe fn matches(_state: &Self::State, _entity: Entity) -> bool {
+        true
+    }
+}
+
+unsafe impl<'w, C> QueryFilter<'w> for With<C>
+where
+    C: Component,
+{
+    type State = *const ComponentStore<C>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        access.push(ComponentAccess::Read(ResourceId::from::<C>()));
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        source.store::<C>()
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+        (**state).candidates()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        (**state).contains(entity)
+    }
+}
+
+unsafe impl<'w, C> QueryFilter<'w> for Without<C>
+where
+    C: Component,
+{
+    type State = Option<*const ComponentStore<C>>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        access.push(ComponentAccess::Read(ResourceId::from::<C>()));
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        // An unregistered component means every entity passes.
+        Some(source.store::<C>())
+    }
+
+    unsafe fn candidates(_state: &Self::State) -> Option<&'w [Entity]> {
+        None
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        match state {
+            Some(store) => !(**store).contains(entity),
+            None => true,
+        }
+    }
+}
+
+/// Resolved store for a change detection filter.
+pub struct ChangeState<C>
+where
+    C: Component,
+{
+    store: *const ComponentStore<C>,
+    last_change_tick: u32,
+}
+
+unsafe impl<'w, C> QueryFilter<'w> for Added<C>
+where
+    C: Component,
+{
+    type State = ChangeState<C>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        access.push(ComponentAccess::Read(ResourceId::from::<C>()));
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        let last_change_tick = source.last_change_tick();
+        source.store::<C>().map(|store| ChangeState {
+            store,
+            last_change_tick,
+        })
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+        (*state.store).candidates()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        match (*state.store).ticks(entity) {
+            Some(ticks) => ticks.is_added(state.last_change_tick),
+            None => false,
+        }
+    }
+}
+
+unsafe impl<'w, C> QueryFilter<'w> for Changed<C>
+where
+    C: Component,
+{
+    type State = ChangeState<C>;
+
+    fn access(access: &mut Vec<ComponentAccess>) {
+        access.push(ComponentAccess::Read(ResourceId::from::<C>()));
+    }
+
+    unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+        <Added<C> as QueryFilter>::init(source)
+    }
+
+    unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+        (*state.store).candidates()
+    }
+
+    unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+        match (*state.store).ticks(entity) {
+            Some(ticks) => ticks.is_changed(state.last_change_tick),
+            None => false,
+        }
+    }
+}
+
+macro_rules! impl_query_tuple {
+    ($($name: ident),*) => {
+        #[allow(non_snake_case)]
+        unsafe impl<'w, $($name),*> Query<'w> for ($($name,)*)
+        where
+            $($name: Query<'w>),*
+        {
+            type Item = ($($name::Item,)*);
+            type State = ($($name::State,)*);
+
+            fn access(access: &mut Vec<ComponentAccess>) {
+                $($name::access(access);)*
+            }
+
+            unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+                Some(($($name::init(source)?,)*))
+            }
+
+            unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+                let ($($name,)*) = state;
+                let candidates = None;
+                $(let candidates = smallest(candidates, $name::candidates($name));)*
+                candidates
+            }
+
+            unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches($name, entity))&&*
+            }
+
+            unsafe fn fetch(state: &mut Self::State, entity: Entity) -> Option<Self::Item> {
+                // Check every member first, so a member that doesn't match can't leave others marked as changed.
+                if !Self::matches(state, entity) {
+                    return None;
+                }
+
+                let ($($name,)*) = state;
+                Some(($($name::fetch($name, entity)?,)*))
+            }
+        }
+
+        unsafe impl<'w, $($name),*> ReadOnlyQuery<'w> for ($($name,)*)
+        where
+            $($name: ReadOnlyQuery<'w>),*
+        {}
+
+        #[allow(non_snake_case)]
+        unsafe impl<'w, $($name),*> QueryFilter<'w> for ($($name,)*)
+        where
+            $($name: QueryFilter<'w>),*
+        {
+            type State = ($($name::State,)*);
+
+            fn access(access: &mut Vec<ComponentAccess>) {
+                $($name::access(access);)*
+            }
+
+            unsafe fn init(source: &QuerySource<'w>) -> Option<Self::State> {
+                Some(($($name::init(source)?,)*))
+            }
+
+            unsafe fn candidates(state: &Self::State) -> Option<&'w [Entity]> {
+                let ($($name,)*) = state;
+                let candidates = None;
+                $(let candidates = smallest(candidates, $name::candidates($name));)*
+                candidates
+            }
+
+            unsafe fn matches(state: &Self::State, entity: Entity) -> bool {
+                let ($($name,)*) = state;
+                $($name::matches($name, entity))&&*
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+impl_query_tuple!(A, B, C, D, E, F, G);
+impl_query_tuple!(A, B, C, D, E, F, G, H);
+
+/// Iterator over all entities that match a query + filter.
+pub struct QueryIter<'w, Q, F>
+where
+    Q: Query<'w>,
+    F: QueryFilter<'w>,
+{
+    state: Option<(Q::State, F::State)>,
+    candidates: &'w [Entity],
+    index: usize,
+}
+
+impl<'w, Q, F> QueryIter<'w, Q, F>
+where
+    Q: Query<'w>,
+    F: QueryFilter<'w>,
+{
+    /// Creates a new query for the given world.
+    pub(crate) fn new(world: &'w mut World) -> Self {
+        Self::validate_access();
+        unsafe { Self::init(&QuerySource::world(world)) }
+    }
+
+    /// Creates a new query that only reads from the world.
+    pub(crate) fn new_read_only(world: &'w World) -> Self
+    where
+        Q: ReadOnlyQuery<'w>,
+    {
+        // Read only queries never write through the pointer, so it is safe to derive from a shared reference.
+        unsafe { Self::init(&QuerySource::world(world as *const World as *mut World)) }
+    }
+
+    /// Panics if the query would alias a mutable component. Filters only check membership so they are not included.
+    pub(crate) fn validate_access() {
+        let mut access = vec![];
+        Q::access(&mut access);
+
+        for (i, a) in access.iter().enumerate() {
+            for b in access.iter().skip(i + 1) {
+                if a.conflicts_with(b) {
+                    panic!(
+                        "Query {} has conflicting access {:?} and {:?}",
+                        std::any::type_name::<Q>(),
+                        a,
+                        b
+                    );
+                }
+            }
+        }
+    }
+
+    pub(crate) unsafe fn init(source: &QuerySource<'w>) -> Self {
+        let state = match (Q::init(source), F::init(source)) {
+            (Some(q), Some(f)) => Some((q, f)),
+            _ => None,
+        };
+
+        let candidates = match &state {
+            Some((q, f)) => match smallest(Q::candidates(q), F::candidates(f)) {
+                Some(candidates) => candidates,
+                // Nothing to drive it, so check every entity
+                None => source.entities(),
+            },
+            None => &[],
+        };
+
+        Self {
+            state,
+            candidates,
+            index: 0,
+        }
+    }
+}
+
+impl<'w, Q, F> Iterator for QueryIter<'w, Q, F>
+where
+    Q: Query<'w>,
+    F: QueryFilter<'w>,
+{
+    type Item = Q::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (query_state, filter_state) = self.state.as_mut()?;
+
+        while self.index < self.candidates.len() {
+            let entity = self.candidates[self.index];
+            self.index += 1;
+
+            // Candidates never contain duplicates, so each entity is only fetched once.
+            unsafe {
+                if !F::matches(filter_state, entity) {
+                    continue;
+                }
+
+                if let Some(item) = Q::fetch(query_state, entity) {
+                    return Some(item);
+                }
+            }
+        }
+
+        None
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.candidates.len() - self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Player;
+    impl Component for Player {}
+
+    fn world() -> (World, Entity, Entity, Entity) {
+        let mut world = World::new();
+        world.register::<Position>(10);
+        world.register::<Velocity>(10);
+        world.register::<Player>(10);
+
+        let a = world.add_entity();
+        *world.add::<Position>(a).unwrap() = Position(1);
+        *world.add::<Velocity>(a).unwrap() = Velocity(10);
+        world.add::<Player>(a).unwrap();
+
+        let b = world.add_entity();
+        *world.add::<Position>(b).unwrap() = Position(2);
+
+        let c = world.add_entity();
+        *world.add::<Position>(c).unwrap() = Position(3);
+        *world.add::<Velocity>(c).unwrap() = Velocity(30);
+
+        (world, a, b, c)
+    }
+
+    #[test]
+    fn query_joins_components() {
+        let (mut world, a, _, c) = world();
+
+        for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+            position.0 += velocity.0;
+        }
+
+        assert_eq!(Some(&Position(11)), world.get::<Position>(a));
+        assert_eq!(Some(&Position(33)), world.get::<Position>(c));
+
+        let mut entities: Vec<Entity> = world
+            .query_ref::<(Entity, &Velocity)>()
+            .map(|(e, _)| e)
+            .collect();
+        entities.sort_by_key(|e| e.id());
+        assert_eq!(vec![a, c], entities);
+    }
+
+    #[test]
+    fn query_optional_components() {
+        let (world, a, b, c) = world();
+
+        let mut results: Vec<(Entity, Option<Velocity>)> = world
+            .query_ref::<(Entity, &Position, Option<&Velocity>)>()
+            .map(|(e, _, v)| (e, v.copied()))
+            .collect();
+        results.sort_by_key(|(e, _)| e.id());
+
+        assert_eq!(
+            vec![(a, Some(Velocity(10))), (b, None), (c, Some(Velocity(30)))],
+            results
+        );
+    }
+
+    #[test]
+    fn query_filters() {
+        let (mut world, a, b, c) = world();
+
+        let with: Vec<Entity> = world.query_filtered::<Entity, With<Player>>().collect();
+        assert_eq!(vec![a], with);
+
+        let mut without: Vec<Entity> = world
+            .query_ref_filtered::<(Entity, &Position), Without<Player>>()
+            .map(|(e, _)| e)
+            .collect();
+        without.sort_by_key(|e| e.id());
+        assert_eq!(vec![b, c], without);
+
+        let both: Vec<Entity> = world
+            .query_ref_filtered::<Entity, (With<Velocity>, Without<Player>)>()
+            .collect();
+        assert_eq!(vec![c], both);
+    }
+
+    #[test]
+    fn query_unregistered_component_is_empty() {
+        #[derive(Copy, Clone, PartialEq, Default, Debug)]
+        struct Unregistered;
+        impl Component for Unregistered {}
+
+        let (mut world, _, _, _) = world();
+
+        assert_eq!(0, world.query::<(&Position, &Unregistered)>().count());
+        assert_eq!(
+            3,
+            world.query::<(&Position, Option<&Unregistered>)>().count()
+        );
+        assert_eq!(
+            3,
+            world
+                .query_filtered::<&Position, Without<Unregistered>>()
+                .count()
+        );
+    }
+
+    #[test]
+    fn query_change_detection() {
+        let (mut world, a, _, _) = world();
+
+        // Everything is added on the first tick
+        assert_eq!(
+            3,
+            world
+                .query_ref_filtered::<Entity, Added<Position>>()
+                .count()
+        );
+        world.clear_trackers();
+        assert_eq!(
+            0,
+            world
+                .query_ref_filtered::<Entity, Added<Position>>()
+                .count()
+        );
+        assert_eq!(
+            0,
+            world
+                .query_ref_filtered::<Entity, Changed<Position>>()
+                .count()
+        );
+
+        // Mutable access marks as changed
+        world.get_mut::<Position>(a).unwrap().0 = 10;
+        for velocity in world.query_filtered::<&mut Velocity, With<Player>>() {
+            velocity.0 = 1;
+        }
+        let d = world.add_entity();
+        world.add::<Position>(d).unwrap();
+
+        let mut changed: Vec<Entity> = world
+            .query_ref_filtered::<Entity, Changed<Position>>()
+            .collect();
+        changed.sort_by_key(|e| e.id());
+        assert_eq!(vec![a, d], changed);
+        assert_eq!(
+            vec![d],
+            world
+                .query_ref_filtered::<Entity, Added<Position>>()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            1,
+            world
+                .query_ref_filtered::<&Velocity, Changed<Velocity>>()
+                .count()
+        );
+
+        // Read only access doesn't
+        world.clear_trackers();
+        assert_eq!(4, world.query_ref::<&Position>().count());
+        assert_eq!(
+            0,
+            world
+                .query_ref_filtered::<Entity, Changed<Position>>()
+                .count()
+        );
+    }
+
+    #[test]
+    fn query_mutable_join_only_changes_matches() {
+        let (mut world, a, _, c) = world();
+        world.clear_trackers();
+
+        // `b` has a position but no velocity, so it must not be marked as changed
+        assert_eq!(2, world.query::<(&mut Position, &Velocity)>().count());
+
+        let mut changed: Vec<Entity> = world
+            .query_ref_filtered::<Entity, Changed<Position>>()
+            .collect();
+        changed.sort_by_key(|e| e.id());
+        assert_eq!(vec![a, c], changed);
+
+        // Same when the mutable member isn't first
+        world.clear_trackers();
+        assert_eq!(1, world.query::<(&Player, &mut Position)>().count());
+        assert_eq!(
+            vec![a],
+            world
+                .query_ref_filtered::<Entity, Changed<Position>>()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn query_conflicting_access_panics() {
+        let (mut world, _, _, _) = world();
+        world.query::<(&mut Position, &Position)>();
+    }
+}
@@ -0,0 +1,337 @@
+// Runtime access to the fields of components, so tools such as inspectors, serialization + prefabs can work with
+// any component without per-type glue. Usually implemented through `#[derive(Component)]`.
+// Fields are addressed by name, with nested fields joined by '.', such as `transform.position.x`.
+use std::any::Any;
+
+use core_math::{Mat4, Vec3};
+
+use crate::Entity;
+
+/// Information about a field on a reflected type.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub type_name: &'static str,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReflectError {
+    PathNotFound(String),
+    TypeMismatch {
+        path: String,
+        expected: &'static str,
+    },
+}
+
+/// A type whose fields can be inspected + modified at runtime.
+pub trait Reflect: Any {
+    /// Returns the name of the type.
+    fn type_name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// Returns the fields of the type. Types without fields, such as numbers, return an empty list.
+    fn fields(&self) -> &'static [Field];
+
+    /// Returns the field with the given name.
+    fn field(&self, name: &str) -> Option<&dyn Reflect>;
+
+    /// Returns the mutable field with the given name.
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect>;
+
+    fn as_any(&self) -> &dyn Any;
+
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+impl dyn Reflect {
+    /// Returns the value at the given path. An empty path returns the value itself.
+    pub fn path(&self, path: &str) -> Option<&dyn Reflect> {
+        let mut current = self;
+        for name in path.split('.').filter(|n| !n.is_empty()) {
+            current = current.field(name)?;
+        }
+
+        Some(current)
+    }
+
+    /// Returns the mutable value at the given path. An empty path returns the value itself.
+    pub fn path_mut(&mut self, path: &str) -> Option<&mut dyn Reflect> {
+        let mut current = self;
+        for name in path.split('.').filter(|n| !n.is_empty()) {
+            current = current.field_mut(name)?;
+        }
+
+        Some(current)
+    }
+
+    /// Returns the value at the given path, if it is the given type.
+    pub fn get<T>(&self, path: &str) -> Option<&T>
+    where
+        T: Reflect,
+    {
+        self.path(path)?.as_any().downcast_ref::<T>()
+    }
+
+    /// Returns the mutable value at the given path, if it is the given type.
+    pub fn get_mut<T>(&mut self, path: &str) -> Option<&mut T>
+    where
+        T: Reflect,
+    {
+        self.path_mut(path)?.as_any_mut().downcast_mut::<T>()
+    }
+
+    /// Sets the value at the given path.
+    pub fn set<T>(&mut self, path: &str, value: T) -> Result<(), ReflectError>
+    where
+        T: Reflect,
+    {
+        let field = self
+            .path_mut(path)
+            .ok_or_else(|| ReflectError::PathNotFound(path.to_string()))?;
+
+        let expected = field.type_name();
+        match field.as_any_mut().downcast_mut::<T>() {
+            Some(field) => {
+                *field = value;
+                Ok(())
+            }
+            None => Err(ReflectError::TypeMismatch {
+                path: path.to_string(),
+                expected,
+            }),
+        }
+    }
+}
+
+/// Implements `Reflect` for types without any fields.
+macro_rules! impl_reflect_value {
+    ($($ty: ty),*) => {
+        $(
+            impl Reflect for $ty {
+                fn fields(&self) -> &'static [Field] {
+                    &[]
+                }
+
+                fn field(&self, _name: &str) -> Option<&dyn Reflect> {
+                    None
+                }
+
+                fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect> {
+                    None
+                }
+
+                fn as_any(&self) -> &dyn Any {
+                    self
+                }
+
+                fn as_any_mut(&mut self) -> &mut dyn Any {
+                    self
+                }
+            }
+        )*
+    };
+}
+
+impl_reflect_value!(
+    bool, char, u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, f32, f64, String, Entity, Mat4
+);
+
+impl<T> Reflect for Option<T>
+where
+    T: 'static,
+{
+    fn fields(&self) -> &'static [Field] {
+        &[]
+    }
+
+    fn field(&self, _name: &str) -> Option<&dyn Reflect> {
+        None
+    }
+
+    fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect> {
+        None
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl<T> Reflect for Vec<T>
+where
+    T: 'static,
+{
+    fn fields(&self) -> &'static [Field] {
+        &[]
+    }
+
+    fn field(&self, _name: &str) -> Option<&dyn Reflect> {
+        None
+    }
+
+    fn field_mut(&mut self, _name: &str) -> Option<&mut dyn Reflect> {
+        None
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+impl Reflect for Vec3 {
+    fn fields(&self) -> &'static [Field] {
+        &[
+            Field {
+                name: "x",
+                type_name: "f32",
+            },
+            Field {
+                name: "y",
+                type_name: "f32",
+            },
+            Field {
+                name: "z",
+                type_name: "f32",
+            },
+        ]
+    }
+
+    fn field(&self, name: &str) -> Option<&dyn Reflect> {
+        match name {
+            "x" => Some(&self.x),
+            "y" => Some(&self.y),
+            "z" => Some(&self.z),
+            _ => None,
+        }
+    }
+
+    fn field_mut(&mut self, name: &str) -> Option<&mut dyn Reflect> {
+        match name {
+            "x" => Some(&mut self.x),
+            "y" => Some(&mut self.y),
+            "z" => Some(&mut self.z),
+            _ => None,
+        }
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Component, LocalTransform, ResourceId, World};
+
+    #[derive(Clone, PartialEq, Default, Debug, Component)]
+    struct Player {
+        name: String,
+        position: Vec3,
+        lives: u8,
+        #[reflect(skip)]
+        cooldown: f32,
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Component)]
+    struct Health(u32, bool);
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Component)]
+    enum Team {
+        #[default]
+        Red,
+    }
+
+    #[test]
+    fn reflect_fields() {
+        let player = Player::default();
+        let fields: Vec<(&str, &str)> = player
+            .fields()
+            .iter()
+            .map(|f| (f.name, f.type_name))
+            .collect();
+        assert_eq!(
+            vec![("name", "String"), ("position", "Vec3"), ("lives", "u8")],
+            fields
+        );
+
+        let health = Health(3, true);
+        assert_eq!(2, health.fields().len());
+        assert_eq!(Some(&true), (&health as &dyn Reflect).get::<bool>("1"));
+
+        assert_eq!(0, Team::Red.fields().len());
+        assert_eq!(1, LocalTransform::default().fields().len());
+    }
+
+    #[test]
+    fn reflect_get_set_by_path() {
+        let mut player = Player::default();
+        let reflected: &mut dyn Reflect = &mut player;
+
+        reflected.set("position.y", 2.5f32).unwrap();
+        reflected.set("name", "ripley".to_string()).unwrap();
+        *reflected.get_mut::<u8>("lives").unwrap() = 3;
+        assert_eq!(Some(&2.5), reflected.get::<f32>("position.y"));
+        assert_eq!(Some(&3), reflected.get::<u8>("lives"));
+
+        assert_eq!(
+            Err(ReflectError::PathNotFound("position.w".to_string())),
+            reflected.set("position.w", 1f32)
+        );
+        assert_eq!(
+            Err(ReflectError::PathNotFound("cooldown".to_string())),
+            reflected.set("cooldown", 1f32)
+        );
+        assert_eq!(
+            Err(ReflectError::TypeMismatch {
+                path: "lives".to_string(),
+                expected: "u8"
+            }),
+            reflected.set("lives", 1u32)
+        );
+        assert_eq!(None, reflected.get::<u32>("lives"));
+
+        assert_eq!("ripley", player.name);
+        assert_eq!(3, player.lives);
+    }
+
+    #[test]
+    fn reflect_world_components() {
+        #[derive(Copy, Clone, PartialEq, Default, Debug)]
+        struct Opaque;
+        impl Component for Opaque {}
+
+        let mut world = World::new();
+        world.register::<Player>(1);
+        world.register::<Opaque>(1);
+        let entity = world.add_entity();
+        world.add::<Player>(entity).unwrap();
+        world.add::<Opaque>(entity).unwrap();
+
+        world
+            .reflect_mut(entity, ResourceId::from::<Player>())
+            .unwrap()
+            .set("lives", 9u8)
+            .unwrap();
+        assert_eq!(9, world.get::<Player>(entity).unwrap().lives);
+
+        let reflected = world.reflect(entity, ResourceId::from::<Player>()).unwrap();
+        assert!(reflected.type_name().ends_with("Player"));
+        assert!(world
+            .reflect(entity, ResourceId::from::<Opaque>())
+            .is_none());
+    }
+}
@@ -0,0 +1,863 @@
+// Systems + the schedule that runs them. Systems are grouped into named stages that run in order,
+// and within a stage systems are sorted by their before/after constraints. Ties keep insertion order so runs are deterministic.
+// Parallel systems that are next to each other in that order, and don't conflict, are batched together so they may run
+// on a thread pool. As nothing in a batch conflicts, the result is the same as running them one after another.
+use core_threading::{Job, ThreadPool};
+
+use crate::{query::ComponentAccess, Component, Query, ResourceId, SystemWorld, World};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ScheduleError {
+    StageNotFound(&'static str),
+    DuplicateStage(&'static str),
+    SystemNotFound {
+        system: &'static str,
+        dependency: &'static str,
+    },
+    CycleDetected {
+        stage: &'static str,
+    },
+}
+
+/// The components + resources a system reads + writes.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SystemAccess {
+    access: Vec<ComponentAccess>,
+}
+
+impl SystemAccess {
+    /// Creates a new, empty, access declaration.
+    pub fn new() -> Self {
+        Self { access: vec![] }
+    }
+
+    /// Declares that the given component is read.
+    pub fn read<C>(mut self) -> Self
+    where
+        C: Component,
+    {
+        self.access
+            .push(ComponentAccess::Read(ResourceId::from::<C>()));
+        self
+    }
+
+    /// Declares that the given component is written.
+    pub fn write<C>(mut self) -> Self
+    where
+        C: Component,
+    {
+        self.access
+            .push(ComponentAccess::Write(ResourceId::from::<C>()));
+        self
+    }
+
+    /// Declares that the given resource is read.
+    pub fn read_resource<R>(mut self) -> Self
+    where
+        R: 'static,
+    {
+        self.access
+            .push(ComponentAccess::Read(ResourceId::from::<R>()));
+        self
+    }
+
+    /// Declares that the given resource is written.
+    pub fn write_resource<R>(mut self) -> Self
+    where
+        R: 'static,
+    {
+        self.access
+            .push(ComponentAccess::Write(ResourceId::from::<R>()));
+        self
+    }
+
+    /// Declares all access the given query requires. Example: `SystemAccess::new().query::<(&Position, &mut Velocity)>()`
+    pub fn query<Q>(mut self) -> Self
+    where
+        Q: Query<'static>,
+    {
+        Q::access(&mut self.access);
+        self
+    }
+
+    /// Returns the declared access.
+    pub fn access(&self) -> &[ComponentAccess] {
+        &self.access
+    }
+
+    /// Returns whether the two systems may not run at the same time.
+    pub fn conflicts_with(&self, other: &SystemAccess) -> bool {
+        self.access
+            .iter()
+            .any(|a| other.access.iter().any(|b| a.conflicts_with(b)))
+    }
+
+    /// Returns whether the given access was declared. Writes also allow reads.
+    pub fn allows(&self, access: &ComponentAccess) -> bool {
+        self.access.iter().any(|declared| match (declared, access) {
+            (ComponentAccess::Read(_), ComponentAccess::Write(_)) => false,
+            _ => declared.resource_id() == access.resource_id(),
+        })
+    }
+}
+
+/// A unit of logic that operates on the world.
+pub trait System {
+    /// The name of the system. Used when ordering systems.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// The components + resources the system reads + writes.
+    fn access(&self) -> SystemAccess;
+
+    /// Executes the system.
+    fn run(&mut self, world: &mut World);
+}
+
+/// A system made from a plain function.
+pub struct FnSystem {
+    name: &'static str,
+    access: SystemAccess,
+    func: fn(&mut World),
+}
+
+impl FnSystem {
+    /// Creates a new function system.
+    pub fn new(name: &'static str, access: SystemAccess, func: fn(&mut World)) -> Self {
+        Self { name, access, func }
+    }
+}
+
+impl System for FnSystem {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn access(&self) -> SystemAccess {
+        self.access.clone()
+    }
+
+    fn run(&mut self, world: &mut World) {
+        (self.func)(world);
+    }
+}
+
+/// A system that only uses the components + resources it declared, so it may run alongside other systems.
+pub trait ParallelSystem: Send {
+    /// The name of the system. Used when ordering systems.
+    fn name(&self) -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// The components + resources the system reads + writes.
+    fn access(&self) -> SystemAccess;
+
+    /// Executes the system. Accessing anything that wasn't declared panics.
+    fn run(&mut self, world: &mut SystemWorld);
+}
+
+/// A parallel system made from a plain function.
+pub struct ParallelFnSystem {
+    name: &'static str,
+    access: SystemAccess,
+    func: fn(&mut SystemWorld),
+}
+
+impl ParallelFnSystem {
+    /// Creates a new parallel function system.
+    pub fn new(name: &'static str, access: SystemAccess, func: fn(&mut SystemWorld)) -> Self {
+        Self { name, access, func }
+    }
+}
+
+impl ParallelSystem for ParallelFnSystem {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn access(&self) -> SystemAccess {
+        self.access.clone()
+    }
+
+    fn run(&mut self, world: &mut SystemWorld) {
+        (self.func)(world);
+    }
+}
+
+enum SystemKind {
+    /// Has full access to the world, so always runs on its own.
+    Exclusive(Box<dyn System>),
+    Parallel(Box<dyn ParallelSystem>),
+}
+
+struct SystemEntry {
+    name: &'static str,
+    system: SystemKind,
+    access: SystemAccess,
+    before: Vec<&'static str>,
+    after: Vec<&'static str>,
+}
+
+impl SystemEntry {
+    /// Runs the system on the current thread.
+    fn run(&mut self, world: &mut World) {
+        match &mut self.system {
+            SystemKind::Exclusive(system) => system.run(world),
+            SystemKind::Parallel(system) => {
+                let mut world = unsafe { SystemWorld::new(world, &self.access) };
+                system.run(&mut world);
+            }
+        }
+    }
+}
+
+struct Stage {
+    name: &'static str,
+    systems: Vec<SystemEntry>,
+    /// The order to execute systems in. Rebuilt whenever systems change.
+    order: Vec<usize>,
+    /// Ranges of `order` that may run at the same time. Rebuilt whenever systems change.
+    batches: Vec<std::ops::Range<usize>>,
+}
+
+impl Stage {
+    /// Sorts the systems based on their constraints.
+    fn build(&mut self) -> Result<(), ScheduleError> {
+        let system_count = self.systems.len();
+        let index_of = |name: &'static str, system: &'static str| {
+            self.systems
+                .iter()
+                .position(|s| s.name == name)
+                .ok_or(ScheduleError::SystemNotFound {
+                    system,
+                    dependency: name,
+                })
+        };
+
+        // Build up the edges, where `dependents[a]` must run after `a`.
+        let mut dependents: Vec<Vec<usize>> = vec![vec![]; system_count];
+        let mut dependency_count = vec![0; system_count];
+        for (i, entry) in self.systems.iter().enumerate() {
+            let name = entry.name;
+            for before in &entry.before {
+                let other = index_of(before, name)?;
+                dependents[i].push(other);
+                dependency_count[other] += 1;
+            }
+            for after in &entry.after {
+                let other = index_of(after, name)?;
+                dependents[other].push(i);
+                dependency_count[i] += 1;
+            }
+        }
+
+        // Kahn's algorithm, always picking the earliest added system that is ready.
+        self.order.clear();
+        let mut visited = vec![false; system_count];
+        while self.order.len() < system_count {
+            let next = (0..system_count).find(|i| !visited[*i] && dependency_count[*i] == 0);
+
+            match next {
+                Some(next) => {
+                    visited[next] = true;
+                    self.order.push(next);
+                    for dependent in &dependents[next] {
+                        dependency_count[*dependent] -= 1;
+                    }
+                }
+                None => return Err(ScheduleError::CycleDetected { stage: self.name }),
+            }
+        }
+
+        self.build_batches();
+        Ok(())
+    }
+
+    /// Groups neighbouring parallel systems that don't conflict. Exclusive systems are always in a batch of their own.
+    fn build_batches(&mut self) {
+        self.batches.clear();
+
+        let mut start = 0;
+        for (end, i) in self.order.iter().enumerate() {
+            let entry = &self.systems[*i];
+            let fits = match entry.system {
+                SystemKind::Exclusive(_) => false,
+                SystemKind::Parallel(_) => self.order[start..end].iter().all(|other| {
+                    let other = &self.systems[*other];
+                    matches!(other.system, SystemKind::Parallel(_))
+                        && !other.access.conflicts_with(&entry.access)
+                }),
+            };
+
+            if !fits && start != end {
+                self.batches.push(start..end);
+                start = end;
+            }
+        }
+
+        if start != self.order.len() {
+            self.batches.push(start..self.order.len());
+        }
+    }
+}
+
+/// Allows adding ordering constraints to a newly added system.
+pub struct SystemConfig<'a> {
+    entry: &'a mut SystemEntry,
+}
+
+impl<'a> SystemConfig<'a> {
+    /// The system will run before the given system in the same stage.
+    pub fn before(self, system: &'static str) -> Self {
+        self.entry.before.push(system);
+        self
+    }
+
+    /// The system will run after the given system in the same stage.
+    pub fn after(self, system: &'static str) -> Self {
+        self.entry.after.push(system);
+        self
+    }
+}
+
+/// Runs systems against a world in a defined order.
+pub struct Schedule {
+    stages: Vec<Stage>,
+    dirty: bool,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Schedule {
+    /// Creates a new schedule without any stages.
+    pub fn new() -> Self {
+        Self {
+            stages: vec![],
+            dirty: false,
+        }
+    }
+
+    /// Adds a new stage that runs after all existing stages.
+    pub fn add_stage(&mut self, name: &'static str) -> Result<&mut Self, ScheduleError> {
+        let index = self.stages.len();
+        self.insert_stage(index, name)
+    }
+
+    /// Adds a new stage that runs before the target stage.
+    pub fn add_stage_before(
+        &mut self,
+        target: &'static str,
+        name: &'static str,
+    ) -> Result<&mut Self, ScheduleError> {
+        let index = self.stage_index(target)?;
+        self.insert_stage(index, name)
+    }
+
+    /// Adds a new stage that runs after the target stage.
+    pub fn add_stage_after(
+        &mut self,
+        target: &'static str,
+        name: &'static str,
+    ) -> Result<&mut Self, ScheduleError> {
+        let index = self.stage_index(target)?;
+        self.insert_stage(index + 1, name)
+    }
+
+    /// Adds a system to the given stage.
+    pub fn add_system<S>(
+        &mut self,
+        stage: &'static str,
+        system: S,
+    ) -> Result<SystemConfig<'_>, ScheduleError>
+    where
+        S: System + 'static,
+    {
+        let name = system.name();
+        let access = system.access();
+        self.insert_system(stage, name, SystemKind::Exclusive(Box::new(system)), access)
+    }
+
+    /// Adds a system that may run at the same time as other parallel systems when using `run_parallel()`.
+    pub fn add_parallel_system<S>(
+        &mut self,
+        stage: &'static str,
+        system: S,
+    ) -> Result<SystemConfig<'_>, ScheduleError>
+    where
+        S: ParallelSystem + 'static,
+    {
+        let name = system.name();
+        let access = system.access();
+        self.insert_system(stage, name, SystemKind::Parallel(Box::new(system)), access)
+    }
+
+    fn insert_system(
+        &mut self,
+        stage: &'static str,
+        name: &'static str,
+        system: SystemKind,
+        access: SystemAccess,
+    ) -> Result<SystemConfig<'_>, ScheduleError> {
+        let index = self.stage_index(stage)?;
+        self.dirty = true;
+
+        let systems = &mut self.stages[index].systems;
+        systems.push(SystemEntry {
+            name,
+            system,
+            access,
+            before: vec![],
+            after: vec![],
+        });
+
+        let last = systems.len() - 1;
+        Ok(SystemConfig {
+            entry: &mut systems[last],
+        })
+    }
+
+    /// Returns the names of the stages in execution order.
+    pub fn stages(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.stages.iter().map(|s| s.name)
+    }
+
+    /// Returns the names + access of all systems in execution order. Builds the schedule if required.
+    pub fn systems(&mut self) -> Result<Vec<(&'static str, SystemAccess)>, ScheduleError> {
+        self.build()?;
+
+        let mut systems = vec![];
+        for stage in &self.stages {
+            for i in &stage.order {
+                let entry = &stage.systems[*i];
+                systems.push((entry.name, entry.access.clone()));
+            }
+        }
+
+        Ok(systems)
+    }
+
+    /// Sorts all systems. Done automatically by `run()`, but may be called ahead of time to surface errors at startup.
+    pub fn build(&mut self) -> Result<(), ScheduleError> {
+        if self.dirty {
+            for stage in self.stages.iter_mut() {
+                stage.build()?;
+            }
+            self.dirty = false;
+        }
+
+        Ok(())
+    }
+
+    /// Runs all systems against the world, one after another.
+    pub fn run(&mut self, world: &mut World) {
+        if let Err(e) = self.build() {
+            panic!("Unable to build schedule: {:?}", e);
+        }
+
+        for stage in self.stages.iter_mut() {
+            for i in stage.order.iter() {
+                stage.systems[*i].run(world);
+            }
+        }
+    }
+
+    /// Runs all systems against the world, running batches of parallel systems on the pool.
+    /// Gives the same result as `run()`.
+    pub fn run_parallel(&mut self, world: &mut World, pool: &dyn ThreadPool) {
+        if let Err(e) = self.build() {
+            panic!("Unable to build schedule: {:?}", e);
+        }
+
+        let world: *mut World = world;
+        for stage in self.stages.iter_mut() {
+            for batch in stage.batches.iter() {
+                if batch.len() == 1 {
+                    stage.systems[stage.order[batch.start]].run(unsafe { &mut *world });
+                    continue;
+                }
+
+                // Nothing in a batch conflicts, so each system gets its own view of the world.
+                let in_batch = &stage.order[batch.clone()];
+                let mut runners: Vec<_> = stage
+                    .systems
+                    .iter_mut()
+                    .enumerate()
+                    .filter(|(i, _)| in_batch.contains(i))
+                    .filter_map(|(_, entry)| match &mut entry.system {
+                        SystemKind::Parallel(system) => {
+                            // Resolved here so the world is never borrowed from the pool's threads.
+                            let mut world = unsafe { SystemWorld::new(&mut *world, &entry.access) };
+                            Some(move || system.run(&mut world))
+                        }
+                        SystemKind::Exclusive(_) => None,
+                    })
+                    .collect();
+
+                let mut jobs: Vec<Job> = runners.iter_mut().map(|r| r as Job).collect();
+                pool.execute(&mut jobs);
+            }
+        }
+    }
+
+    fn stage_index(&self, name: &'static str) -> Result<usize, ScheduleError> {
+        self.stages
+            .iter()
+            .position(|s| s.name == name)
+            .ok_or(ScheduleError::StageNotFound(name))
+    }
+
+    fn insert_stage(
+        &mut self,
+        index: usize,
+        name: &'static str,
+    ) -> Result<&mut Self, ScheduleError> {
+        if self.stage_index(name).is_ok() {
+            return Err(ScheduleError::DuplicateStage(name));
+        }
+
+        self.stages.insert(
+            index,
+            Stage {
+                name,
+                systems: vec![],
+                order: vec![],
+                batches: vec![],
+            },
+        );
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Entity;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Log {
+        values: [u8; 8],
+        len: usize,
+    }
+    impl Component for Log {}
+
+    impl Log {
+        fn push(&mut self, value: u8) {
+            self.values[self.len] = value;
+            self.len += 1;
+        }
+
+        fn values(&self) -> &[u8] {
+            &self.values[0..self.len]
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    struct Push(&'static str, u8);
+
+    impl System for Push {
+        fn name(&self) -> &'static str {
+            self.0
+        }
+
+        fn access(&self) -> SystemAccess {
+            SystemAccess::new().write::<Log>()
+        }
+
+        fn run(&mut self, world: &mut World) {
+            for log in world.query::<&mut Log>() {
+                log.push(self.1);
+            }
+        }
+    }
+
+    fn world() -> (World, Entity) {
+        let mut world = World::new();
+        world.register::<Log>(1);
+        let entity = world.add_entity();
+        world.add::<Log>(entity).unwrap();
+        (world, entity)
+    }
+
+    #[test]
+    fn schedule_runs_stages_in_order() {
+        let (mut world, entity) = world();
+
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule.add_stage_before("update", "input").unwrap();
+        schedule.add_stage_after("update", "render").unwrap();
+        assert_eq!(
+            vec!["input", "update", "render"],
+            schedule.stages().collect::<Vec<_>>()
+        );
+
+        schedule.add_system("render", Push("c", 3)).unwrap();
+        schedule.add_system("update", Push("b", 2)).unwrap();
+        schedule.add_system("input", Push("a", 1)).unwrap();
+        schedule.run(&mut world);
+
+        assert_eq!(&[1, 2, 3], world.get::<Log>(entity).unwrap().values());
+    }
+
+    #[test]
+    fn schedule_orders_systems_in_stage() {
+        let (mut world, entity) = world();
+
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule
+            .add_system("update", Push("c", 3))
+            .unwrap()
+            .after("b");
+        schedule.add_system("update", Push("d", 4)).unwrap();
+        schedule
+            .add_system("update", Push("b", 2))
+            .unwrap()
+            .after("a")
+            .before("d");
+        schedule.add_system("update", Push("a", 1)).unwrap();
+        schedule.run(&mut world);
+
+        assert_eq!(&[1, 2, 3, 4], world.get::<Log>(entity).unwrap().values());
+    }
+
+    #[test]
+    fn schedule_errors() {
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+
+        assert_eq!(
+            Some(ScheduleError::DuplicateStage("update")),
+            schedule.add_stage("update").err()
+        );
+        assert_eq!(
+            Some(ScheduleError::StageNotFound("missing")),
+            schedule.add_system("missing", Push("a", 1)).err()
+        );
+
+        schedule
+            .add_system("update", Push("a", 1))
+            .unwrap()
+            .after("missing");
+        assert_eq!(
+            Err(ScheduleError::SystemNotFound {
+                system: "a",
+                dependency: "missing"
+            }),
+            schedule.build()
+        );
+
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule
+            .add_system("update", Push("a", 1))
+            .unwrap()
+            .after("b");
+        schedule
+            .add_system("update", Push("b", 2))
+            .unwrap()
+            .after("a");
+        assert_eq!(
+            Err(ScheduleError::CycleDetected { stage: "update" }),
+            schedule.build()
+        );
+    }
+
+    #[test]
+    fn system_access_conflicts() {
+        let movement = SystemAccess::new().query::<(&mut Position, &Log)>();
+        let render = SystemAccess::new().read::<Position>().read::<Log>();
+        let logging = SystemAccess::new().write::<Log>();
+
+        assert!(movement.conflicts_with(&render));
+        assert!(!render.conflicts_with(&render));
+        assert!(render.conflicts_with(&logging));
+
+        struct Score;
+        let scoring = SystemAccess::new().write_resource::<Score>();
+        let hud = SystemAccess::new().read_resource::<Score>();
+        assert!(scoring.conflicts_with(&hud));
+        assert!(!hud.conflicts_with(&hud));
+        assert!(!scoring.conflicts_with(&logging));
+
+        let empty = FnSystem::new("empty", SystemAccess::new(), |_| {});
+        assert!(!empty.access().conflicts_with(&logging));
+    }
+
+    /// Runs every job on its own thread.
+    struct ScopedPool;
+
+    impl ThreadPool for ScopedPool {
+        fn thread_count(&self) -> usize {
+            4
+        }
+
+        fn execute(&self, jobs: &mut [Job]) {
+            std::thread::scope(|scope| {
+                for job in jobs.iter_mut() {
+                    scope.spawn(job);
+                }
+            });
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Velocity(i32);
+    impl Component for Velocity {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Gravity(i32);
+
+    fn parallel_schedule() -> Schedule {
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule
+            .add_parallel_system(
+                "update",
+                ParallelFnSystem::new(
+                    "gravity",
+                    SystemAccess::new()
+                        .query::<&mut Velocity>()
+                        .read_resource::<Gravity>(),
+                    |world| {
+                        let gravity = world.resource::<Gravity>().unwrap().0;
+                        for velocity in world.query::<&mut Velocity>() {
+                            velocity.0 += gravity;
+                        }
+                    },
+                ),
+            )
+            .unwrap();
+        schedule
+            .add_parallel_system(
+                "update",
+                ParallelFnSystem::new(
+                    "log_positions",
+                    SystemAccess::new().query::<(&Position, &mut Log)>(),
+                    |world| {
+                        for (position, log) in world.query::<(&Position, &mut Log)>() {
+                            log.push(position.0 as u8);
+                        }
+                    },
+                ),
+            )
+            .unwrap();
+        schedule
+            .add_parallel_system(
+                "update",
+                ParallelFnSystem::new(
+                    "movement",
+                    SystemAccess::new().query::<(&mut Position, &Velocity)>(),
+                    |world| {
+                        for (position, velocity) in world.query::<(&mut Position, &Velocity)>() {
+                            position.0 += velocity.0;
+                        }
+                    },
+                ),
+            )
+            .unwrap()
+            .after("gravity");
+        schedule
+            .add_system(
+                "update",
+                FnSystem::new("reset", SystemAccess::new().write::<Log>(), |world| {
+                    for log in world.query::<&mut Log>() {
+                        if log.len == 8 {
+                            *log = Log::default();
+                        }
+                    }
+                }),
+            )
+            .unwrap();
+        schedule
+            .add_parallel_system(
+                "update",
+                ParallelFnSystem::new("count", SystemAccess::new().read::<Log>(), |world| {
+                    assert_eq!(4, world.query::<&Log>().count());
+                }),
+            )
+            .unwrap();
+        schedule
+    }
+
+    fn parallel_world() -> World {
+        let mut world = World::new();
+        world.register::<Log>(4);
+        world.register::<Position>(4);
+        world.register::<Velocity>(4);
+        world.insert_resource(Gravity(1));
+
+        for i in 0..4 {
+            let entity = world.add_entity();
+            world.add::<Log>(entity).unwrap();
+            *world.add::<Position>(entity).unwrap() = Position(i);
+            *world.add::<Velocity>(entity).unwrap() = Velocity(i);
+        }
+
+        world
+    }
+
+    #[test]
+    fn schedule_batches_non_conflicting_systems() {
+        let mut schedule = parallel_schedule();
+        schedule.build().unwrap();
+
+        // gravity + log_positions don't conflict, movement writes Position which log_positions reads,
+        // and reset is exclusive.
+        assert_eq!(vec![0..2, 2..3, 3..4, 4..5], schedule.stages[0].batches);
+    }
+
+    #[test]
+    fn schedule_run_parallel_matches_serial() {
+        let mut serial = parallel_world();
+        let mut parallel = parallel_world();
+        let mut serial_schedule = parallel_schedule();
+        let mut parallel_schedule = parallel_schedule();
+
+        for _ in 0..20 {
+            serial_schedule.run(&mut serial);
+            parallel_schedule.run_parallel(&mut parallel, &ScopedPool);
+        }
+
+        let logs = |world: &World| -> Vec<(Log, Position, Velocity)> {
+            world
+                .query_ref::<(&Log, &Position, &Velocity)>()
+                .map(|(l, p, v)| (*l, *p, *v))
+                .collect()
+        };
+        assert_eq!(logs(&serial), logs(&parallel));
+        assert_eq!(
+            Some(&Velocity(23)),
+            parallel.get::<Velocity>(parallel.entities()[3])
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn system_world_panics_on_undeclared_access() {
+        let mut world = parallel_world();
+        let mut schedule = Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule
+            .add_parallel_system(
+                "update",
+                ParallelFnSystem::new("sneaky", SystemAccess::new().read::<Position>(), |world| {
+                    for position in world.query::<&mut Position>() {
+                        position.0 = 0;
+                    }
+                }),
+            )
+            .unwrap();
+
+        schedule.run_parallel(&mut world, &ScopedPool);
+    }
+}
@@ -0,0 +1,531 @@
+// Saving + loading worlds. Components opt in by implementing `SerializableComponent` and being registered with a
+// `SerializationRegistry`, which maps stable names to `ResourceId`s since `TypeId`s change between builds.
+// Worlds may be written as a versioned binary blob or as human readable JSON/RON. Entities are given new handles
+// when loaded, so components holding entities should implement `Component::map_entities()`.
+use std::collections::BTreeMap;
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{prefab::PrefabComponent, Component, Entity, EntityMap, ResourceId, World, WorldError};
+
+/// The current version of the saved format. Bumped whenever the layout changes.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Written at the start of binary saves to detect invalid data.
+const BINARY_MAGIC: &[u8; 4] = b"VKWD";
+
+/// A component that can be saved + loaded.
+pub trait SerializableComponent: Component + Serialize + DeserializeOwned {
+    /// The name used to identify the component in saved data. Must be unique and should not change once data has been saved.
+    const NAME: &'static str;
+}
+
+#[derive(Debug)]
+pub enum SerializationError {
+    DuplicateName(&'static str),
+    UnknownComponent(String),
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    Binary(String),
+    Json(String),
+    Ron(String),
+    WorldError(WorldError),
+}
+
+impl From<WorldError> for SerializationError {
+    fn from(error: WorldError) -> Self {
+        Self::WorldError(error)
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct WorldDocument<T> {
+    version: u32,
+    entities: Vec<EntityDocument<T>>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct EntityDocument<T> {
+    entity: Entity,
+    components: BTreeMap<String, T>,
+}
+
+type SaveFn<T> = fn(&World, Entity) -> Option<Result<T, SerializationError>>;
+type LoadFn<T> = fn(&mut World, Entity, T, &EntityMap) -> Result<(), SerializationError>;
+pub(crate) type DefaultFn = fn() -> Result<Value, SerializationError>;
+pub(crate) type PrototypeFn = fn(Value) -> Result<Box<dyn PrefabComponent>, SerializationError>;
+type RegisterFn = fn(&mut World, usize);
+
+struct RegistryEntry {
+    name: &'static str,
+    resource_id: ResourceId,
+    register: RegisterFn,
+    save_value: SaveFn<Value>,
+    load_value: LoadFn<Value>,
+    save_bytes: SaveFn<Vec<u8>>,
+    load_bytes: LoadFn<Vec<u8>>,
+    default_value: DefaultFn,
+    prototype: PrototypeFn,
+}
+
+/// The components that may be saved + loaded, along with their stable names.
+pub struct SerializationRegistry {
+    entries: Vec<RegistryEntry>,
+}
+
+impl SerializationRegistry {
+    /// Creates a new, empty, registry.
+    pub fn new() -> Self {
+        Self { entries: vec![] }
+    }
+
+    /// Registers the given component. Registering the same component twice does nothing.
+    pub fn register<C>(&mut self) -> Result<(), SerializationError>
+    where
+        C: SerializableComponent,
+    {
+        let resource_id = ResourceId::from::<C>();
+        for entry in self.entries.iter() {
+            if entry.resource_id == resource_id {
+                return Ok(());
+            } else if entry.name == C::NAME {
+                return Err(SerializationError::DuplicateName(C::NAME));
+            }
+        }
+
+        self.entries.push(RegistryEntry {
+            name: C::NAME,
+            resource_id,
+            register: World::register::<C>,
+            save_value: save_value::<C>,
+            load_value: load_value::<C>,
+            save_bytes: save_bytes::<C>,
+            load_bytes: load_bytes::<C>,
+            default_value: default_value::<C>,
+            prototype: prototype::<C>,
+        });
+
+        Ok(())
+    }
+
+    /// Returns the id of the component registered with the given name.
+    pub fn resource_id(&self, name: &str) -> Option<ResourceId> {
+        self.entry(name).map(|e| e.resource_id)
+    }
+
+    /// Returns the name of the given component.
+    pub fn name(&self, resource_id: ResourceId) -> Option<&'static str> {
+        self.entries
+            .iter()
+            .find(|e| e.resource_id == resource_id)
+            .map(|e| e.name)
+    }
+
+    /// Saves the world to the binary format.
+    pub fn to_binary(&self, world: &World) -> Result<Vec<u8>, SerializationError> {
+        let document = self.save(world, |entry| entry.save_bytes)?;
+
+        let mut bytes = BINARY_MAGIC.to_vec();
+        bytes.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &document)
+            .map_err(|e| SerializationError::Binary(e.to_string()))?;
+
+        Ok(bytes)
+    }
+
+    /// Loads the binary data into the world, returning the handles the saved entities were given.
+    pub fn from_binary(
+        &self,
+        world: &mut World,
+        bytes: &[u8],
+    ) -> Result<EntityMap, SerializationError> {
+        if bytes.len() < 8 || &bytes[0..4] != BINARY_MAGIC {
+            return Err(SerializationError::InvalidHeader);
+        }
+
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != FORMAT_VERSION {
+            return Err(SerializationError::UnsupportedVersion(version));
+        }
+
+        let document = bincode::deserialize(&bytes[8..])
+            .map_err(|e| SerializationError::Binary(e.to_string()))?;
+
+        self.load(world, document, |entry| entry.load_bytes)
+    }
+
+    /// Saves the world to JSON.
+    pub fn to_json(&self, world: &World) -> Result<String, SerializationError> {
+        let document = self.save(world, |entry| entry.save_value)?;
+        serde_json::to_string_pretty(&document).map_err(|e| SerializationError::Json(e.to_string()))
+    }
+
+    /// Loads the JSON into the world, returning the handles the saved entities were given.
+    pub fn from_json(
+        &self,
+        world: &mut World,
+        json: &str,
+    ) -> Result<EntityMap, SerializationError> {
+        let document =
+            serde_json::from_str(json).map_err(|e| SerializationError::Json(e.to_string()))?;
+
+        self.load(world, document, |entry| entry.load_value)
+    }
+
+    /// Saves the world to RON.
+    pub fn to_ron(&self, world: &World) -> Result<String, SerializationError> {
+        let document = self.save(world, |entry| entry.save_value)?;
+        ron::ser::to_string_pretty(&document, ron::ser::PrettyConfig::default())
+            .map_err(|e| SerializationError::Ron(e.to_string()))
+    }
+
+    /// Loads the RON into the world, returning the handles the saved entities were given.
+    pub fn from_ron(&self, world: &mut World, ron: &str) -> Result<EntityMap, SerializationError> {
+        let document = ron::from_str(ron).map_err(|e| SerializationError::Ron(e.to_string()))?;
+
+        self.load(world, document, |entry| entry.load_value)
+    }
+
+    /// Returns the functions used to build prefab components for the component with the given name.
+    pub(crate) fn prefab_fns(&self, name: &str) -> Option<(DefaultFn, PrototypeFn)> {
+        self.entry(name).map(|e| (e.default_value, e.prototype))
+    }
+
+    fn entry(&self, name: &str) -> Option<&RegistryEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    /// Builds a document containing all alive entities + their registered components.
+    fn save<T, F>(&self, world: &World, save_fn: F) -> Result<WorldDocument<T>, SerializationError>
+    where
+        F: Fn(&RegistryEntry) -> SaveFn<T>,
+    {
+        let mut entities = Vec::with_capacity(world.entity_len());
+        for entity in world.entities() {
+            let mut components = BTreeMap::new();
+            for entry in self.entries.iter() {
+                if let Some(component) = save_fn(entry)(world, *entity) {
+                    components.insert(entry.name.to_string(), component?);
+                }
+            }
+
+            entities.push(EntityDocument {
+                entity: *entity,
+                components,
+            });
+        }
+
+        Ok(WorldDocument {
+            version: FORMAT_VERSION,
+            entities,
+        })
+    }
+
+    /// Spawns the entities in the document. Everything is loaded into a staging world first, then merged in,
+    /// so the world is left unchanged if any component fails to load. All entities are created before any components
+    /// are added, so that references between them can be mapped.
+    fn load<T, F>(
+        &self,
+        world: &mut World,
+        document: WorldDocument<T>,
+        load_fn: F,
+    ) -> Result<EntityMap, SerializationError>
+    where
+        F: Fn(&RegistryEntry) -> LoadFn<T>,
+    {
+        if document.version != FORMAT_VERSION {
+            return Err(SerializationError::UnsupportedVersion(document.version));
+        }
+
+        // Check everything can be loaded before doing any work
+        for entity in document.entities.iter() {
+            for name in entity.components.keys() {
+                if self.entry(name).is_none() {
+                    return Err(SerializationError::UnknownComponent(name.clone()));
+                }
+            }
+        }
+
+        let mut staging = World::new();
+        for entry in self.entries.iter() {
+            (entry.register)(&mut staging, document.entities.len());
+        }
+
+        let saved: Vec<Entity> = document.entities.iter().map(|e| e.entity).collect();
+        let mut staging_map = EntityMap::new();
+        for entity in saved.iter() {
+            staging_map.insert(*entity, staging.add_entity());
+        }
+
+        for entity in document.entities.into_iter() {
+            let new_entity = staging_map.get(entity.entity).unwrap_or(entity.entity);
+            for (name, component) in entity.components.into_iter() {
+                if let Some(entry) = self.entry(&name) {
+                    load_fn(entry)(&mut staging, new_entity, component, &staging_map)?;
+                }
+            }
+        }
+
+        let merged = world.merge(&mut staging)?;
+
+        let mut map = EntityMap::new();
+        for entity in saved.into_iter() {
+            if let Some(new_entity) = staging_map.get(entity).and_then(|e| merged.get(e)) {
+                map.insert(entity, new_entity);
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+impl Default for SerializationRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn save_value<C>(world: &World, entity: Entity) -> Option<Result<Value, SerializationError>>
+where
+    C: SerializableComponent,
+{
+    let component = world.get::<C>(entity)?;
+    Some(serde_json::to_value(component).map_err(|e| SerializationError::Json(e.to_string())))
+}
+
+fn load_value<C>(
+    world: &mut World,
+    entity: Entity,
+    value: Value,
+    map: &EntityMap,
+) -> Result<(), SerializationError>
+where
+    C: SerializableComponent,
+{
+    let mut component: C =
+        serde_json::from_value(value).map_err(|e| SerializationError::Json(e.to_string()))?;
+    component.map_entities(map);
+    world.insert(entity, component)?;
+
+    Ok(())
+}
+
+fn save_bytes<C>(world: &World, entity: Entity) -> Option<Result<Vec<u8>, SerializationError>>
+where
+    C: SerializableComponent,
+{
+    let component = world.get::<C>(entity)?;
+    Some(bincode::serialize(component).map_err(|e| SerializationError::Binary(e.to_string())))
+}
+
+fn load_bytes<C>(
+    world: &mut World,
+    entity: Entity,
+    bytes: Vec<u8>,
+    map: &EntityMap,
+) -> Result<(), SerializationError>
+where
+    C: SerializableComponent,
+{
+    let mut component: C =
+        bincode::deserialize(&bytes).map_err(|e| SerializationError::Binary(e.to_string()))?;
+    component.map_entities(map);
+    world.insert(entity, component)?;
+
+    Ok(())
+}
+
+fn default_value<C>() -> Result<Value, SerializationError>
+where
+    C: SerializableComponent,
+{
+    serde_json::to_value(C::default()).map_err(|e| SerializationError::Json(e.to_string()))
+}
+
+fn prototype<C>(value: Value) -> Result<Box<dyn PrefabComponent>, SerializationError>
+where
+    C: SerializableComponent,
+{
+    let component: C =
+        serde_json::from_value(value).map_err(|e| SerializationError::Json(e.to_string()))?;
+    Ok(Box::new(component))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Hierarchy;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+    struct Position {
+        x: i32,
+        y: i32,
+    }
+    impl Component for Position {}
+    impl SerializableComponent for Position {
+        const NAME: &'static str = "Position";
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Transient;
+    impl Component for Transient {}
+
+    fn registry() -> SerializationRegistry {
+        let mut registry = SerializationRegistry::new();
+        registry.register::<Position>().unwrap();
+        registry.register::<Hierarchy>().unwrap();
+        registry
+    }
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Position>(10);
+        world.register::<Hierarchy>(10);
+        world.register::<Transient>(10);
+        world
+    }
+
+    /// Saves a world with a small hierarchy, then loads it into a world that already has entities.
+    fn round_trip<S, L>(save: S, load: L)
+    where
+        S: Fn(&SerializationRegistry, &World) -> Result<Vec<u8>, SerializationError>,
+        L: Fn(&SerializationRegistry, &mut World, &[u8]) -> Result<EntityMap, SerializationError>,
+    {
+        let registry = registry();
+
+        let mut world = world();
+        let parent = world.add_entity();
+        let child = world.add_entity();
+        *world.add::<Position>(parent).unwrap() = Position { x: 1, y: 2 };
+        world.add::<Transient>(parent).unwrap();
+        world.set_parent(child, parent).unwrap();
+        let saved = save(&registry, &world).unwrap();
+
+        let mut loaded = self::world();
+        let existing = loaded.add_entity();
+        let map = load(&registry, &mut loaded, &saved).unwrap();
+
+        let new_parent = map.get(parent).unwrap();
+        let new_child = map.get(child).unwrap();
+        assert_ne!(existing, new_parent);
+        assert_eq!(3, loaded.entity_len());
+        assert_eq!(
+            Some(&Position { x: 1, y: 2 }),
+            loaded.get::<Position>(new_parent)
+        );
+        assert_eq!(None, loaded.get::<Transient>(new_parent));
+        assert_eq!(Some(new_parent), loaded.parent(new_child));
+        assert_eq!(
+            vec![new_child],
+            loaded.children(new_parent).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn serialization_binary_round_trip() {
+        round_trip(|r, w| r.to_binary(w), |r, w, b| r.from_binary(w, b));
+    }
+
+    #[test]
+    fn serialization_json_round_trip() {
+        round_trip(
+            |r, w| r.to_json(w).map(|s| s.into_bytes()),
+            |r, w, b| r.from_json(w, std::str::from_utf8(b).unwrap()),
+        );
+    }
+
+    #[test]
+    fn serialization_ron_round_trip() {
+        round_trip(
+            |r, w| r.to_ron(w).map(|s| s.into_bytes()),
+            |r, w, b| r.from_ron(w, std::str::from_utf8(b).unwrap()),
+        );
+    }
+
+    #[test]
+    fn serialization_registry_names() {
+        #[derive(Copy, Clone, PartialEq, Default, Debug, Serialize, Deserialize)]
+        struct OtherPosition;
+        impl Component for OtherPosition {}
+        impl SerializableComponent for OtherPosition {
+            const NAME: &'static str = "Position";
+        }
+
+        let mut registry = registry();
+        assert!(registry.register::<Position>().is_ok());
+        assert!(matches!(
+            registry.register::<OtherPosition>(),
+            Err(SerializationError::DuplicateName("Position"))
+        ));
+
+        assert_eq!(
+            Some(ResourceId::from::<Position>()),
+            registry.resource_id("Position")
+        );
+        assert_eq!(
+            Some("core_ecs::Hierarchy"),
+            registry.name(ResourceId::from::<Hierarchy>())
+        );
+        assert_eq!(None, registry.resource_id("Transient"));
+    }
+
+    #[test]
+    fn serialization_rejects_invalid_data() {
+        let registry = registry();
+        let mut world = world();
+
+        let mut bytes = registry.to_binary(&world).unwrap();
+        bytes[4] = 99;
+        assert!(matches!(
+            registry.from_binary(&mut world, &bytes),
+            Err(SerializationError::UnsupportedVersion(99))
+        ));
+        assert!(matches!(
+            registry.from_binary(&mut world, b"nope"),
+            Err(SerializationError::InvalidHeader)
+        ));
+
+        let json =
+            r#"{"version": 1, "entities": [{"entity": 0, "components": {"Missing": null}}]}"#;
+        assert!(matches!(
+            registry.from_json(&mut world, json),
+            Err(SerializationError::UnknownComponent(_))
+        ));
+        assert_eq!(0, world.entity_len());
+    }
+
+    #[test]
+    fn serialization_failed_load_leaves_world_unchanged() {
+        let registry = registry();
+        let mut world = world();
+        let existing = world.add_entity();
+        world.insert(existing, Position { x: 1, y: 1 }).unwrap();
+
+        // The first entity loads, the second has an invalid component
+        let json = r#"{"version": 1, "entities": [
+            {"entity": 0, "components": {"Position": {"x": 2, "y": 2}}},
+            {"entity": 1, "components": {"Position": "invalid"}}
+        ]}"#;
+        assert!(matches!(
+            registry.from_json(&mut world, json),
+            Err(SerializationError::Json(_))
+        ));
+        assert_eq!(vec![existing], world.entities().to_vec());
+        assert_eq!(1, world.query_ref::<&Position>().count());
+
+        // Components that don't fit in the world aren't partially loaded either
+        let mut full = World::new();
+        full.register::<Position>(1);
+        full.register::<Hierarchy>(10);
+        let json = r#"{"version": 1, "entities": [
+            {"entity": 0, "components": {"Position": {"x": 2, "y": 2}}},
+            {"entity": 1, "components": {"Position": {"x": 3, "y": 3}}}
+        ]}"#;
+        assert!(matches!(
+            registry.from_json(&mut full, json),
+            Err(SerializationError::WorldError(_))
+        ));
+        assert_eq!(0, full.entity_len());
+    }
+}
@@ -0,0 +1,231 @@
+// Snapshots copy the full state of a world so it can be restored later, for save states + rollback.
+// Taking a snapshot into an existing one reuses its buffers, so after the first capture no allocations are needed
+// unless new component types were registered. Resources are only included if registered with
+// `World::register_snapshot_resource()`, as they may hold things that can't be cloned, such as loaded assets.
+use crate::component_store::BackingComponentStore;
+use crate::entity_manager::EntityManagerSnapshot;
+use crate::{Entity, ResourceId, World};
+use core::any::Any;
+use core_data_structures::hashmap::HashMap;
+
+type Resources = HashMap<ResourceId, Box<dyn Any + Send>>;
+
+/// Copies a resource from one set of resources to another, removing it from the target if it doesn't exist.
+pub(crate) type CopyResourceFn = fn(&Resources, &mut Resources);
+
+fn copy_resource<R>(from: &Resources, to: &mut Resources)
+where
+    R: Clone + Send + 'static,
+{
+    let id = ResourceId::from::<R>();
+    match from.get(&id).and_then(|r| r.downcast_ref::<R>()) {
+        Some(resource) => match to.get_mut(&id).and_then(|r| r.downcast_mut::<R>()) {
+            Some(existing) => existing.clone_from(resource),
+            None => {
+                to.insert(id, Box::new(resource.clone()));
+            }
+        },
+        None => {
+            to.remove(&id);
+        }
+    }
+}
+
+/// A copy of the entities, components + registered resources of a world.
+#[derive(Debug)]
+pub struct WorldSnapshot {
+    entity_manager: EntityManagerSnapshot,
+    alive_entities: Vec<Entity>,
+    components: HashMap<ResourceId, Box<dyn BackingComponentStore>>,
+    resources: Resources,
+    change_tick: u32,
+    last_change_tick: u32,
+}
+
+impl WorldSnapshot {
+    /// Creates a new, empty, snapshot. Use `World::snapshot_into()` to fill it.
+    pub fn new() -> Self {
+        Self {
+            entity_manager: EntityManagerSnapshot::new(),
+            alive_entities: vec![],
+            components: HashMap::new(),
+            resources: HashMap::new(),
+            change_tick: 0,
+            last_change_tick: 0,
+        }
+    }
+}
+
+impl Default for WorldSnapshot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl World {
+    /// Includes the given resource in snapshots. Registering the same resource twice does nothing.
+    pub fn register_snapshot_resource<R>(&mut self)
+    where
+        R: Clone + Send + 'static,
+    {
+        let id = ResourceId::from::<R>();
+        if !self
+            .snapshot_resources
+            .iter()
+            .any(|(existing, _)| *existing == id)
+        {
+            self.snapshot_resources.push((id, copy_resource::<R>));
+        }
+    }
+
+    /// Returns a snapshot of the world.
+    pub fn snapshot(&self) -> WorldSnapshot {
+        let mut snapshot = WorldSnapshot::new();
+        self.snapshot_into(&mut snapshot);
+        snapshot
+    }
+
+    /// Copies the state of the world into an existing snapshot, reusing its buffers.
+    pub fn snapshot_into(&self, snapshot: &mut WorldSnapshot) {
+        self.entity_manager
+            .snapshot_into(&mut snapshot.entity_manager);
+        snapshot.alive_entities.clone_from(&self.alive_entities);
+        snapshot.change_tick = self.change_tick;
+        snapshot.last_change_tick = self.last_change_tick;
+
+        for (id, store) in self.components.iter() {
+            match snapshot.components.get_mut(id) {
+                Some(snapshot_store) => snapshot_store.copy_from(store.as_ref()),
+                None => {
+                    snapshot.components.insert(*id, store.clone_store());
+                }
+            }
+        }
+
+        for (_, copy) in self.snapshot_resources.iter() {
+            copy(&self.resources, &mut snapshot.resources);
+        }
+    }
+
+    /// Restores the world to the state of the snapshot. Components registered after the snapshot was taken are cleared,
+    /// and registered resources that weren't in the snapshot are removed.
+    pub fn restore(&mut self, snapshot: &WorldSnapshot) {
+        self.entity_manager.restore(&snapshot.entity_manager);
+
+        for entity in self.alive_entities.iter() {
+            self.alive_indexes.remove(entity.id() as usize);
+        }
+        self.alive_entities.clone_from(&snapshot.alive_entities);
+        for (index, entity) in self.alive_entities.iter().enumerate() {
+            self.alive_indexes.insert(entity.id() as usize, index);
+        }
+
+        self.change_tick = snapshot.change_tick;
+        self.last_change_tick = snapshot.last_change_tick;
+
+        for (id, store) in self.components.iter_mut() {
+            match snapshot.components.get(id) {
+                Some(snapshot_store) => store.copy_from(snapshot_store.as_ref()),
+                None => store.clear(),
+            }
+        }
+
+        for (_, copy) in self.snapshot_resources.iter() {
+            copy(&snapshot.resources, &mut self.resources);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Component, Hierarchy};
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position(i32);
+    impl Component for Position {}
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Bullet;
+    impl Component for Bullet {}
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Score(u32);
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Lives(u8);
+
+    #[derive(Clone, PartialEq, Debug)]
+    struct Assets(u32);
+
+    #[test]
+    fn world_snapshot_restore() {
+        let mut world = World::new();
+        world.register::<Position>(10);
+        world.register::<Hierarchy>(10);
+
+        let player = world.add_entity();
+        let gun = world.add_entity();
+        world.add::<Position>(player).unwrap().0 = 1;
+        world.set_parent(gun, player).unwrap();
+
+        let mut snapshot = world.snapshot();
+
+        // Mutate everything
+        world.get_mut::<Position>(player).unwrap().0 = 5;
+        world.kill(gun);
+        let spawned = world.add_entity();
+        world.add::<Position>(spawned).unwrap();
+        world.register::<Bullet>(10);
+        world.add::<Bullet>(spawned).unwrap();
+
+        world.restore(&snapshot);
+        assert_eq!(vec![player, gun], world.entities().to_vec());
+        assert!(world.is_alive(gun));
+        assert!(!world.is_alive(spawned));
+        assert_eq!(Some(&Position(1)), world.get::<Position>(player));
+        assert_eq!(Some(player), world.parent(gun));
+        assert_eq!(0, world.query_ref::<&Bullet>().count());
+
+        // Entities are handed out the same as they were after the snapshot
+        assert_eq!(spawned, world.add_entity());
+
+        // Reusing the snapshot
+        world.get_mut::<Position>(player).unwrap().0 = 7;
+        world.snapshot_into(&mut snapshot);
+        world.kill(player);
+        world.restore(&snapshot);
+        assert_eq!(Some(&Position(7)), world.get::<Position>(player));
+        assert_eq!(3, world.entity_len());
+    }
+
+    #[test]
+    fn world_snapshot_restore_resources() {
+        let mut world = World::new();
+        world.register_snapshot_resource::<Score>();
+        world.register_snapshot_resource::<Lives>();
+        world.insert_resource(Score(10));
+        world.insert_resource(Assets(1));
+
+        let mut snapshot = world.snapshot();
+
+        world.resource_mut::<Score>().unwrap().0 = 50;
+        world.insert_resource(Lives(3));
+        world.insert_resource(Assets(2));
+
+        // Unregistered resources are left alone
+        world.restore(&snapshot);
+        assert_eq!(Some(&Score(10)), world.resource::<Score>());
+        assert_eq!(None, world.resource::<Lives>());
+        assert_eq!(Some(&Assets(2)), world.resource::<Assets>());
+
+        // Reusing the snapshot
+        world.insert_resource(Lives(2));
+        world.snapshot_into(&mut snapshot);
+        world.remove_resource::<Score>();
+        world.resource_mut::<Lives>().unwrap().0 = 0;
+        world.restore(&snapshot);
+        assert_eq!(Some(&Score(10)), world.resource::<Score>());
+        assert_eq!(Some(&Lives(2)), world.resource::<Lives>());
+    }
+}
@@ -0,0 +1,94 @@
+// Paged sparse array, used to map entity ids to dense indexes. Pages are only allocated once an id
+// in their range is used, so memory scales with the ids in use instead of the maximum number of entities.
+
+const PAGE_SIZE: usize = 4096;
+const EMPTY: u32 = u32::MAX;
+
+#[derive(Clone, Debug)]
+pub struct SparseArray {
+    pages: Vec<Option<Box<[u32]>>>,
+}
+
+impl SparseArray {
+    /// Creates a new sparse array without any pages.
+    pub fn new() -> Self {
+        Self { pages: vec![] }
+    }
+
+    /// Returns the value for the given index.
+    pub fn get(&self, index: usize) -> Option<usize> {
+        let page = self.pages.get(index / PAGE_SIZE)?.as_ref()?;
+
+        match page[index % PAGE_SIZE] {
+            EMPTY => None,
+            value => Some(value as usize),
+        }
+    }
+
+    /// Sets the value for the given index, allocating a page if required.
+    pub fn insert(&mut self, index: usize, value: usize) {
+        let page_index = index / PAGE_SIZE;
+        if page_index >= self.pages.len() {
+            self.pages.resize(page_index + 1, None);
+        }
+
+        let page =
+            self.pages[page_index].get_or_insert_with(|| vec![EMPTY; PAGE_SIZE].into_boxed_slice());
+
+        page[index % PAGE_SIZE] = value as u32;
+    }
+
+    /// Clears the value for the given index.
+    pub fn remove(&mut self, index: usize) {
+        if let Some(Some(page)) = self.pages.get_mut(index / PAGE_SIZE) {
+            page[index % PAGE_SIZE] = EMPTY;
+        }
+    }
+
+    /// Returns the number of allocated pages.
+    #[cfg(test)]
+    pub fn allocated_pages(&self) -> usize {
+        self.pages.iter().filter(|p| p.is_some()).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sparse_array_new() {
+        let array = SparseArray::new();
+        assert_eq!(0, array.allocated_pages());
+        assert_eq!(None, array.get(0));
+        assert_eq!(None, array.get(PAGE_SIZE * 100));
+    }
+
+    #[test]
+    fn sparse_array_insert_allocates_pages() {
+        let mut array = SparseArray::new();
+
+        array.insert(3, 10);
+        assert_eq!(Some(10), array.get(3));
+        assert_eq!(None, array.get(4));
+        assert_eq!(1, array.allocated_pages());
+
+        // Only the touched page is allocated
+        array.insert(PAGE_SIZE * 10 + 1, 2);
+        assert_eq!(Some(2), array.get(PAGE_SIZE * 10 + 1));
+        assert_eq!(2, array.allocated_pages());
+        assert_eq!(None, array.get(PAGE_SIZE * 5));
+    }
+
+    #[test]
+    fn sparse_array_remove() {
+        let mut array = SparseArray::new();
+        array.insert(3, 10);
+        array.remove(3);
+        assert_eq!(None, array.get(3));
+
+        // Removing something that doesn't exist does nothing
+        array.remove(PAGE_SIZE * 20);
+        assert_eq!(1, array.allocated_pages());
+    }
+}
@@ -0,0 +1,461 @@
+// Spatial hash for neighbour queries, such as testing bullets against the player.
+// Entities with both a position + an extent component are tracked as bounding spheres in a uniform grid of cells.
+// Entities larger than a cell are stored in every cell they touch. To report each match once without a scratch set,
+// a match is only reported from the lowest cell both shapes share.
+// The index is a world resource, updated by `SpatialIndexUpdate` or `World::update_spatial_index()`. Only entities
+// whose components changed since the last update are re-bucketed. 2D games can leave z as 0.
+// Results are sorted by entity so they're deterministic.
+use std::marker::PhantomData;
+
+use core_data_structures::hashmap::HashMap;
+use core_math::Vec3;
+
+use crate::{Component, Entity, ResourceId, System, SystemAccess, World};
+
+/// A component that gives an entity's position in the spatial index.
+pub trait SpatialPosition: Component {
+    fn spatial_position(&self) -> Vec3;
+}
+
+/// A component that gives the radius of an entity's bounding sphere in the spatial index.
+pub trait SpatialExtent: Component {
+    fn spatial_radius(&self) -> f32;
+}
+
+type Cell = [i32; 3];
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Entry {
+    center: Vec3,
+    radius: f32,
+    min_cell: Cell,
+    max_cell: Cell,
+}
+
+/// A spatial hash of all entities with both `P` + `E`.
+#[derive(Debug)]
+pub struct SpatialIndex<P, E> {
+    cell_size: f32,
+    cells: HashMap<Cell, Vec<Entity>>,
+    entries: HashMap<Entity, Entry>,
+    /// Changes after this tick haven't been indexed yet.
+    seen_tick: u32,
+    stale: Vec<Entity>,
+    phantom: PhantomData<fn() -> (P, E)>,
+}
+
+impl<P, E> SpatialIndex<P, E>
+where
+    P: SpatialPosition,
+    E: SpatialExtent,
+{
+    /// Creates a new index. Cells should be around the size of the common shapes being queried.
+    pub fn new(cell_size: f32) -> Self {
+        Self {
+            cell_size: cell_size.max(f32::EPSILON),
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            seen_tick: 0,
+            stale: vec![],
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns the number of entities being tracked.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether no entities are being tracked.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns whether the entity is being tracked.
+    pub fn contains(&self, entity: Entity) -> bool {
+        self.entries.get(&entity).is_some()
+    }
+
+    /// Fills `out` with all entities whose bounds overlap the given sphere.
+    pub fn query_radius(&self, center: Vec3, radius: f32, out: &mut Vec<Entity>) {
+        let (min, max) = bounds(center, radius);
+        self.query_cells(min, max, out, |entry| {
+            distance_squared(entry.center, center) <= square(entry.radius + radius)
+        });
+    }
+
+    /// Fills `out` with all entities whose bounds overlap the given box.
+    pub fn query_aabb(&self, min: Vec3, max: Vec3, out: &mut Vec<Entity>) {
+        self.query_cells(min, max, out, |entry| {
+            let closest = Vec3::new(
+                entry.center.x.clamp(min.x, max.x),
+                entry.center.y.clamp(min.y, max.y),
+                entry.center.z.clamp(min.z, max.z),
+            );
+            distance_squared(entry.center, closest) <= square(entry.radius)
+        });
+    }
+
+    /// Fills `out` with every pair of entities whose bounds overlap. Each pair is only reported once.
+    pub fn pairs_overlapping(&self, out: &mut Vec<(Entity, Entity)>) {
+        out.clear();
+        for (cell, entities) in self.cells.iter() {
+            for (i, a) in entities.iter().enumerate() {
+                let entry_a = &self.entries.get(a).unwrap();
+                for b in entities[i + 1..].iter() {
+                    let entry_b = &self.entries.get(b).unwrap();
+                    if *cell != max_cell(entry_a.min_cell, entry_b.min_cell) {
+                        continue;
+                    }
+
+                    let reach = entry_a.radius + entry_b.radius;
+                    if distance_squared(entry_a.center, entry_b.center) <= square(reach) {
+                        out.push(match sort_key(*a) <= sort_key(*b) {
+                            true => (*a, *b),
+                            false => (*b, *a),
+                        });
+                    }
+                }
+            }
+        }
+
+        out.sort_unstable_by_key(|(a, b)| (sort_key(*a), sort_key(*b)));
+    }
+
+    /// Updates the index with entities that moved, were added or removed since the last update.
+    pub fn update(&mut self, world: &World) {
+        // Drop entities that were killed or lost a component
+        self.stale.clear();
+        for (entity, _) in self.entries.iter() {
+            if world.get::<P>(*entity).is_none() || world.get::<E>(*entity).is_none() {
+                self.stale.push(*entity);
+            }
+        }
+        for i in 0..self.stale.len() {
+            let entity = self.stale[i];
+            self.remove(entity);
+        }
+
+        let (positions, extents) = match (world.store::<P>(), world.store::<E>()) {
+            (Some(p), Some(e)) => (p, e),
+            _ => return,
+        };
+
+        let seen_tick = self.seen_tick;
+        let changed =
+            |ticks: Option<crate::ComponentTicks>| ticks.is_none_or(|t| t.is_changed(seen_tick));
+        for (entity, position, extent) in world.query_ref::<(Entity, &P, &E)>() {
+            if self.contains(entity)
+                && !changed(positions.ticks(entity))
+                && !changed(extents.ticks(entity))
+            {
+                continue;
+            }
+
+            self.insert(entity, position.spatial_position(), extent.spatial_radius());
+        }
+
+        self.seen_tick = world.last_change_tick();
+    }
+
+    /// Adds or moves the entity.
+    fn insert(&mut self, entity: Entity, center: Vec3, radius: f32) {
+        let (min, max) = bounds(center, radius);
+        let entry = Entry {
+            center,
+            radius,
+            min_cell: self.cell(min),
+            max_cell: self.cell(max),
+        };
+
+        if let Some(existing) = self.entries.get_mut(&entity) {
+            if existing.min_cell == entry.min_cell && existing.max_cell == entry.max_cell {
+                *existing = entry;
+                return;
+            }
+        }
+
+        self.remove(entity);
+        self.entries.insert(entity, entry);
+        for_cells(entry.min_cell, entry.max_cell, |cell| {
+            match self.cells.get_mut(&cell) {
+                Some(entities) => entities.push(entity),
+                None => {
+                    self.cells.insert(cell, vec![entity]);
+                }
+            }
+        });
+    }
+
+    /// Removes the entity. Emptied cells are kept so their buffers can be reused.
+    fn remove(&mut self, entity: Entity) {
+        let entry = match self.entries.remove(&entity) {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        for_cells(entry.min_cell, entry.max_cell, |cell| {
+            if let Some(entities) = self.cells.get_mut(&cell) {
+                entities.retain(|e| *e != entity);
+            }
+        });
+    }
+
+    fn query_cells<F>(&self, min: Vec3, max: Vec3, out: &mut Vec<Entity>, overlaps: F)
+    where
+        F: Fn(&Entry) -> bool,
+    {
+        out.clear();
+        let (min_cell, max_cell) = (self.cell(min), self.cell(max));
+        let mut check_cell = |cell: Cell, entities: &Vec<Entity>| {
+            for entity in entities.iter() {
+                let entry = self.entries.get(entity).unwrap();
+                if cell == self::max_cell(min_cell, entry.min_cell) && overlaps(entry) {
+                    out.push(*entity);
+                }
+            }
+        };
+
+        // Large queries are cheaper to check against the occupied cells than every cell in range
+        let cells_in_range = (0..3)
+            .map(|i| (max_cell[i] as i64 - min_cell[i] as i64 + 1) as u64)
+            .fold(1u64, |total, n| total.saturating_mul(n));
+        if cells_in_range > self.cells.len() as u64 {
+            for (cell, entities) in self.cells.iter() {
+                if (0..3).all(|i| min_cell[i] <= cell[i] && cell[i] <= max_cell[i]) {
+                    check_cell(*cell, entities);
+                }
+            }
+        } else {
+            for_cells(min_cell, max_cell, |cell| {
+                if let Some(entities) = self.cells.get(&cell) {
+                    check_cell(cell, entities);
+                }
+            });
+        }
+
+        out.sort_unstable_by_key(|e| sort_key(*e));
+    }
+
+    fn cell(&self, point: Vec3) -> Cell {
+        [
+            (point.x / self.cell_size).floor() as i32,
+            (point.y / self.cell_size).floor() as i32,
+            (point.z / self.cell_size).floor() as i32,
+        ]
+    }
+}
+
+fn for_cells<F>(min: Cell, max: Cell, mut f: F)
+where
+    F: FnMut(Cell),
+{
+    for x in min[0]..=max[0] {
+        for y in min[1]..=max[1] {
+            for z in min[2]..=max[2] {
+                f([x, y, z]);
+            }
+        }
+    }
+}
+
+fn max_cell(a: Cell, b: Cell) -> Cell {
+    [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])]
+}
+
+fn bounds(center: Vec3, radius: f32) -> (Vec3, Vec3) {
+    (
+        Vec3::new(center.x - radius, center.y - radius, center.z - radius),
+        Vec3::new(center.x + radius, center.y + radius, center.z + radius),
+    )
+}
+
+fn distance_squared(a: Vec3, b: Vec3) -> f32 {
+    square(a.x - b.x) + square(a.y - b.y) + square(a.z - b.z)
+}
+
+fn square(n: f32) -> f32 {
+    n * n
+}
+
+fn sort_key(entity: Entity) -> (u32, u8) {
+    (entity.id(), entity.generation())
+}
+
+/// System that updates the `SpatialIndex<P, E>` resource.
+pub struct SpatialIndexUpdate<P, E> {
+    phantom: PhantomData<fn() -> (P, E)>,
+}
+
+impl<P, E> SpatialIndexUpdate<P, E> {
+    /// Creates a new system.
+    pub fn new() -> Self {
+        Self {
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl<P, E> Default for SpatialIndexUpdate<P, E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<P, E> System for SpatialIndexUpdate<P, E>
+where
+    P: SpatialPosition,
+    E: SpatialExtent,
+{
+    fn name(&self) -> &'static str {
+        "spatial_index_update"
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .read::<P>()
+            .read::<E>()
+            .write_resource::<SpatialIndex<P, E>>()
+    }
+
+    fn run(&mut self, world: &mut World) {
+        world.update_spatial_index::<P, E>();
+    }
+}
+
+impl World {
+    /// Updates the `SpatialIndex<P, E>` resource, if there is one.
+    pub fn update_spatial_index<P, E>(&mut self)
+    where
+        P: SpatialPosition,
+        E: SpatialExtent,
+    {
+        // Take the index out while updating so the world may be read, then put the same allocation back.
+        let id = ResourceId::from::<SpatialIndex<P, E>>();
+        let mut index = match self.resources.remove(&id) {
+            Some(index) => index,
+            None => return,
+        };
+        if let Some(index) = index.downcast_mut::<SpatialIndex<P, E>>() {
+            index.update(self);
+        }
+        self.resources.insert(id, index);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Position(f32, f32);
+    impl Component for Position {}
+    impl SpatialPosition for Position {
+        fn spatial_position(&self) -> Vec3 {
+            Vec3::new(self.0, self.1, 0.)
+        }
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Collidable(f32);
+    impl Component for Collidable {}
+    impl SpatialExtent for Collidable {
+        fn spatial_radius(&self) -> f32 {
+            self.0
+        }
+    }
+
+    type Index = SpatialIndex<Position, Collidable>;
+
+    fn world() -> World {
+        let mut world = World::new();
+        world.register::<Position>(16);
+        world.register::<Collidable>(16);
+        world.insert_resource(Index::new(10.));
+        world
+    }
+
+    fn spawn(world: &mut World, x: f32, y: f32, radius: f32) -> Entity {
+        let entity = world.add_entity();
+        world.insert(entity, Position(x, y)).unwrap();
+        world.insert(entity, Collidable(radius)).unwrap();
+        entity
+    }
+
+    fn radius(world: &World, x: f32, y: f32, radius: f32) -> Vec<Entity> {
+        let mut out = vec![];
+        let index = world.resource::<Index>().unwrap();
+        index.query_radius(Vec3::new(x, y, 0.), radius, &mut out);
+        out
+    }
+
+    #[test]
+    fn spatial_index_queries() {
+        let mut world = world();
+        let player = spawn(&mut world, 0., 0., 2.);
+        let near = spawn(&mut world, 3., 0., 1.);
+        let far = spawn(&mut world, 50., 50., 1.);
+        // Spans many cells, but is only reported once
+        let big = spawn(&mut world, -25., 0., 25.);
+        let untracked = world.add_entity();
+        world.insert(untracked, Position(0., 0.)).unwrap();
+        world.update_spatial_index::<Position, Collidable>();
+
+        assert_eq!(4, world.resource::<Index>().unwrap().len());
+        assert_eq!(vec![player, near, big], radius(&world, 0., 0., 2.));
+        assert_eq!(vec![far], radius(&world, 49., 49., 0.5));
+        assert_eq!(Vec::<Entity>::new(), radius(&world, 100., 0., 5.));
+
+        // A huge query checks occupied cells instead
+        assert_eq!(
+            vec![player, near, far, big],
+            radius(&world, 0., 0., 100_000.)
+        );
+
+        let mut out = vec![];
+        let index = world.resource::<Index>().unwrap();
+        index.query_aabb(Vec3::new(1.5, -1., -1.), Vec3::new(60., 1., 1.), &mut out);
+        assert_eq!(vec![player, near], out);
+
+        let mut pairs = vec![];
+        index.pairs_overlapping(&mut pairs);
+        assert_eq!(vec![(player, near), (player, big)], pairs);
+    }
+
+    #[test]
+    fn spatial_index_updates_incrementally() {
+        let mut world = world();
+        let mut schedule = crate::Schedule::new();
+        schedule.add_stage("update").unwrap();
+        schedule
+            .add_system("update", SpatialIndexUpdate::<Position, Collidable>::new())
+            .unwrap();
+
+        let a = spawn(&mut world, 0., 0., 1.);
+        let b = spawn(&mut world, 20., 0., 1.);
+        schedule.run(&mut world);
+        world.clear_trackers();
+        assert_eq!(vec![a], radius(&world, 0., 0., 1.));
+
+        // Moving into another cell
+        world.get_mut::<Position>(b).unwrap().0 = 1.;
+        schedule.run(&mut world);
+        world.clear_trackers();
+        assert_eq!(vec![a, b], radius(&world, 0., 0., 1.));
+
+        // Changes made after the update in the same tick are picked up next time
+        schedule.run(&mut world);
+        world.get_mut::<Collidable>(a).unwrap().0 = 30.;
+        world.clear_trackers();
+        schedule.run(&mut world);
+        assert_eq!(vec![a], radius(&world, 0., 25., 1.));
+
+        // Removed components + killed entities are dropped
+        world.remove::<Collidable>(a);
+        world.kill(b);
+        schedule.run(&mut world);
+        let index = world.resource::<Index>().unwrap();
+        assert!(index.is_empty());
+        assert_eq!(Vec::<Entity>::new(), radius(&world, 0., 0., 100.));
+    }
+}
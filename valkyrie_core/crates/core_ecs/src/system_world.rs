@@ -0,0 +1,227 @@
+// A view of the world limited to the components + resources a system declared. Systems in the same batch
+// never have conflicting access, so each may be given a view of the same world and run on its own thread.
+// The declared stores + resources are resolved when the view is created on the scheduling thread, so the world
+// itself is never borrowed from another thread. Anything outside the declared access panics.
+use core::any::Any;
+use std::marker::PhantomData;
+
+use crate::{
+    component_store::BackingComponentStore, entity_manager::EntityManager, query::ComponentAccess,
+    sparse_array::SparseArray, Component, ComponentStore, Entity, Query, QueryFilter, QueryIter,
+    QuerySource, ResourceId, SystemAccess, World,
+};
+
+/// The stores + resources a system declared, resolved from the world.
+pub(crate) struct SystemStores {
+    alive_entities: *const [Entity],
+    alive_indexes: *const SparseArray,
+    entity_manager: *const EntityManager,
+    last_change_tick: u32,
+    components: Vec<(ResourceId, *mut dyn BackingComponentStore)>,
+    resources: Vec<(ResourceId, *mut (dyn Any + Send))>,
+}
+
+impl SystemStores {
+    /// Resolves everything in the access from the world.
+    fn new(world: &mut World, access: &SystemAccess) -> Self {
+        let mut components = vec![];
+        let mut resources = vec![];
+
+        // Components + resources share ids, so check both.
+        for id in access.access().iter().map(|a| a.resource_id()) {
+            if let Some(store) = world.components.get_mut(&id) {
+                components.push((id, &mut **store as *mut dyn BackingComponentStore));
+            }
+            if let Some(resource) = world.resources.get_mut(&id) {
+                resources.push((id, &mut **resource as *mut (dyn Any + Send)));
+            }
+        }
+
+        Self {
+            alive_entities: world.alive_entities.as_slice(),
+            alive_indexes: &world.alive_indexes,
+            entity_manager: &world.entity_manager,
+            last_change_tick: world.last_change_tick(),
+            components,
+            resources,
+        }
+    }
+
+    fn component(&self, id: ResourceId) -> Option<*mut dyn BackingComponentStore> {
+        self.components
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, store)| *store)
+    }
+
+    fn resource(&self, id: ResourceId) -> Option<*mut (dyn Any + Send)> {
+        self.resources
+            .iter()
+            .find(|(i, _)| *i == id)
+            .map(|(_, resource)| *resource)
+    }
+
+    /// Returns the store for the given component, if declared and registered.
+    ///
+    /// # Safety
+    /// The world the stores were resolved from must still be valid.
+    pub(crate) unsafe fn store<C>(&self) -> Option<*const ComponentStore<C>>
+    where
+        C: Component,
+    {
+        let store = self.component(ResourceId::from::<C>())?;
+        (*store)
+            .as_any()
+            .downcast_ref::<ComponentStore<C>>()
+            .map(|store| store as *const ComponentStore<C>)
+    }
+
+    /// Returns the mutable store for the given component, if declared and registered.
+    ///
+    /// # Safety
+    /// The world the stores were resolved from must still be valid, and the component must have been declared as written.
+    pub(crate) unsafe fn store_mut<C>(&self) -> Option<*mut ComponentStore<C>>
+    where
+        C: Component,
+    {
+        let store = self.component(ResourceId::from::<C>())?;
+        (*store)
+            .as_any_mut()
+            .downcast_mut::<ComponentStore<C>>()
+            .map(|store| store as *mut ComponentStore<C>)
+    }
+
+    /// Returns all alive entities.
+    ///
+    /// # Safety
+    /// The world the stores were resolved from must still be valid.
+    pub(crate) unsafe fn entities<'w>(&self) -> &'w [Entity] {
+        &*self.alive_entities
+    }
+
+    /// Returns the tick trackers were last cleared on.
+    pub(crate) fn last_change_tick(&self) -> u32 {
+        self.last_change_tick
+    }
+}
+
+/// The world as seen by a `ParallelSystem`.
+pub struct SystemWorld<'w> {
+    stores: SystemStores,
+    access: &'w SystemAccess,
+    phantom: PhantomData<&'w mut World>,
+}
+
+// Only the declared access is used, which never conflicts with other systems running at the same time.
+unsafe impl<'w> Send for SystemWorld<'w> {}
+
+impl<'w> SystemWorld<'w> {
+    /// Creates a new view of the world. Must be called on the thread that owns the world.
+    ///
+    /// # Safety
+    /// `world` must be valid for `'w`. Other views of the world alive at the same time may not have access that
+    /// conflicts with `access`, and nothing else may use the world.
+    pub(crate) unsafe fn new(world: &mut World, access: &'w SystemAccess) -> Self {
+        Self {
+            stores: SystemStores::new(world, access),
+            access,
+            phantom: PhantomData,
+        }
+    }
+
+    /// Returns all alive entities.
+    pub fn entities(&self) -> &[Entity] {
+        unsafe { self.stores.entities() }
+    }
+
+    /// Returns whether the given entity is alive.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        unsafe {
+            match (*self.stores.alive_indexes).get(entity.id() as usize) {
+                Some(index) => {
+                    (*self.stores.alive_entities)[index] == entity
+                        && (*self.stores.entity_manager).is_alive(entity)
+                }
+                None => false,
+            }
+        }
+    }
+
+    /// Retrieves a component for a given entity. The component must have been declared as read or written.
+    pub fn get<C>(&self, entity: Entity) -> Option<&C>
+    where
+        C: Component,
+    {
+        self.check(ComponentAccess::Read(ResourceId::from::<C>()));
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        unsafe { (*self.stores.store::<C>()?).get(entity) }
+    }
+
+    /// Retrieves a mutable component for a given entity. The component must have been declared as written.
+    pub fn get_mut<C>(&mut self, entity: Entity) -> Option<&mut C>
+    where
+        C: Component,
+    {
+        self.check(ComponentAccess::Write(ResourceId::from::<C>()));
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        unsafe { (*self.stores.store_mut::<C>()?).get_mut(entity) }
+    }
+
+    /// Returns an iterator over all entities that match the query. Everything in the query must have been declared.
+    pub fn query<'s, Q>(&'s mut self) -> QueryIter<'s, Q, ()>
+    where
+        Q: Query<'s>,
+    {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Returns an iterator over all entities that match the query and the filter. Everything in both must have been declared.
+    pub fn query_filtered<'s, Q, F>(&'s mut self) -> QueryIter<'s, Q, F>
+    where
+        Q: Query<'s>,
+        F: QueryFilter<'s>,
+    {
+        let mut access = vec![];
+        Q::access(&mut access);
+        F::access(&mut access);
+        for a in access {
+            self.check(a);
+        }
+
+        QueryIter::<Q, F>::validate_access();
+        unsafe { QueryIter::<Q, F>::init(&QuerySource::system(&self.stores)) }
+    }
+
+    /// Retrieves the given resource. The resource must have been declared as read or written.
+    pub fn resource<R>(&self) -> Option<&R>
+    where
+        R: Send + Sync + 'static,
+    {
+        self.check(ComponentAccess::Read(ResourceId::from::<R>()));
+        let resource = self.stores.resource(ResourceId::from::<R>())?;
+        unsafe { (*resource).downcast_ref::<R>() }
+    }
+
+    /// Retrieves the given resource mutably. The resource must have been declared as written.
+    pub fn resource_mut<R>(&mut self) -> Option<&mut R>
+    where
+        R: Send + Sync + 'static,
+    {
+        self.check(ComponentAccess::Write(ResourceId::from::<R>()));
+        let resource = self.stores.resource(ResourceId::from::<R>())?;
+        unsafe { (*resource).downcast_mut::<R>() }
+    }
+
+    /// Panics if the access wasn't declared.
+    fn check(&self, access: ComponentAccess) {
+        if !self.access.allows(&access) {
+            panic!("System accessed {:?} without declaring it", access);
+        }
+    }
+}
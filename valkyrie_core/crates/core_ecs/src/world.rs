@@ -1,11 +1,26 @@
+use crate::checksum::ChecksumFn;
 use crate::component_store::BackingComponentStore;
-use crate::{entity_manager::EntityManager, ComponentStore, ComponentStoreError, Entity};
+use crate::events::BackingEvents;
+use crate::query::{Query, QueryFilter, QueryIter, ReadOnlyQuery, RemovedComponents};
+use crate::snapshot::CopyResourceFn;
+use crate::sparse_array::SparseArray;
+use crate::{
+    entity_manager::EntityManager, Commands, ComponentStore, ComponentStoreError, Entity,
+    EntityMap, Reflect,
+};
+use core::any::Any;
 use core_data_structures::hashmap::HashMap;
 
 #[derive(Debug)]
 pub enum WorldError {
     ComponentStoreError(ComponentStoreError),
     ComponentNotRegistered,
+    /// An `on_add` hook removed the component that was being added.
+    ComponentRemovedByHook,
+    EntityNotAlive,
+    InvalidParent,
+    EventNotRegistered,
+    EventQueueFull,
 }
 
 impl From<ComponentStoreError> for WorldError {
@@ -14,28 +29,64 @@ impl From<ComponentStoreError> for WorldError {
     }
 }
 
+/// Called when a component is added to or removed from an entity.
+pub type ComponentHook = fn(&mut World, Entity);
+
 #[derive(Debug)]
 pub struct World {
-    entity_manager: EntityManager,
-    alive_entities: Vec<Entity>,
-    alive_index: usize,
-    components: HashMap<ResourceId, Box<dyn BackingComponentStore>>,
+    pub(crate) entity_manager: EntityManager,
+    pub(crate) alive_entities: Vec<Entity>,
+    pub(crate) alive_indexes: SparseArray,
+    pub(crate) components: HashMap<ResourceId, Box<dyn BackingComponentStore>>,
+    pub(crate) resources: HashMap<ResourceId, Box<dyn Any + Send>>,
+    pub(crate) events: HashMap<ResourceId, Box<dyn BackingEvents>>,
+    /// Hooks in the order they were registered. Kept as lists as there are typically only a few.
+    pub(crate) on_add: Vec<(ResourceId, ComponentHook)>,
+    pub(crate) on_remove: Vec<(ResourceId, ComponentHook)>,
+    /// Components included in checksums, in the order they were registered.
+    pub(crate) checksums: Vec<(ResourceId, ChecksumFn)>,
+    /// Resources included in snapshots.
+    pub(crate) snapshot_resources: Vec<(ResourceId, CopyResourceFn)>,
+    pub(crate) change_tick: u32,
+    pub(crate) last_change_tick: u32,
 }
 
-#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct ResourceId {
     type_id: core::any::TypeId,
 }
 
-pub trait Component: Sized + Default + Clone + std::fmt::Debug + 'static {}
+/// Data attached to an entity. Components are shared between threads when systems run in parallel, so must be `Send + Sync`.
+pub trait Component: Sized + Default + Clone + std::fmt::Debug + Send + Sync + 'static {
+    /// Updates any entity references held by the component. Called when entities are given new handles,
+    /// such as when loading a saved world.
+    fn map_entities(&mut self, _map: &EntityMap) {}
+
+    /// Returns the component as a reflected value, if it supports reflection. Implemented by `#[derive(Component)]`.
+    fn as_reflect(&self) -> Option<&dyn Reflect> {
+        None
+    }
+
+    /// Returns the component as a mutable reflected value, if it supports reflection.
+    fn as_reflect_mut(&mut self) -> Option<&mut dyn Reflect> {
+        None
+    }
+}
+
+#[derive(Copy, Clone)]
+pub(crate) enum HookKind {
+    Add,
+    Remove,
+}
 
 impl ResourceId {
-    fn from<C>() -> Self
+    /// Returns the id for the given component or resource.
+    pub fn from<T>() -> Self
     where
-        C: Component,
+        T: 'static,
     {
         Self {
-            type_id: core::any::TypeId::of::<C>(),
+            type_id: core::any::TypeId::of::<T>(),
         }
     }
 }
@@ -44,10 +95,18 @@ impl World {
     /// Creates a new world
     pub fn new() -> Self {
         Self {
-            alive_entities: vec![Entity::new(0, 0); Entity::MAX_ENTITIES()],
-            alive_index: 0,
+            alive_entities: vec![],
+            alive_indexes: SparseArray::new(),
             entity_manager: EntityManager::new(),
             components: HashMap::new(),
+            resources: HashMap::new(),
+            events: HashMap::new(),
+            on_add: vec![],
+            on_remove: vec![],
+            checksums: vec![],
+            snapshot_resources: vec![],
+            change_tick: 1,
+            last_change_tick: 0,
         }
     }
 
@@ -58,43 +117,123 @@ impl World {
     {
         let component_id = ResourceId::from::<C>();
         if self.components.get(&component_id).is_none() {
-            self.components
-                .insert(component_id, Box::new(ComponentStore::<C>::new(capacity)));
+            let mut store = ComponentStore::<C>::new(capacity);
+            store.set_change_tick(self.change_tick);
+            self.components.insert(component_id, Box::new(store));
         }
     }
 
     /// Adds a new entity
     pub fn add_entity(&mut self) -> Entity {
+        self.flush_reserved();
+
         let entity = self.entity_manager.create();
-        self.alive_entities[self.alive_index] = entity;
-        self.alive_index += 1;
+        self.alive_indexes
+            .insert(entity.id() as usize, self.alive_entities.len());
+        self.alive_entities.push(entity);
         entity
     }
 
+    /// Returns a new command buffer for deferring changes to the world.
+    pub fn commands(&self) -> Commands {
+        Commands::new(self.entity_manager.reserver())
+    }
+
+    /// Makes all entities reserved by command buffers alive.
+    pub(crate) fn flush_reserved(&mut self) {
+        let alive_entities = &mut self.alive_entities;
+        let alive_indexes = &mut self.alive_indexes;
+        self.entity_manager.flush_reserved(|entity| {
+            alive_indexes.insert(entity.id() as usize, alive_entities.len());
+            alive_entities.push(entity);
+        });
+    }
+
+    /// Returns whether the given entity is alive. Entities that were killed, or whose id was reused, are not alive.
+    pub fn is_alive(&self, entity: Entity) -> bool {
+        match self.alive_indexes.get(entity.id() as usize) {
+            Some(index) => {
+                self.alive_entities[index] == entity && self.entity_manager.is_alive(entity)
+            }
+            None => false,
+        }
+    }
+
+    /// Returns the store for the given component, if registered.
+    pub(crate) fn store<C>(&self) -> Option<&ComponentStore<C>>
+    where
+        C: Component,
+    {
+        self.components
+            .get(&ResourceId::from::<C>())
+            .and_then(|store| store.as_any().downcast_ref::<ComponentStore<C>>())
+    }
+
+    /// Returns the mutable store for the given component, if registered.
+    pub(crate) fn store_mut<C>(&mut self) -> Option<&mut ComponentStore<C>>
+    where
+        C: Component,
+    {
+        self.components
+            .get_mut(&ResourceId::from::<C>())
+            .and_then(|store| store.as_any_mut().downcast_mut::<ComponentStore<C>>())
+    }
+
     /// Retrieves a component for a given entity
     pub fn get<C>(&self, entity: Entity) -> Option<&C>
     where
         C: Component,
     {
-        if let Some(store) = self.components.get(&ResourceId::from::<C>()) {
-            match store.as_any().downcast_ref::<ComponentStore<C>>() {
-                Some(component_store) => {
-                    return component_store.get(entity);
-                }
-                None => {}
-            }
+        if !self.is_alive(entity) {
+            return None;
         }
 
-        None
+        self.store::<C>()?.get(entity)
+    }
+
+    /// Returns an iterator over all entities that match the query.
+    /// Example: `world.query::<(&Position, &mut Velocity, Option<&Collidable>)>()`
+    pub fn query<'w, Q>(&'w mut self) -> QueryIter<'w, Q, ()>
+    where
+        Q: Query<'w>,
+    {
+        QueryIter::new(self)
+    }
+
+    /// Returns an iterator over all entities that match the query and the filter.
+    /// Example: `world.query_filtered::<&mut Position, (With<Player>, Without<Debug>)>()`
+    pub fn query_filtered<'w, Q, F>(&'w mut self) -> QueryIter<'w, Q, F>
+    where
+        Q: Query<'w>,
+        F: QueryFilter<'w>,
+    {
+        QueryIter::new(self)
+    }
+
+    /// Returns an iterator over all entities that match the read only query.
+    pub fn query_ref<'w, Q>(&'w self) -> QueryIter<'w, Q, ()>
+    where
+        Q: ReadOnlyQuery<'w>,
+    {
+        QueryIter::new_read_only(self)
+    }
+
+    /// Returns an iterator over all entities that match the read only query and the filter.
+    pub fn query_ref_filtered<'w, Q, F>(&'w self) -> QueryIter<'w, Q, F>
+    where
+        Q: ReadOnlyQuery<'w>,
+        F: QueryFilter<'w>,
+    {
+        QueryIter::new_read_only(self)
     }
 
     /// Returns a list of entities that are alive
     pub fn entities(&self) -> &[Entity] {
-        &self.alive_entities[0..self.alive_index]
+        &self.alive_entities
     }
 
     pub fn entity_len(&self) -> usize {
-        self.alive_index
+        self.alive_entities.len()
     }
 
     /// Retrieves a mutable component for a given entity
@@ -102,65 +241,270 @@ impl World {
     where
         C: Component,
     {
-        if let Some(store) = self.components.get_mut(&ResourceId::from::<C>()) {
-            match store.as_any_mut().downcast_mut::<ComponentStore<C>>() {
-                Some(component_store) => {
-                    return component_store.get_mut(entity);
-                }
-                None => {}
-            }
+        if !self.is_alive(entity) {
+            return None;
         }
 
-        None
+        self.store_mut::<C>()?.get_mut(entity)
     }
 
-    /// Adds a component to the entity.
+    /// Adds a component to the entity. If the entity already has the component, the existing one is returned.
+    /// `on_add` hooks see the default value, use `insert()` if they need the actual value.
     pub fn add<C>(&mut self, entity: Entity) -> Result<&mut C, WorldError>
     where
         C: Component,
     {
-        if let Some(store) = self.components.get_mut(&ResourceId::from::<C>()) {
-            match store.as_any_mut().downcast_mut::<ComponentStore<C>>() {
-                Some(component_store) => match component_store.add(entity) {
-                    Ok(c) => return Ok(c),
-                    Err(e) => return Err(e.into()),
-                },
-                None => {}
+        self.add_component(entity, None)
+    }
+
+    /// Adds the component to the entity, replacing any existing one. `on_add` hooks are only called if the entity
+    /// didn't already have the component.
+    pub fn insert<C>(&mut self, entity: Entity, component: C) -> Result<&mut C, WorldError>
+    where
+        C: Component,
+    {
+        self.add_component(entity, Some(component))
+    }
+
+    fn add_component<C>(&mut self, entity: Entity, value: Option<C>) -> Result<&mut C, WorldError>
+    where
+        C: Component,
+    {
+        if !self.is_alive(entity) {
+            return Err(WorldError::EntityNotAlive);
+        }
+
+        let component_store = self
+            .store_mut::<C>()
+            .ok_or(WorldError::ComponentNotRegistered)?;
+        let added = !component_store.contains(entity);
+        let component = component_store.add(entity)?;
+        if let Some(value) = value {
+            *component = value;
+        }
+
+        if added && !self.on_add.is_empty() {
+            self.run_hooks(HookKind::Add, ResourceId::from::<C>(), entity);
+
+            // A hook may have killed the entity or removed the component
+            if !self.is_alive(entity) {
+                return Err(WorldError::EntityNotAlive);
             }
         }
 
-        return Err(WorldError::ComponentNotRegistered);
+        // Fetched again as the hooks needed the world
+        self.store_mut::<C>()
+            .and_then(|store| store.get_mut(entity))
+            .ok_or(WorldError::ComponentRemovedByHook)
     }
 
-    /// Removes a component for a given entity
+    /// Removes a component for a given entity. `on_remove` hooks are called before the component is removed.
     pub fn remove<C>(&mut self, entity: Entity)
     where
         C: Component,
     {
-        if let Some(store) = self.components.get_mut(&ResourceId::from::<C>()) {
-            match store.as_any_mut().downcast_mut::<ComponentStore<C>>() {
-                Some(component_store) => {
-                    component_store.destroy(entity);
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        let id = ResourceId::from::<C>();
+        if !self.on_remove.is_empty() && self.store::<C>().is_some_and(|s| s.contains(entity)) {
+            self.run_hooks(HookKind::Remove, id, entity);
+            if !self.is_alive(entity) {
+                return;
+            }
+        }
+
+        if let Some(component_store) = self.store_mut::<C>() {
+            component_store.destroy(entity);
+        }
+    }
+
+    /// Registers a hook that is called after the given component is added to an entity.
+    /// Hooks are not called when restoring snapshots.
+    pub fn on_add<C>(&mut self, hook: ComponentHook)
+    where
+        C: Component,
+    {
+        self.on_add.push((ResourceId::from::<C>(), hook));
+    }
+
+    /// Registers a hook that is called before the given component is removed from an entity, including when the entity is killed.
+    /// The component may still be read from within the hook. Hooks should not kill the entity they are called for.
+    pub fn on_remove<C>(&mut self, hook: ComponentHook)
+    where
+        C: Component,
+    {
+        self.on_remove.push((ResourceId::from::<C>(), hook));
+    }
+
+    /// Calls the hooks for every component the entity has. Stops early if a hook kills the entity.
+    pub(crate) fn run_entity_hooks(&mut self, kind: HookKind, entity: Entity) {
+        let mut i = 0;
+        loop {
+            let hooks = match kind {
+                HookKind::Add => &self.on_add,
+                HookKind::Remove => &self.on_remove,
+            };
+
+            let (component, hook) = match hooks.get(i) {
+                Some(hook) => *hook,
+                None => return,
+            };
+
+            let has_component = self
+                .components
+                .get(&component)
+                .is_some_and(|store| store.contains(entity));
+            if has_component {
+                hook(self, entity);
+                if !self.is_alive(entity) {
+                    return;
                 }
-                None => {}
             }
+
+            i += 1;
+        }
+    }
+
+    /// Calls all hooks for the given component. Hooks may register more hooks, so they are looked up by index.
+    fn run_hooks(&mut self, kind: HookKind, component: ResourceId, entity: Entity) {
+        let mut i = 0;
+        loop {
+            let hooks = match kind {
+                HookKind::Add => &self.on_add,
+                HookKind::Remove => &self.on_remove,
+            };
+
+            match hooks.get(i) {
+                Some((id, hook)) => {
+                    let hook = *hook;
+                    if *id == component {
+                        hook(self, entity);
+                    }
+                }
+                None => return,
+            }
+
+            i += 1;
+        }
+    }
+
+    /// Returns the tick that adds + mutable accesses are currently recorded on.
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// Returns the tick trackers were last cleared on. `Added` + `Changed` return components modified after this tick.
+    pub fn last_change_tick(&self) -> u32 {
+        self.last_change_tick
+    }
+
+    /// Moves on to a new change tick and forgets all removed components. Typically called once per frame,
+    /// after everything interested in changes has run.
+    pub fn clear_trackers(&mut self) {
+        self.last_change_tick = self.change_tick;
+        self.change_tick += 1;
+
+        for store in self.components.values_mut() {
+            store.clear_trackers(self.change_tick);
         }
     }
 
-    /// Kills a given entity
+    /// Returns the entities that had the given component removed, or were killed, since trackers were last cleared.
+    pub fn removed_components<C>(&self) -> RemovedComponents<'_, C>
+    where
+        C: Component,
+    {
+        RemovedComponents::new(self.store::<C>().map_or(&[], |store| store.removed()))
+    }
+
+    /// Returns the given component for the entity as a reflected value, if the component supports reflection.
+    pub fn reflect(&self, entity: Entity, component: ResourceId) -> Option<&dyn Reflect> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        self.components.get(&component)?.reflect(entity)
+    }
+
+    /// Returns the given component for the entity as a mutable reflected value. Marks the component as changed.
+    pub fn reflect_mut(
+        &mut self,
+        entity: Entity,
+        component: ResourceId,
+    ) -> Option<&mut dyn Reflect> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        self.components.get_mut(&component)?.reflect_mut(entity)
+    }
+
+    /// Inserts a resource into the world. Resources are singletons, such as the score or config, that are not tied to an entity.
+    /// If the resource already exists, it is replaced.
+    pub fn insert_resource<R>(&mut self, resource: R)
+    where
+        R: Send + 'static,
+    {
+        self.resources
+            .insert(ResourceId::from::<R>(), Box::new(resource));
+    }
+
+    /// Removes the given resource from the world, returning it if it existed.
+    pub fn remove_resource<R>(&mut self) -> Option<R>
+    where
+        R: 'static,
+    {
+        let resource = self.resources.remove(&ResourceId::from::<R>())?;
+        resource.downcast::<R>().ok().map(|r| *r)
+    }
+
+    /// Retrieves the given resource.
+    pub fn resource<R>(&self) -> Option<&R>
+    where
+        R: 'static,
+    {
+        self.resources
+            .get(&ResourceId::from::<R>())
+            .and_then(|r| r.downcast_ref::<R>())
+    }
+
+    /// Retrieves the given resource mutably.
+    pub fn resource_mut<R>(&mut self) -> Option<&mut R>
+    where
+        R: 'static,
+    {
+        self.resources
+            .get_mut(&ResourceId::from::<R>())
+            .and_then(|r| r.downcast_mut::<R>())
+    }
+
+    /// Kills a given entity. Any children are detached, use `kill_recursive()` to kill them as well.
     pub fn kill(&mut self, entity: Entity) {
+        // Stale handles may point at an entity that reused the id, so leave it alone
+        if !self.is_alive(entity) {
+            return;
+        }
+
+        self.detach_hierarchy(entity);
+
+        // Let hooks see the components before they are destroyed
+        self.run_entity_hooks(HookKind::Remove, entity);
+        if !self.is_alive(entity) {
+            return;
+        }
+
         // Mark it as dead and swap it with the last element
         {
-            let index_to_remove = self
-                .alive_entities
-                .iter()
-                .position(|e| e.id() == entity.id());
-
-            if self.alive_index > 0 && index_to_remove.is_some() {
-                let index_to_remove = index_to_remove.unwrap_or_default();
-                self.alive_entities
-                    .swap(index_to_remove, self.alive_index - 1);
-                self.alive_index -= 1;
+            let id = entity.id() as usize;
+            if let Some(index_to_remove) = self.alive_indexes.get(id) {
+                self.alive_entities.swap_remove(index_to_remove);
+                if let Some(swapped) = self.alive_entities.get(index_to_remove) {
+                    self.alive_indexes
+                        .insert(swapped.id() as usize, index_to_remove);
+                }
+                self.alive_indexes.remove(id);
             }
         }
 
@@ -168,6 +512,9 @@ impl World {
         for store in self.components.values_mut() {
             store.destroy(entity);
         }
+
+        // Bump the generation so the id may be recycled
+        self.entity_manager.destroy(entity);
     }
 }
 /*
@@ -238,3 +585,313 @@ mod specs_test {
     }
 }
  */
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::entity_manager::MINIMUM_FREE_INDICES;
+    use crate::{Added, Changed, With, Without};
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[test]
+    fn world_kill_rejects_stale_handles() {
+        let mut world = World::new();
+        world.register::<Health>(4);
+
+        let a = world.add_entity();
+        let b = world.add_entity();
+        let c = world.add_entity();
+        world.add::<Health>(a).unwrap().0 = 1;
+        world.add::<Health>(c).unwrap().0 = 3;
+
+        world.kill(a);
+        assert!(!world.is_alive(a));
+        assert!(world.is_alive(b));
+        assert!(world.is_alive(c));
+        assert_eq!(vec![c, b], world.entities().to_vec());
+
+        assert_eq!(None, world.get::<Health>(a));
+        assert_eq!(None, world.get_mut::<Health>(a));
+        assert!(matches!(
+            world.add::<Health>(a),
+            Err(WorldError::EntityNotAlive)
+        ));
+        assert_eq!(Some(&Health(3)), world.get::<Health>(c));
+
+        // Killing twice does nothing
+        world.kill(a);
+        assert_eq!(2, world.entity_len());
+
+        world.kill(c);
+        world.kill(b);
+        assert_eq!(0, world.entity_len());
+    }
+
+    #[test]
+    fn world_removed_components() {
+        let mut world = World::new();
+        world.register::<Health>(4);
+
+        let a = world.add_entity();
+        let b = world.add_entity();
+        let c = world.add_entity();
+        world.add::<Health>(a).unwrap();
+        world.add::<Health>(b).unwrap();
+
+        world.remove::<Health>(a);
+        world.kill(b);
+        world.kill(c);
+        assert_eq!(
+            vec![a, b],
+            world.removed_components::<Health>().collect::<Vec<_>>()
+        );
+
+        world.clear_trackers();
+        assert_eq!(0, world.removed_components::<Health>().count());
+    }
+
+    /// Records hook calls, along with the value of the component when the hook ran.
+    #[derive(Debug, Default)]
+    struct HookLog(Vec<(&'static str, Entity, Option<u32>)>);
+
+    fn log_hook(world: &mut World, event: &'static str, entity: Entity) {
+        let health = world.get::<Health>(entity).map(|h| h.0);
+        world
+            .resource_mut::<HookLog>()
+            .unwrap()
+            .0
+            .push((event, entity, health));
+    }
+
+    #[test]
+    fn world_component_hooks() {
+        let mut world = World::new();
+        world.register::<Health>(4);
+        world.insert_resource(HookLog::default());
+        world.on_add::<Health>(|world, entity| log_hook(world, "add", entity));
+        world.on_remove::<Health>(|world, entity| log_hook(world, "remove", entity));
+
+        let a = world.add_entity();
+        let b = world.add_entity();
+        let c = world.add_entity();
+        world.add::<Health>(a).unwrap().0 = 1;
+        world.insert(b, Health(2)).unwrap();
+
+        // Already present, so not added again
+        world.add::<Health>(a).unwrap();
+        world.insert(b, Health(3)).unwrap();
+
+        world.remove::<Health>(a);
+        world.remove::<Health>(a);
+        world.kill(b);
+        world.kill(c);
+
+        let mut commands = world.commands();
+        let d = commands.spawn();
+        commands.add(d, Health(4));
+        commands.kill(d);
+        commands.apply(&mut world).unwrap();
+
+        assert_eq!(
+            vec![
+                ("add", a, Some(0)),
+                ("add", b, Some(2)),
+                ("remove", a, Some(1)),
+                ("remove", b, Some(3)),
+                ("add", d, Some(4)),
+                ("remove", d, Some(4)),
+            ],
+            world.resource::<HookLog>().unwrap().0
+        );
+    }
+
+    #[test]
+    fn world_component_removed_by_hook() {
+        let mut world = World::new();
+        world.register::<Health>(4);
+        world.on_add::<Health>(|world, entity| world.remove::<Health>(entity));
+
+        let entity = world.add_entity();
+        assert!(matches!(
+            world.add::<Health>(entity),
+            Err(WorldError::ComponentRemovedByHook)
+        ));
+        assert_eq!(None, world.get::<Health>(entity));
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Score(u32);
+
+    #[test]
+    fn world_resources() {
+        let mut world = World::new();
+        assert_eq!(None, world.resource::<Score>());
+
+        world.insert_resource(Score(10));
+        world.resource_mut::<Score>().unwrap().0 += 5;
+        assert_eq!(Some(&Score(15)), world.resource::<Score>());
+
+        // Inserting again replaces it
+        world.insert_resource(Score(1));
+        assert_eq!(Some(&Score(1)), world.resource::<Score>());
+
+        // Resources and components of the same type don't collide
+        world.register::<Health>(1);
+        world.insert_resource(Health(7));
+        let entity = world.add_entity();
+        world.add::<Health>(entity).unwrap();
+        assert_eq!(Some(&Health(0)), world.get::<Health>(entity));
+        assert_eq!(Some(&Health(7)), world.resource::<Health>());
+
+        assert_eq!(Some(Score(1)), world.remove_resource::<Score>());
+        assert_eq!(None, world.resource::<Score>());
+        assert_eq!(None, world.remove_resource::<Score>());
+    }
+
+    #[derive(Copy, Clone, PartialEq, Default, Debug)]
+    struct Player;
+    impl Component for Player {}
+
+    #[test]
+    fn world_tag_components() {
+        let mut world = World::new();
+        world.register::<Health>(4);
+        world.register::<Player>(4);
+        let a = world.add_entity();
+        let b = world.add_entity();
+        let c = world.add_entity();
+        world.insert(a, Health(1)).unwrap();
+        world.insert(b, Health(2)).unwrap();
+        world.add::<Player>(b).unwrap();
+        world.insert(c, Player).unwrap();
+
+        assert_eq!(Some(&Player), world.get::<Player>(b));
+        assert_eq!(None, world.get::<Player>(a));
+        assert_eq!(
+            vec![b, c],
+            world
+                .query_ref_filtered::<Entity, With<Player>>()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![(b, Health(2))],
+            world
+                .query_ref::<(Entity, &Health, &Player)>()
+                .map(|(e, h, _)| (e, *h))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(1, world.query::<(&mut Health, &mut Player)>().count());
+        assert_eq!(
+            vec![a],
+            world
+                .query_ref_filtered::<Entity, (With<Health>, Without<Player>)>()
+                .collect::<Vec<_>>()
+        );
+
+        world.remove::<Player>(b);
+        world.kill(c);
+        assert_eq!(
+            vec![b, c],
+            world.removed_components::<Player>().collect::<Vec<_>>()
+        );
+        assert_eq!(0, world.query_ref::<&Player>().count());
+        assert_eq!(0, world.store::<Player>().unwrap().len());
+    }
+
+    #[test]
+    fn world_tag_change_detection() {
+        let mut world = World::new();
+        world.register::<Health>(4);
+        world.register::<Player>(4);
+        let a = world.add_entity();
+        let b = world.add_entity();
+        world.insert(a, Health(1)).unwrap();
+        world.insert(b, Health(2)).unwrap();
+        world.add::<Player>(a).unwrap();
+        world.clear_trackers();
+
+        world.add::<Player>(b).unwrap();
+        assert_eq!(
+            vec![b],
+            world
+                .query_ref_filtered::<Entity, Added<Player>>()
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![b],
+            world
+                .query_ref_filtered::<Entity, Changed<Player>>()
+                .collect::<Vec<_>>()
+        );
+
+        world.clear_trackers();
+        assert_eq!(
+            0,
+            world.query_ref_filtered::<Entity, Added<Player>>().count()
+        );
+        world.get_mut::<Player>(a).unwrap();
+        assert_eq!(
+            vec![a],
+            world
+                .query_ref_filtered::<Entity, Changed<Player>>()
+                .collect::<Vec<_>>()
+        );
+
+        world.clear_trackers();
+        for _ in world.query::<(&Health, &mut Player)>() {}
+        assert_eq!(
+            vec![a, b],
+            world
+                .query_ref_filtered::<Entity, Changed<Player>>()
+                .collect::<Vec<_>>()
+        );
+
+        world.clear_trackers();
+        world.remove::<Player>(a);
+        assert_eq!(
+            vec![a],
+            world.removed_components::<Player>().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            0,
+            world
+                .query_ref_filtered::<Entity, Changed<Player>>()
+                .count()
+        );
+    }
+
+    #[test]
+    fn world_reuses_ids_after_minimum_free_indices() {
+        let mut world = World::new();
+        world.register::<Health>(MINIMUM_FREE_INDICES + 2);
+
+        let entities: Vec<Entity> = (0..MINIMUM_FREE_INDICES)
+            .map(|_| world.add_entity())
+            .collect();
+        for entity in entities.iter() {
+            world.add::<Health>(*entity).unwrap().0 = entity.id();
+            world.kill(*entity);
+        }
+
+        // Ids are not reused until enough have been freed
+        let fresh = world.add_entity();
+        assert_eq!(MINIMUM_FREE_INDICES as u32, fresh.id());
+        world.kill(fresh);
+
+        let reused = world.add_entity();
+        assert_eq!(entities[0].id(), reused.id());
+        assert_ne!(entities[0], reused);
+        assert!(world.is_alive(reused));
+        assert!(!world.is_alive(entities[0]));
+
+        // Stale handles don't resolve to the new entity
+        world.add::<Health>(reused).unwrap().0 = 42;
+        assert_eq!(None, world.get::<Health>(entities[0]));
+        world.kill(entities[0]);
+        assert!(world.is_alive(reused));
+        assert_eq!(Some(&Health(42)), world.get::<Health>(reused));
+    }
+}
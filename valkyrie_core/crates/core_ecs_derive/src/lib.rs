@@ -0,0 +1,167 @@
+// Derive macros for core_ecs.
+// The generated code refers to `core_ecs::`, so crates that use core_ecs through another crate
+// should bring it into scope under that name, such as `use valkyrie_core::ecs as core_ecs;`.
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Meta, NestedMeta};
+
+/// Implements `Component` + `Reflect` for the type. Fields may be hidden from reflection with `#[reflect(skip)]`.
+#[proc_macro_derive(Component, attributes(reflect))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    match component(&input) {
+        Ok(tokens) => tokens.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+/// Implements `HashComponent` by feeding the type's `Hash` implementation into the checksum.
+/// Types holding floats can't implement `Hash`, so should implement `HashComponent` by hand through `to_bits()`.
+#[proc_macro_derive(HashComponent)]
+pub fn derive_hash_component(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let tokens = quote! {
+        impl #impl_generics core_ecs::HashComponent for #ident #ty_generics #where_clause {
+            fn hash_component(&self, checksum: &mut core_ecs::Checksum) {
+                std::hash::Hash::hash(self, checksum);
+            }
+        }
+    };
+
+    tokens.into()
+}
+
+/// A field that is exposed to reflection.
+struct ReflectedField {
+    name: String,
+    member: TokenStream2,
+    type_name: String,
+}
+
+fn component(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let fields = reflected_fields(input)?;
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let infos = fields.iter().map(|f| {
+        let name = &f.name;
+        let type_name = &f.type_name;
+        quote! { core_ecs::Field { name: #name, type_name: #type_name } }
+    });
+    let names: Vec<&String> = fields.iter().map(|f| &f.name).collect();
+    let members: Vec<&TokenStream2> = fields.iter().map(|f| &f.member).collect();
+
+    Ok(quote! {
+        impl #impl_generics core_ecs::Component for #ident #ty_generics #where_clause {
+            fn as_reflect(&self) -> Option<&dyn core_ecs::Reflect> {
+                Some(self)
+            }
+
+            fn as_reflect_mut(&mut self) -> Option<&mut dyn core_ecs::Reflect> {
+                Some(self)
+            }
+        }
+
+        impl #impl_generics core_ecs::Reflect for #ident #ty_generics #where_clause {
+            fn fields(&self) -> &'static [core_ecs::Field] {
+                &[#(#infos),*]
+            }
+
+            fn field(&self, name: &str) -> Option<&dyn core_ecs::Reflect> {
+                match name {
+                    #(#names => Some(&self.#members),)*
+                    _ => None,
+                }
+            }
+
+            fn field_mut(&mut self, name: &str) -> Option<&mut dyn core_ecs::Reflect> {
+                match name {
+                    #(#names => Some(&mut self.#members),)*
+                    _ => None,
+                }
+            }
+
+            fn as_any(&self) -> &dyn std::any::Any {
+                self
+            }
+
+            fn as_any_mut(&mut self) -> &mut dyn std::any::Any {
+                self
+            }
+        }
+    })
+}
+
+/// Returns the fields to reflect. Enums are treated as values without fields.
+fn reflected_fields(input: &DeriveInput) -> syn::Result<Vec<ReflectedField>> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        Data::Enum(_) => return Ok(vec![]),
+        Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "Component can not be derived for unions",
+            ))
+        }
+    };
+
+    let mut reflected = vec![];
+    for (index, field) in fields.iter().enumerate() {
+        if is_skipped(field)? {
+            continue;
+        }
+
+        let ty = &field.ty;
+        let (name, member) = match (&field.ident, fields) {
+            (Some(ident), Fields::Named(_)) => (ident.to_string(), quote! { #ident }),
+            _ => {
+                let index = syn::Index::from(index);
+                (index.index.to_string(), quote! { #index })
+            }
+        };
+
+        reflected.push(ReflectedField {
+            name,
+            member,
+            type_name: quote! { #ty }.to_string().replace(' ', ""),
+        });
+    }
+
+    Ok(reflected)
+}
+
+/// Returns whether the field is marked with `#[reflect(skip)]`.
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skipped = false;
+    for attr in field.attrs.iter().filter(|a| a.path.is_ident("reflect")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => {
+                for nested in list.nested.iter() {
+                    match nested {
+                        NestedMeta::Meta(Meta::Path(path)) if path.is_ident("skip") => {
+                            skipped = true;
+                        }
+                        _ => {
+                            return Err(syn::Error::new_spanned(
+                                nested,
+                                "Unknown reflect attribute. Expected `skip`",
+                            ))
+                        }
+                    }
+                }
+            }
+            meta => return Err(syn::Error::new_spanned(meta, "Expected `#[reflect(skip)]`")),
+        }
+    }
+
+    Ok(skipped)
+}
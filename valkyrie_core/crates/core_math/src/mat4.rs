@@ -1,5 +1,5 @@
 use crate::Vec3;
-use cgmath::{Vector3, Zero};
+use cgmath::{SquareMatrix, Transform, Zero};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Mat4 {
@@ -19,6 +19,45 @@ impl Mat4 {
         vm
     }
 
+    /// Returns the identity matrix.
+    pub fn identity() -> Self {
+        Self {
+            mat: cgmath::Matrix4::identity(),
+        }
+    }
+
+    /// Creates a matrix that translates by the given vector.
+    pub fn from_translation(translation: Vec3) -> Self {
+        Self {
+            mat: cgmath::Matrix4::from_translation(translation.into()),
+        }
+    }
+
+    /// Creates a matrix that scales uniformly.
+    pub fn from_scale(scale: f32) -> Self {
+        Self {
+            mat: cgmath::Matrix4::from_scale(scale),
+        }
+    }
+
+    /// Creates a matrix that rotates around the y axis by the given radians.
+    pub fn from_angle_y(radians: f32) -> Self {
+        Self {
+            mat: cgmath::Matrix4::from_angle_y(cgmath::Rad(radians)),
+        }
+    }
+
+    /// Returns the translation component of the matrix.
+    pub fn translation(&self) -> Vec3 {
+        Vec3::new(self.mat.w.x, self.mat.w.y, self.mat.w.z)
+    }
+
+    /// Transforms the given point by the matrix.
+    pub fn transform_point(&self, point: Vec3) -> Vec3 {
+        let p = self.mat.transform_point(point.into());
+        Vec3::new(p.x, p.y, p.z)
+    }
+
     pub fn as_slice(&self) -> &[f32] {
         let r: &[f32; 16] = self.mat.as_ref();
         r
@@ -32,3 +71,13 @@ impl Default for Mat4 {
         }
     }
 }
+
+impl std::ops::Mul for Mat4 {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self::Output {
+        Self {
+            mat: self.mat * rhs.mat,
+        }
+    }
+}
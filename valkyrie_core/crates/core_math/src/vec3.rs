@@ -33,6 +33,15 @@ impl Vec3 {
             z: 1.,
         }
     }
+
+    /// Linearly interpolates between this and the other vector. `t` of 0.0 returns this, 1.0 returns the other.
+    pub fn lerp(self, other: Self, t: Num) -> Self {
+        Self {
+            x: self.x + (other.x - self.x) * t,
+            y: self.y + (other.y - self.y) * t,
+            z: self.z + (other.z - self.z) * t,
+        }
+    }
 }
 
 impl Into<(f32, f32, f32)> for Vec3 {
@@ -0,0 +1,84 @@
+/// A fixed size history of per frame checksums. Used to compare two peers or two replays and find where they desynced.
+/// Only the most recent `capacity` frames are kept.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChecksumHistory {
+    /// (frame, checksum) pairs, indexed by frame % capacity.
+    entries: Vec<Option<(u64, u64)>>,
+}
+
+impl ChecksumHistory {
+    /// Creates a new history that keeps the last `capacity` frames.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: vec![None; capacity.max(1)],
+        }
+    }
+
+    /// Records the checksum for the given frame, replacing the oldest frame if full.
+    pub fn record(&mut self, frame: u64, checksum: u64) {
+        let index = self.index(frame);
+        self.entries[index] = Some((frame, checksum));
+    }
+
+    /// Removes all recorded checksums.
+    pub fn clear(&mut self) {
+        for entry in self.entries.iter_mut() {
+            *entry = None;
+        }
+    }
+
+    /// Returns the checksum for the given frame, if it is still kept.
+    pub fn get(&self, frame: u64) -> Option<u64> {
+        match self.entries[self.index(frame)] {
+            Some((f, checksum)) if f == frame => Some(checksum),
+            _ => None,
+        }
+    }
+
+    /// Returns the first frame both histories have a checksum for, where the checksums differ.
+    pub fn first_divergence(&self, other: &Self) -> Option<u64> {
+        self.entries
+            .iter()
+            .filter_map(|entry| *entry)
+            .filter(|(frame, checksum)| other.get(*frame).is_some_and(|c| c != *checksum))
+            .map(|(frame, _)| frame)
+            .min()
+    }
+
+    fn index(&self, frame: u64) -> usize {
+        (frame % self.entries.len() as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksum_history_keeps_recent_frames() {
+        let mut history = ChecksumHistory::new(3);
+        for frame in 1..=4 {
+            history.record(frame, frame * 10);
+        }
+
+        assert_eq!(None, history.get(1));
+        assert_eq!(Some(20), history.get(2));
+        assert_eq!(Some(40), history.get(4));
+        assert_eq!(None, history.get(5));
+    }
+
+    #[test]
+    fn checksum_history_first_divergence() {
+        let mut a = ChecksumHistory::new(8);
+        let mut b = ChecksumHistory::new(4);
+        for frame in 1..=8 {
+            a.record(frame, frame);
+            b.record(frame, if frame >= 6 { 0 } else { frame });
+        }
+
+        // Only frames both histories still have are compared
+        assert_eq!(Some(6), a.first_divergence(&b));
+        assert_eq!(Some(6), b.first_divergence(&a));
+        assert_eq!(None, a.first_divergence(&a.clone()));
+    }
+}
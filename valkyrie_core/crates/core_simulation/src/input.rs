@@ -0,0 +1,239 @@
+// Platform independent input types. Window backends translate their events into these, so simulations never
+// depend on a windowing library. Keys carry both the layout dependent key and the physical scancode.
+// Positions + deltas are in physical pixels unless noted.
+
+/// A key press or release.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct KeyboardMsg {
+    /// The key, based on the keyboard layout. `Key::Unknown` if the platform couldn't map it.
+    pub key: Key,
+    /// The platform specific code for the physical key, regardless of layout.
+    pub scancode: u32,
+    /// The modifiers held when the key was pressed or released.
+    pub modifiers: Modifiers,
+}
+
+/// The modifier keys that are held.
+#[derive(Copy, Clone, Debug, PartialEq, Default)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+    /// The Windows, Command or Super key.
+    pub logo: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum Key {
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    Num0,
+
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+
+    PrintScreen,
+    ScrollLock,
+    Pause,
+
+    Insert,
+    Home,
+    Delete,
+    End,
+    PageDown,
+    PageUp,
+
+    Left,
+    Up,
+    Right,
+    Down,
+
+    Backspace,
+    Enter,
+    Space,
+    Tab,
+    CapsLock,
+
+    NumLock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadSubtract,
+    NumpadMultiply,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadComma,
+    NumpadEnter,
+    NumpadEquals,
+
+    Apostrophe,
+    Backslash,
+    Comma,
+    Equals,
+    Grave,
+    LBracket,
+    Minus,
+    Period,
+    RBracket,
+    Semicolon,
+    Slash,
+
+    LAlt,
+    LControl,
+    LShift,
+    LLogo,
+    RAlt,
+    RControl,
+    RShift,
+    RLogo,
+    Menu,
+
+    Mute,
+    VolumeDown,
+    VolumeUp,
+    PlayPause,
+    MediaStop,
+    NextTrack,
+    PrevTrack,
+
+    Copy,
+    Cut,
+    Paste,
+
+    Unknown,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+/// How far the mouse wheel scrolled.
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum MouseScroll {
+    /// Scrolled by lines or rows, such as a mouse wheel.
+    Lines { x: f32, y: f32 },
+    /// Scrolled by pixels, such as a touchpad.
+    Pixels { x: f32, y: f32 },
+}
+
+/// Identifies a connected gamepad. Ids may be reused after a gamepad is disconnected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct GamepadId(pub u32);
+
+/// Gamepad buttons, named by their position on the pad.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum GamepadButton {
+    /// A on Xbox pads, Cross on PlayStation pads.
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    LeftTrigger,
+    RightBumper,
+    RightTrigger,
+    Select,
+    Start,
+    /// The Xbox, PS or Home button.
+    Mode,
+    LeftStick,
+    RightStick,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+    Unknown,
+}
+
+/// Gamepad axes. Sticks range from -1.0 to 1.0, with up and right being positive.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    /// Analog left trigger, from 0.0 to 1.0.
+    LeftTrigger,
+    /// Analog right trigger, from 0.0 to 1.0.
+    RightTrigger,
+    DPadX,
+    DPadY,
+    Unknown,
+}
@@ -1,9 +1,29 @@
+use std::any::Any;
 use std::marker::PhantomData;
 
+mod checksum;
+mod input;
+mod replay;
+mod rollback;
+pub use checksum::ChecksumHistory;
+pub use input::{
+    GamepadAxis, GamepadButton, GamepadId, Key, KeyboardMsg, Modifiers, MouseButton, MouseScroll,
+};
+#[cfg(feature = "serialize")]
+pub use replay::REPLAY_VERSION;
+pub use replay::{Replay, ReplayError};
+pub use rollback::{
+    InputPacket, RollbackChannel, RollbackConfig, RollbackExecutor, RollbackStats, SaveState,
+};
+
 use core_data_structures::queue::Queue;
 use core_timing::{hz_to_duration, Stopwatch};
 
-pub use core_timing::Duration;
+pub use core_timing::{Clock, Duration, ManualClock, RealClock};
+
+/// In the event that the loop gets in a spiral of death where the sim can't keep up,
+/// clamp it to a set number of ticks per frame to prevent spiraling downward.
+pub const MAX_TICKS_PER_FRAME: u8 = 10;
 
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum ValkErr {}
@@ -16,26 +36,63 @@ pub enum ControlMessage {
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub enum Input<UserMsg> {
     UserMsg(UserMsg),
     WindowMsg(WindowMsg),
 }
 
 #[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
 pub enum WindowMsg {
     Shutdown,
     RedrawRequested,
-    Resize { w: u32, h: u32 },
+    Resize {
+        w: u32,
+        h: u32,
+    },
+    /// The window gained focus.
+    Focused,
+    /// The window lost focus. Keys + buttons held will not send releases, so treat them as released.
+    Unfocused,
+
     KeyPress(KeyboardMsg),
     KeyRelease(KeyboardMsg),
-}
+    ModifiersChanged(Modifiers),
+    /// A character was typed, after the keyboard layout + IME were applied. Use for text entry instead of keys.
+    Text(char),
 
-#[derive(Copy, Clone, Debug, PartialEq)]
-pub enum KeyboardMsg {
-    W,
-    A,
-    S,
-    D,
+    /// The cursor moved to the given position in the window.
+    MouseMove {
+        x: f32,
+        y: f32,
+    },
+    /// Raw mouse movement, not bound by the window or cursor acceleration. Use for camera controls.
+    MouseMotion {
+        dx: f32,
+        dy: f32,
+    },
+    MousePress(MouseButton),
+    MouseRelease(MouseButton),
+    MouseWheel(MouseScroll),
+    MouseEntered,
+    MouseLeft,
+
+    GamepadConnected(GamepadId),
+    GamepadDisconnected(GamepadId),
+    GamepadPress {
+        gamepad: GamepadId,
+        button: GamepadButton,
+    },
+    GamepadRelease {
+        gamepad: GamepadId,
+        button: GamepadButton,
+    },
+    GamepadAxis {
+        gamepad: GamepadId,
+        axis: GamepadAxis,
+        value: f32,
+    },
 }
 
 /// Common functionality a simulation must implement.
@@ -45,22 +102,58 @@ pub trait Simulation<Cfg, Msg> {
 
     /// A single 'tick' for an application.
     fn tick(&mut self, delta_t: Duration, messages: &[Input<Msg>]) -> ControlMessage;
+
+    /// Returns a checksum of the simulation state, used to detect desyncs. Simulations that don't support it return `None`.
+    fn checksum(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// A simulation that keeps the state needed to render between ticks, such as positions.
+/// Unlike `SaveState` this doesn't need to be the full state, as it is never restored.
+pub trait Interpolate {
+    type RenderState: Default + 'static;
+
+    /// Saves the state to render into `state`, reusing its buffers where possible.
+    fn save_render_state(&self, state: &mut Self::RenderState);
 }
 
 /// Executor for simulation. Handles timestepping.
-pub struct SimulationExecutor<Sim, Cfg, Msg>
+/// Time is read from the given clock; a `ManualClock` allows stepping it deterministically, such as when headless.
+pub struct SimulationExecutor<Sim, Cfg, Msg, C = RealClock>
 where
     Sim: Simulation<Cfg, Msg>,
+    C: Clock,
 {
     use_fixed_timestep: bool,
-    time_keeper: Timekeeper,
+    time_keeper: Timekeeper<C>,
     sim: Sim,
     engine_queue: Queue<Input<Msg>>,
     cfg_phantom: PhantomData<Cfg>,
     frame: u64,
+    checksums: Option<ChecksumHistory>,
+    recording: Option<Replay<Msg>>,
+    playback: Option<Replay<Msg>>,
+    interpolation: Option<Interpolation<Sim>>,
+}
+
+/// The simulation's state before the last tick, kept so rendering can interpolate between ticks.
+/// Type erased so only simulations that render need to implement `Interpolate`.
+struct Interpolation<Sim> {
+    previous: Box<dyn Any>,
+    save: fn(&Sim, &mut dyn Any),
+}
+
+fn save_previous<Sim>(sim: &Sim, previous: &mut dyn Any)
+where
+    Sim: Interpolate,
+{
+    if let Some(previous) = previous.downcast_mut::<Sim::RenderState>() {
+        sim.save_render_state(previous);
+    }
 }
 
-impl<Sim, Cfg, Msg> SimulationExecutor<Sim, Cfg, Msg>
+impl<Sim, Cfg, Msg> SimulationExecutor<Sim, Cfg, Msg, RealClock>
 where
     Sim: Simulation<Cfg, Msg>,
 {
@@ -70,6 +163,29 @@ where
         sim_hz: Option<u32>,
         use_fixed_timestep: bool,
         config: Cfg,
+    ) -> Self {
+        Self::with_clock(
+            max_engine_msgs,
+            sim_hz,
+            use_fixed_timestep,
+            config,
+            RealClock::new(),
+        )
+    }
+}
+
+impl<Sim, Cfg, Msg, C> SimulationExecutor<Sim, Cfg, Msg, C>
+where
+    Sim: Simulation<Cfg, Msg>,
+    C: Clock,
+{
+    /// Creates a new SimulationExecutor that reads time from the given clock.
+    pub fn with_clock(
+        max_engine_msgs: usize,
+        sim_hz: Option<u32>,
+        use_fixed_timestep: bool,
+        config: Cfg,
+        clock: C,
     ) -> Self {
         let sim_hz = match sim_hz {
             Some(hz) => hz.max(1),
@@ -79,7 +195,7 @@ where
         let time_keeper = Timekeeper {
             tick_duration: hz_to_duration(sim_hz),
             accumulated_time: Duration::from_secs(0),
-            simulation_stopwatch: Stopwatch::new(),
+            simulation_stopwatch: Stopwatch::with_clock(clock),
         };
 
         let sim = Sim::new(config);
@@ -91,6 +207,10 @@ where
             engine_queue: Queue::new(max_engine_msgs),
             cfg_phantom: PhantomData,
             frame: 0,
+            checksums: None,
+            recording: None,
+            playback: None,
+            interpolation: None,
         }
     }
 
@@ -98,18 +218,154 @@ where
         &self.sim
     }
 
+    /// Returns the clock the executor reads time from.
+    pub fn clock(&self) -> &C {
+        self.time_keeper.simulation_stopwatch.clock()
+    }
+
     /// Returns the last updated frame. Will wrap to 0 when it reaches the max value.
     pub fn last_updated_frame(&self) -> u64 {
         self.frame
     }
 
+    /// Starts recording the simulation's checksum after each frame, keeping the last `capacity` frames.
+    pub fn record_checksums(&mut self, capacity: usize) {
+        self.checksums = Some(ChecksumHistory::new(capacity));
+    }
+
+    /// Returns the recorded checksums, if recording.
+    pub fn checksums(&self) -> Option<&ChecksumHistory> {
+        self.checksums.as_ref()
+    }
+
+    /// Returns how far between the last tick and the next one the executor is, from 0.0 to 1.0.
+    /// Used to interpolate rendering between the previous and current state. Always 1.0 when not using a fixed timestep.
+    pub fn alpha(&self) -> f32 {
+        if !self.use_fixed_timestep {
+            return 1.0;
+        }
+
+        let alpha = self.time_keeper.accumulated_time.as_secs_f32()
+            / self.time_keeper.tick_duration.as_secs_f32();
+        alpha.min(1.0)
+    }
+
+    /// Recreates the simulation from the config and returns to frame 0. Queued inputs, recorded checksums and any
+    /// replay being recorded or played back are dropped.
+    pub fn reset(&mut self, config: Cfg) {
+        self.sim = Sim::new(config);
+        self.frame = 0;
+        self.time_keeper.accumulated_time = Duration::from_secs(0);
+        self.engine_queue.clear();
+        self.recording = None;
+        self.playback = None;
+
+        if let Some(checksums) = self.checksums.as_mut() {
+            checksums.clear();
+        }
+
+        if let Some(interpolation) = self.interpolation.as_mut() {
+            (interpolation.save)(&self.sim, interpolation.previous.as_mut());
+        }
+    }
+
+    /// Restarts the simulation from the config, then records every input it receives.
+    /// Replays always start from a new simulation, so they can be played back without saving its state.
+    pub fn start_recording(&mut self, config: Cfg) {
+        self.reset(config);
+        self.recording = Some(Replay::new(self.frame));
+    }
+
+    /// Stops recording, returning the replay if one was being recorded.
+    pub fn stop_recording(&mut self) -> Option<Replay<Msg>> {
+        self.recording.take()
+    }
+
+    /// Restarts the simulation from the config the replay was recorded with, then plays it back starting with the next frame.
+    /// While playing back, inputs passed to `tick()` are dropped.
+    pub fn start_playback(&mut self, replay: Replay<Msg>, config: Cfg) -> Result<(), ReplayError> {
+        self.check_replay_start(&replay)?;
+        self.reset(config);
+        self.playback = Some(replay);
+        Ok(())
+    }
+
+    /// Returns whether a replay is being played back.
+    pub fn is_playing_back(&self) -> bool {
+        self.playback.is_some()
+    }
+
+    /// Restarts the simulation from the config the replay was recorded with, then immediately ticks it through every
+    /// frame of the replay, ignoring the timestep. Used to verify a replay drives the simulation to the same result,
+    /// such as in tests.
+    pub fn replay_uncapped(
+        &mut self,
+        replay: &Replay<Msg>,
+        config: Cfg,
+    ) -> Result<ControlMessage, ReplayError> {
+        self.check_replay_start(replay)?;
+        self.reset(config);
+
+        let mut control_msg = ControlMessage::Ok;
+        while self.frame < replay.end_frame() && control_msg != ControlMessage::ExitSim {
+            self.frame = self.frame.wrapping_add(1);
+            if let Some((delta_t, inputs)) = replay.frame(self.frame) {
+                control_msg = self.sim.tick(delta_t, inputs);
+                self.record_checksum();
+            }
+        }
+
+        Ok(control_msg)
+    }
+
+    /// Starts keeping the simulation's state from before the last tick, so it can be interpolated when rendering.
+    pub fn enable_interpolation(&mut self)
+    where
+        Sim: Interpolate,
+    {
+        let mut previous = Sim::RenderState::default();
+        self.sim.save_render_state(&mut previous);
+
+        self.interpolation = Some(Interpolation {
+            previous: Box::new(previous),
+            save: save_previous::<Sim>,
+        });
+    }
+
+    /// Returns the simulation's state before the last tick, if interpolation is enabled.
+    pub fn previous_state(&self) -> Option<&Sim::RenderState>
+    where
+        Sim: Interpolate,
+    {
+        self.interpolation
+            .as_ref()
+            .and_then(|interpolation| interpolation.previous.downcast_ref())
+    }
+
+    /// Replays are played back from a new simulation, so they must start on the first frame.
+    fn check_replay_start(&self, replay: &Replay<Msg>) -> Result<(), ReplayError> {
+        if replay.start_frame() != 0 {
+            return Err(ReplayError::WrongStartFrame {
+                expected: 0,
+                actual: replay.start_frame(),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Queues the input message, passing it in on the next tick.
+    pub fn queue(&mut self, input: Input<Msg>) {
+        self.engine_queue.push(input);
+    }
+
     /// Passes in the input message and attempts to execute.
     pub fn tick(&mut self, input: Option<Input<Msg>>) -> ControlMessage {
         let mut control_msg = ControlMessage::Ok;
 
         // Queue up any messages
         if let Some(input) = input {
-            self.engine_queue.push(input);
+            self.queue(input);
         }
 
         // If we're using a fixed time step, see if it should be executed.
@@ -119,21 +375,15 @@ where
 
             self.time_keeper.accumulated_time += self.time_keeper.simulation_stopwatch.elapsed();
 
-            // In the event that the loop gets in a spiral of death where the sim can't keep up,
-            // clamp it to a set number of ticks per frame to prevent spiraling downward.
-            const MAX_TICKS_PER_FRAME: u8 = 10;
             let mut times_ticked = 0;
 
             // Tick the simulation until it has caught up
-            while self.time_keeper.accumulated_time > self.time_keeper.tick_duration {
+            while self.time_keeper.accumulated_time >= self.time_keeper.tick_duration {
                 self.frame = self.frame.wrapping_add(1);
                 self.time_keeper.accumulated_time -= self.time_keeper.tick_duration;
                 times_ticked += 1;
 
-                control_msg = self
-                    .sim
-                    .tick(self.time_keeper.tick_duration, &self.engine_queue.items());
-                self.engine_queue.clear();
+                control_msg = self.tick_sim(self.time_keeper.tick_duration);
 
                 // Break out if the sim is taking too long. Or it should shut down.
                 // This way it keeps processing and doesn't get stuck in a horrendous loop. It'll slow the game down
@@ -147,18 +397,257 @@ where
         else {
             self.frame = self.frame.wrapping_add(1);
             let delta_t = self.time_keeper.simulation_stopwatch.elapsed();
-            control_msg = self.sim.tick(delta_t, &self.engine_queue.items());
-            self.engine_queue.clear();
+            control_msg = self.tick_sim(delta_t);
         }
 
         // Return the control message
         control_msg
     }
+
+    /// Saves the previous state if interpolating, ticks the simulation with all queued messages, then records its checksum.
+    /// When playing back a replay, the recorded delta + inputs are used instead.
+    fn tick_sim(&mut self, delta_t: Duration) -> ControlMessage {
+        if let Some(interpolation) = self.interpolation.as_mut() {
+            (interpolation.save)(&self.sim, interpolation.previous.as_mut());
+        }
+
+        let frame = self.frame;
+        let recorded = self
+            .playback
+            .as_ref()
+            .and_then(|replay| replay.frame(frame));
+
+        let control_msg = match recorded {
+            Some((delta_t, inputs)) => {
+                let control_msg = self.sim.tick(delta_t, inputs);
+                self.engine_queue.clear();
+                control_msg
+            }
+            None => {
+                self.playback = None;
+
+                let control_msg = self.sim.tick(delta_t, self.engine_queue.items());
+                match self.recording.as_mut() {
+                    Some(replay) => replay.record_frame(delta_t, self.engine_queue.drain()),
+                    None => self.engine_queue.clear(),
+                }
+                control_msg
+            }
+        };
+
+        if self
+            .playback
+            .as_ref()
+            .is_some_and(|replay| self.frame >= replay.end_frame())
+        {
+            self.playback = None;
+        }
+
+        self.record_checksum();
+
+        control_msg
+    }
+
+    fn record_checksum(&mut self) {
+        if let Some(checksums) = self.checksums.as_mut() {
+            if let Some(checksum) = self.sim.checksum() {
+                checksums.record(self.frame, checksum);
+            }
+        }
+    }
 }
 
 /// Time tracking record manager
-struct Timekeeper {
+struct Timekeeper<C> {
     tick_duration: Duration,
     accumulated_time: Duration,
-    simulation_stopwatch: Stopwatch,
+    simulation_stopwatch: Stopwatch<C>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sums all messages it has been sent.
+    struct Sum(u64);
+    impl Simulation<u64, u64> for Sum {
+        fn new(config: u64) -> Self {
+            Self(config)
+        }
+
+        fn tick(&mut self, _delta_t: Duration, messages: &[Input<u64>]) -> ControlMessage {
+            for message in messages {
+                if let Input::UserMsg(value) = message {
+                    self.0 += value;
+                }
+            }
+            ControlMessage::Ok
+        }
+
+        fn checksum(&self) -> Option<u64> {
+            Some(self.0)
+        }
+    }
+
+    #[test]
+    fn simulation_executor_finds_first_divergent_frame() {
+        let mut a = SimulationExecutor::<Sum, u64, u64>::new(4, Some(60), false, 0);
+        let mut b = SimulationExecutor::<Sum, u64, u64>::new(4, Some(60), false, 0);
+        a.record_checksums(16);
+        b.record_checksums(16);
+
+        for frame in 1..=5 {
+            a.tick(Some(Input::UserMsg(1)));
+            b.tick(Some(Input::UserMsg(if frame == 3 { 2 } else { 1 })));
+        }
+
+        let (a, b) = (a.checksums().unwrap(), b.checksums().unwrap());
+        assert_eq!(Some(2), a.get(2));
+        assert_eq!(b.get(2), a.get(2));
+        assert_eq!(Some(3), a.first_divergence(b));
+    }
+
+    #[test]
+    fn simulation_executor_fixed_timestep_manual_clock() {
+        let clock = ManualClock::new();
+        let tick_duration = hz_to_duration(60);
+        let mut executor =
+            SimulationExecutor::<Sum, u64, u64, _>::with_clock(4, Some(60), true, 0, clock.clone());
+
+        // Not enough time has passed to tick
+        clock.advance(tick_duration / 2);
+        executor.tick(Some(Input::UserMsg(1)));
+        assert_eq!(0, executor.last_updated_frame());
+        assert_eq!(0, executor.sim().0);
+
+        // Queued inputs are passed in on the next tick
+        clock.advance(tick_duration / 2);
+        executor.tick(Some(Input::UserMsg(2)));
+        assert_eq!(1, executor.last_updated_frame());
+        assert_eq!(3, executor.sim().0);
+
+        clock.advance(tick_duration * 3);
+        executor.tick(None);
+        assert_eq!(4, executor.last_updated_frame());
+    }
+
+    #[test]
+    fn simulation_executor_clamps_ticks_per_frame() {
+        let clock = ManualClock::new();
+        let tick_duration = hz_to_duration(60);
+        let mut executor =
+            SimulationExecutor::<Sum, u64, u64, _>::with_clock(4, Some(60), true, 0, clock.clone());
+
+        // Falling far behind only ticks the max amount per frame, then catches up on later frames
+        clock.advance(tick_duration * 25);
+        executor.tick(None);
+        assert_eq!(MAX_TICKS_PER_FRAME as u64, executor.last_updated_frame());
+        executor.tick(None);
+        assert_eq!(
+            2 * MAX_TICKS_PER_FRAME as u64,
+            executor.last_updated_frame()
+        );
+        executor.tick(None);
+        assert_eq!(25, executor.last_updated_frame());
+        executor.tick(None);
+        assert_eq!(25, executor.last_updated_frame());
+    }
+
+    impl Interpolate for Sum {
+        type RenderState = u64;
+
+        fn save_render_state(&self, state: &mut Self::RenderState) {
+            *state = self.0;
+        }
+    }
+
+    #[test]
+    fn simulation_executor_interpolation() {
+        let clock = ManualClock::new();
+        let tick_duration = hz_to_duration(60);
+        let mut executor =
+            SimulationExecutor::<Sum, u64, u64, _>::with_clock(4, Some(60), true, 0, clock.clone());
+        assert_eq!(None, executor.previous_state());
+        executor.enable_interpolation();
+        assert_eq!(Some(&0), executor.previous_state());
+
+        clock.advance(tick_duration);
+        executor.tick(Some(Input::UserMsg(5)));
+        assert_eq!(Some(&0), executor.previous_state());
+        assert_eq!(5, executor.sim().0);
+        assert_eq!(0.0, executor.alpha());
+
+        clock.advance(tick_duration / 4);
+        executor.tick(Some(Input::UserMsg(2)));
+        assert!((executor.alpha() - 0.25).abs() < 0.001);
+
+        // Only the state from before the last tick is kept
+        clock.advance(tick_duration * 2);
+        executor.tick(None);
+        assert_eq!(Some(&7), executor.previous_state());
+        assert!((executor.alpha() - 0.25).abs() < 0.001);
+    }
+
+    #[test]
+    fn simulation_executor_replays_recorded_inputs() {
+        let mut recorded = SimulationExecutor::<Sum, u64, u64>::new(4, Some(60), false, 0);
+        recorded.record_checksums(16);
+        recorded.tick(Some(Input::UserMsg(100)));
+
+        // Recording restarts the sim, so nothing before it is needed to play it back
+        recorded.start_recording(100);
+        assert_eq!(0, recorded.last_updated_frame());
+        assert_eq!(100, recorded.sim().0);
+        for value in 1..=6 {
+            let input = if value % 2 == 0 {
+                None
+            } else {
+                Some(Input::UserMsg(value))
+            };
+            recorded.tick(input);
+        }
+        let replay = recorded.stop_recording().unwrap();
+        assert_eq!(0, replay.start_frame());
+        assert_eq!(6, replay.end_frame());
+
+        // The fixed timestep can be skipped to replay at uncapped speed
+        let mut uncapped = SimulationExecutor::<Sum, u64, u64>::new(4, Some(60), true, 5);
+        uncapped.record_checksums(16);
+        uncapped.tick(Some(Input::UserMsg(7)));
+        assert_eq!(
+            ControlMessage::Ok,
+            uncapped.replay_uncapped(&replay, 100).unwrap()
+        );
+        assert_eq!(recorded.sim().0, uncapped.sim().0);
+        assert_eq!(6, uncapped.last_updated_frame());
+        assert_eq!(
+            None,
+            recorded
+                .checksums()
+                .unwrap()
+                .first_divergence(uncapped.checksums().unwrap())
+        );
+
+        // Live input is dropped during playback
+        let mut playback = SimulationExecutor::<Sum, u64, u64>::new(4, Some(60), false, 5);
+        playback.tick(Some(Input::UserMsg(7)));
+        playback.start_playback(replay, 100).unwrap();
+        for _ in 0..6 {
+            assert!(playback.is_playing_back());
+            playback.tick(Some(Input::UserMsg(1000)));
+        }
+        assert!(!playback.is_playing_back());
+        assert_eq!(recorded.sim().0, playback.sim().0);
+
+        // Replays that didn't start from a new simulation can't be played back
+        let mut partial = Replay::new(3);
+        partial.record_frame(Duration::from_millis(16), vec![Input::UserMsg(1)]);
+        assert!(matches!(
+            playback.start_playback(partial, 100),
+            Err(ReplayError::WrongStartFrame {
+                expected: 0,
+                actual: 3
+            })
+        ));
+    }
 }
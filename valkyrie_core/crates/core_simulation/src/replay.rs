@@ -0,0 +1,193 @@
+// Recordings of every input given to a simulation, so a session can be played back exactly.
+// Each frame stores the delta it was ticked with + the inputs the sim received, so replays are deterministic
+// with either timestep. Replays start from a newly created simulation, so no state needs to be saved with them.
+// With the `serialize` feature, replays can be saved to a versioned binary file.
+use crate::{Duration, Input};
+
+/// The current version of the replay format. Bumped whenever the layout changes.
+#[cfg(feature = "serialize")]
+pub const REPLAY_VERSION: u32 = 2;
+
+/// Written at the start of replay files to detect invalid data.
+#[cfg(feature = "serialize")]
+const REPLAY_MAGIC: &[u8; 4] = b"VKRP";
+
+#[derive(Debug)]
+pub enum ReplayError {
+    /// The replay didn't start from a newly created simulation.
+    WrongStartFrame {
+        expected: u64,
+        actual: u64,
+    },
+    InvalidHeader,
+    UnsupportedVersion(u32),
+    Binary(String),
+    Io(String),
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+struct ReplayFrame {
+    delta_t: Duration,
+    /// The index of the frame's first input.
+    first_input: usize,
+}
+
+/// A recording of a simulation's inputs.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serialize", derive(serde::Serialize, serde::Deserialize))]
+pub struct Replay<Msg> {
+    start_frame: u64,
+    frames: Vec<ReplayFrame>,
+    inputs: Vec<Input<Msg>>,
+}
+
+impl<Msg> Replay<Msg> {
+    /// Creates a new replay, starting after the given frame.
+    pub fn new(start_frame: u64) -> Self {
+        Self {
+            start_frame,
+            frames: vec![],
+            inputs: vec![],
+        }
+    }
+
+    /// Returns the frame the replay starts after.
+    pub fn start_frame(&self) -> u64 {
+        self.start_frame
+    }
+
+    /// Returns the last frame in the replay.
+    pub fn end_frame(&self) -> u64 {
+        self.start_frame + self.frames.len() as u64
+    }
+
+    /// Returns the delta + inputs for the given frame.
+    pub fn frame(&self, frame: u64) -> Option<(Duration, &[Input<Msg>])> {
+        if frame <= self.start_frame {
+            return None;
+        }
+
+        let index = (frame - self.start_frame - 1) as usize;
+        let current = self.frames.get(index)?;
+        let end = match self.frames.get(index + 1) {
+            Some(next) => next.first_input,
+            None => self.inputs.len(),
+        };
+
+        Some((current.delta_t, &self.inputs[current.first_input..end]))
+    }
+
+    /// Records the next frame.
+    pub fn record_frame<I>(&mut self, delta_t: Duration, inputs: I)
+    where
+        I: IntoIterator<Item = Input<Msg>>,
+    {
+        self.frames.push(ReplayFrame {
+            delta_t,
+            first_input: self.inputs.len(),
+        });
+        self.inputs.extend(inputs);
+    }
+}
+
+#[cfg(feature = "serialize")]
+impl<Msg> Replay<Msg>
+where
+    Msg: serde::Serialize + serde::de::DeserializeOwned,
+{
+    /// Saves the replay to the binary format.
+    pub fn to_bytes(&self) -> Result<Vec<u8>, ReplayError> {
+        let mut bytes = REPLAY_MAGIC.to_vec();
+        bytes.extend_from_slice(&REPLAY_VERSION.to_le_bytes());
+        bincode::serialize_into(&mut bytes, self)
+            .map_err(|e| ReplayError::Binary(e.to_string()))?;
+
+        Ok(bytes)
+    }
+
+    /// Loads a replay from the binary format.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ReplayError> {
+        if bytes.len() < 8 || &bytes[0..4] != REPLAY_MAGIC {
+            return Err(ReplayError::InvalidHeader);
+        }
+
+        let version = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]);
+        if version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(version));
+        }
+
+        bincode::deserialize(&bytes[8..]).map_err(|e| ReplayError::Binary(e.to_string()))
+    }
+
+    /// Writes the replay to a file.
+    pub fn save<P>(&self, path: P) -> Result<(), ReplayError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        std::fs::write(path, self.to_bytes()?).map_err(|e| ReplayError::Io(e.to_string()))
+    }
+
+    /// Reads a replay from a file.
+    pub fn load<P>(path: P) -> Result<Self, ReplayError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        let bytes = std::fs::read(path).map_err(|e| ReplayError::Io(e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replay_frames() {
+        let mut replay = Replay::<u8>::new(10);
+        let delta = Duration::from_millis(16);
+        replay.record_frame(delta, vec![Input::UserMsg(1), Input::UserMsg(2)]);
+        replay.record_frame(delta * 2, vec![]);
+        replay.record_frame(delta, vec![Input::UserMsg(3)]);
+
+        assert_eq!(13, replay.end_frame());
+        assert_eq!(None, replay.frame(10));
+        assert_eq!(
+            Some((delta, &[Input::UserMsg(1), Input::UserMsg(2)][..])),
+            replay.frame(11)
+        );
+        assert_eq!(Some((delta * 2, &[][..])), replay.frame(12));
+        assert_eq!(Some((delta, &[Input::UserMsg(3)][..])), replay.frame(13));
+        assert_eq!(None, replay.frame(14));
+    }
+
+    #[cfg(feature = "serialize")]
+    #[test]
+    fn replay_to_bytes() {
+        let mut replay = Replay::<u8>::new(0);
+        replay.record_frame(
+            Duration::from_millis(16),
+            vec![
+                Input::UserMsg(1),
+                Input::WindowMsg(crate::WindowMsg::KeyPress(crate::KeyboardMsg {
+                    key: crate::Key::W,
+                    scancode: 17,
+                    modifiers: crate::Modifiers::default(),
+                })),
+            ],
+        );
+        let bytes = replay.to_bytes().unwrap();
+        assert_eq!(replay, Replay::from_bytes(&bytes).unwrap());
+
+        assert!(matches!(
+            Replay::<u8>::from_bytes(&bytes[1..]),
+            Err(ReplayError::InvalidHeader)
+        ));
+        let mut future = bytes.clone();
+        future[4] = 3;
+        assert!(matches!(
+            Replay::<u8>::from_bytes(&future),
+            Err(ReplayError::UnsupportedVersion(3))
+        ));
+    }
+}
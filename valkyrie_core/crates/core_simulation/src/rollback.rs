@@ -0,0 +1,576 @@
+// GGPO style rollback for two player games.
+// Each frame every player gives exactly one input. Remote input that hasn't arrived yet is predicted by repeating the
+// last input received. The sim's state is saved before every frame, so when a remote input arrives that differs
+// from the prediction the sim is rewound to that frame and resimulated with the real input.
+// Local input is delayed by a few frames to hide latency, and peers wait for each other when one gets too far ahead.
+// Inputs are resent until acknowledged, so lost packets are recovered from later ones.
+// All buffers are allocated up front and sized by the config.
+use core_timing::hz_to_duration;
+
+use crate::{ControlMessage, Duration, Input, Simulation, MAX_TICKS_PER_FRAME};
+
+/// A simulation whose state can be saved + restored, so it can be rolled back.
+/// The saved state must be the full simulation state, or rollbacks will desync.
+pub trait SaveState {
+    type State: Default;
+
+    /// Saves the current state into `state`, reusing its buffers where possible.
+    fn save_state(&self, state: &mut Self::State);
+
+    /// Restores the state.
+    fn load_state(&mut self, state: &Self::State);
+}
+
+/// Inputs + timing sent between peers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InputPacket<Msg> {
+    /// The frame of the first input.
+    pub start_frame: u64,
+    /// The sender's inputs, starting at `start_frame`.
+    pub inputs: Vec<Msg>,
+    /// The sender has all of the receiver's inputs before this frame.
+    pub ack_frame: u64,
+    /// The sender's current frame.
+    pub frame: u64,
+    /// How many frames the sender thinks it is ahead of the receiver.
+    pub advantage: i64,
+}
+
+/// Sends + receives packets between peers. Packets may be lost or delayed.
+pub trait RollbackChannel<Msg> {
+    fn send(&mut self, packet: &InputPacket<Msg>);
+    fn receive(&mut self) -> Option<InputPacket<Msg>>;
+}
+
+/// Configuration for a rollback session.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RollbackConfig {
+    /// The index of the local player, either 0 or 1. Inputs are passed to the sim in player order.
+    pub local_player: usize,
+    /// How many frames local input is delayed by. Higher values mean fewer rollbacks, but less responsive controls.
+    pub input_delay: u64,
+    /// How many frames may be predicted before waiting on the remote player. Clamped so a rollback + the next frame
+    /// fit in `MAX_TICKS_PER_FRAME`.
+    pub max_prediction: u64,
+}
+
+impl Default for RollbackConfig {
+    fn default() -> Self {
+        Self {
+            local_player: 0,
+            input_delay: 2,
+            max_prediction: 8,
+        }
+    }
+}
+
+/// Counters for how the session is going.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RollbackStats {
+    pub rollbacks: u64,
+    pub resimulated_frames: u64,
+    /// Frames skipped waiting on the remote player.
+    pub waited_frames: u64,
+}
+
+/// Only skip a frame for time sync this often, so a single late packet doesn't cause stutter.
+const FRAME_SYNC_INTERVAL: u64 = 4;
+
+/// Fixed size buffer indexed by frame.
+struct FrameRing<T> {
+    items: Vec<T>,
+}
+
+impl<T> FrameRing<T> {
+    fn new(capacity: usize) -> Self
+    where
+        T: Default,
+    {
+        Self {
+            items: (0..capacity).map(|_| T::default()).collect(),
+        }
+    }
+
+    fn get(&self, frame: u64) -> &T {
+        &self.items[(frame % self.items.len() as u64) as usize]
+    }
+
+    fn get_mut(&mut self, frame: u64) -> &mut T {
+        let len = self.items.len() as u64;
+        &mut self.items[(frame % len) as usize]
+    }
+}
+
+/// Executor for a two player simulation, using rollback to hide latency.
+pub struct RollbackExecutor<Sim, Cfg, Msg, Chan>
+where
+    Sim: Simulation<Cfg, Msg> + SaveState,
+{
+    sim: Sim,
+    channel: Chan,
+    config: RollbackConfig,
+    tick_duration: Duration,
+    capacity: u64,
+
+    /// The next frame to simulate.
+    current_frame: u64,
+    /// The frame the next local input is for.
+    local_input_frame: u64,
+    local_inputs: FrameRing<Msg>,
+    /// Remote inputs received ahead of `remote_confirmed`.
+    remote_inputs: FrameRing<Option<(u64, Msg)>>,
+    /// All remote inputs before this frame are known.
+    remote_confirmed: u64,
+    last_remote_input: Msg,
+    /// The remote input each frame was simulated with.
+    predicted: FrameRing<Msg>,
+    /// The state at the start of each frame.
+    states: FrameRing<Sim::State>,
+    first_incorrect: Option<u64>,
+    /// The remote player has all local inputs before this frame.
+    remote_ack: u64,
+
+    remote_frame: u64,
+    local_advantage: i64,
+    remote_advantage: i64,
+    last_sync_frame: u64,
+
+    packet: InputPacket<Msg>,
+    stats: RollbackStats,
+    cfg_phantom: std::marker::PhantomData<Cfg>,
+}
+
+impl<Sim, Cfg, Msg, Chan> RollbackExecutor<Sim, Cfg, Msg, Chan>
+where
+    Sim: Simulation<Cfg, Msg> + SaveState,
+    Msg: Copy + PartialEq + Default,
+    Chan: RollbackChannel<Msg>,
+{
+    /// Creates a new executor, ticking the sim at `sim_hz`.
+    pub fn new(sim_hz: u32, config: RollbackConfig, sim_config: Cfg, channel: Chan) -> Self {
+        let mut config = config;
+        config.local_player = config.local_player.min(1);
+        config.max_prediction = config
+            .max_prediction
+            .clamp(1, MAX_TICKS_PER_FRAME as u64 - 1);
+
+        let capacity = 2 * (config.max_prediction + config.input_delay + 1);
+        let ring = capacity as usize;
+
+        Self {
+            sim: Sim::new(sim_config),
+            channel,
+            config,
+            tick_duration: hz_to_duration(sim_hz.max(1)),
+            capacity,
+            current_frame: 0,
+            // Frames before the input delay have no local input, so they're sent as defaults.
+            local_input_frame: config.input_delay,
+            local_inputs: FrameRing::new(ring),
+            remote_inputs: FrameRing::new(ring),
+            remote_confirmed: 0,
+            last_remote_input: Msg::default(),
+            predicted: FrameRing::new(ring),
+            states: FrameRing::new(ring),
+            first_incorrect: None,
+            remote_ack: 0,
+            remote_frame: 0,
+            local_advantage: 0,
+            remote_advantage: 0,
+            last_sync_frame: 0,
+            packet: InputPacket {
+                start_frame: 0,
+                inputs: Vec::with_capacity(ring),
+                ack_frame: 0,
+                frame: 0,
+                advantage: 0,
+            },
+            stats: RollbackStats::default(),
+            cfg_phantom: std::marker::PhantomData,
+        }
+    }
+
+    pub fn sim(&self) -> &Sim {
+        &self.sim
+    }
+
+    /// Returns the next frame that will be simulated.
+    pub fn current_frame(&self) -> u64 {
+        self.current_frame
+    }
+
+    /// Returns the frame all inputs are known up to. The sim's state for earlier frames won't be rolled back.
+    pub fn confirmed_frame(&self) -> u64 {
+        self.current_frame.min(self.remote_confirmed)
+    }
+
+    /// Returns how many frames this peer is ahead of the remote one. Negative if it is behind.
+    pub fn frame_advantage(&self) -> i64 {
+        self.local_advantage.saturating_sub(self.remote_advantage) / 2
+    }
+
+    pub fn stats(&self) -> RollbackStats {
+        self.stats
+    }
+
+    /// Handles incoming packets + rolls back if required, then simulates the next frame with the given local input.
+    /// If this peer has to wait on the remote player, the frame is skipped and the input is dropped.
+    pub fn tick(&mut self, local_input: Msg) -> ControlMessage {
+        let mut control_msg = self.receive_and_rollback();
+        if control_msg == ControlMessage::ExitSim {
+            return control_msg;
+        }
+
+        if self.should_wait() {
+            self.stats.waited_frames += 1;
+        } else {
+            *self.local_inputs.get_mut(self.local_input_frame) = local_input;
+            self.local_input_frame += 1;
+
+            control_msg = self.simulate(self.current_frame);
+            self.current_frame += 1;
+        }
+
+        self.send();
+        control_msg
+    }
+
+    /// Handles incoming packets + rolls back if required, without simulating a new frame.
+    pub fn poll(&mut self) -> ControlMessage {
+        let control_msg = self.receive_and_rollback();
+        self.send();
+        control_msg
+    }
+
+    fn receive_and_rollback(&mut self) -> ControlMessage {
+        while let Some(packet) = self.channel.receive() {
+            self.receive(packet);
+        }
+
+        let first_incorrect = match self.first_incorrect.take() {
+            Some(frame) => frame,
+            None => return ControlMessage::Ok,
+        };
+
+        self.stats.rollbacks += 1;
+        self.sim.load_state(self.states.get(first_incorrect));
+
+        let mut control_msg = ControlMessage::Ok;
+        for frame in first_incorrect..self.current_frame {
+            self.stats.resimulated_frames += 1;
+            if self.simulate(frame) == ControlMessage::ExitSim {
+                control_msg = ControlMessage::ExitSim;
+            }
+        }
+
+        control_msg
+    }
+
+    /// Whether this peer is too far ahead of the remote one to keep going.
+    fn should_wait(&mut self) -> bool {
+        // Can't predict any further, or unacknowledged inputs would be overwritten
+        if self.current_frame >= self.remote_confirmed + self.config.max_prediction
+            || self.local_input_frame + 1 - self.remote_ack >= self.capacity
+        {
+            return true;
+        }
+
+        // Let the remote player catch up
+        if self.frame_advantage() >= 1
+            && self.current_frame >= self.last_sync_frame + FRAME_SYNC_INTERVAL
+        {
+            self.last_sync_frame = self.current_frame;
+            return true;
+        }
+
+        false
+    }
+
+    /// Saves the state, then simulates the frame with the best known inputs.
+    fn simulate(&mut self, frame: u64) -> ControlMessage {
+        self.sim.save_state(self.states.get_mut(frame));
+
+        let local = *self.local_inputs.get(frame);
+        let remote = match frame < self.remote_confirmed {
+            true => self
+                .remote_inputs
+                .get(frame)
+                .map_or(self.last_remote_input, |(_, i)| i),
+            false => self.last_remote_input,
+        };
+        *self.predicted.get_mut(frame) = remote;
+
+        let inputs = match self.config.local_player {
+            0 => [Input::UserMsg(local), Input::UserMsg(remote)],
+            _ => [Input::UserMsg(remote), Input::UserMsg(local)],
+        };
+
+        self.sim.tick(self.tick_duration, &inputs)
+    }
+
+    /// Packets come from the network, so frames in them are clamped or checked rather than trusted.
+    fn receive(&mut self, packet: InputPacket<Msg>) {
+        // The remote player can't have inputs that haven't been made yet
+        let ack_frame = packet.ack_frame.min(self.local_input_frame);
+        self.remote_ack = self.remote_ack.max(ack_frame);
+
+        if packet.frame >= self.remote_frame {
+            self.remote_frame = packet.frame;
+            self.local_advantage = (self.current_frame as i64)
+                .saturating_sub(packet.frame.min(i64::MAX as u64) as i64);
+            self.remote_advantage = packet.advantage;
+        }
+
+        for (i, input) in packet.inputs.iter().enumerate() {
+            let frame = match packet.start_frame.checked_add(i as u64) {
+                Some(frame) => frame,
+                None => break,
+            };
+            // Inputs past the buffer are dropped, they'll be resent as they aren't acknowledged.
+            // The buffer starts at the oldest frame that could be rolled back to.
+            let oldest = self
+                .current_frame
+                .saturating_sub(self.config.max_prediction);
+            if frame >= self.remote_confirmed && frame < oldest + self.capacity {
+                *self.remote_inputs.get_mut(frame) = Some((frame, *input));
+            }
+        }
+
+        // Confirm everything that's now contiguous, checking it against what was predicted
+        while let Some((frame, input)) = *self.remote_inputs.get(self.remote_confirmed) {
+            if frame != self.remote_confirmed {
+                break;
+            }
+
+            if frame < self.current_frame && *self.predicted.get(frame) != input {
+                self.first_incorrect = Some(self.first_incorrect.map_or(frame, |f| f.min(frame)));
+            }
+
+            self.last_remote_input = input;
+            self.remote_confirmed += 1;
+        }
+    }
+
+    /// Sends all unacknowledged local inputs.
+    fn send(&mut self) {
+        self.packet.start_frame = self.remote_ack;
+        self.packet.inputs.clear();
+        for frame in self.remote_ack..self.local_input_frame {
+            self.packet.inputs.push(*self.local_inputs.get(frame));
+        }
+        self.packet.ack_frame = self.remote_confirmed;
+        self.packet.frame = self.current_frame;
+        self.packet.advantage = self.local_advantage;
+
+        self.channel.send(&self.packet);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+    /// Order dependent mix of all inputs, so any desync shows up.
+    #[derive(Default, Debug, PartialEq)]
+    struct Mixer {
+        frame: u64,
+        value: u64,
+    }
+
+    impl Simulation<(), u8> for Mixer {
+        fn new(_config: ()) -> Self {
+            Self::default()
+        }
+
+        fn tick(&mut self, _delta_t: Duration, messages: &[Input<u8>]) -> ControlMessage {
+            for message in messages {
+                if let Input::UserMsg(input) = message {
+                    self.value = self.value.wrapping_mul(31).wrapping_add(*input as u64 + 1);
+                }
+            }
+            self.frame += 1;
+            ControlMessage::Ok
+        }
+    }
+
+    impl SaveState for Mixer {
+        type State = (u64, u64);
+
+        fn save_state(&self, state: &mut Self::State) {
+            *state = (self.frame, self.value);
+        }
+
+        fn load_state(&mut self, state: &Self::State) {
+            self.frame = state.0;
+            self.value = state.1;
+        }
+    }
+
+    /// One direction of an in-memory network.
+    #[derive(Default)]
+    struct Link {
+        in_flight: VecDeque<(u64, InputPacket<u8>)>,
+        sent: u64,
+        lost: u64,
+    }
+
+    /// Shared clock + links for both peers.
+    struct Network {
+        time: u64,
+        latency: u64,
+        /// Drop one in every `loss` packets.
+        loss: u64,
+        links: [Link; 2],
+    }
+
+    struct TestChannel {
+        network: Rc<RefCell<Network>>,
+        local: usize,
+    }
+
+    impl RollbackChannel<u8> for TestChannel {
+        fn send(&mut self, packet: &InputPacket<u8>) {
+            let mut network = self.network.borrow_mut();
+            let deliver_at = network.time + network.latency;
+            let loss = network.loss;
+            let link = &mut network.links[self.local];
+            link.sent += 1;
+            if loss > 0 && link.sent.is_multiple_of(loss) {
+                link.lost += 1;
+                return;
+            }
+            link.in_flight.push_back((deliver_at, packet.clone()));
+        }
+
+        fn receive(&mut self) -> Option<InputPacket<u8>> {
+            let mut network = self.network.borrow_mut();
+            let time = network.time;
+            let link = &mut network.links[1 - self.local];
+            match link.in_flight.front() {
+                Some((deliver_at, _)) if *deliver_at <= time => {
+                    link.in_flight.pop_front().map(|p| p.1)
+                }
+                _ => None,
+            }
+        }
+    }
+
+    type Executor = RollbackExecutor<Mixer, (), u8, TestChannel>;
+
+    fn peers(
+        latency: u64,
+        loss: u64,
+        input_delay: u64,
+    ) -> (Executor, Executor, Rc<RefCell<Network>>) {
+        let network = Rc::new(RefCell::new(Network {
+            time: 0,
+            latency,
+            loss,
+            links: [Link::default(), Link::default()],
+        }));
+
+        let peer = |local| {
+            let config = RollbackConfig {
+                local_player: local,
+                input_delay,
+                max_prediction: 8,
+            };
+            let channel = TestChannel {
+                network: network.clone(),
+                local,
+            };
+            Executor::new(60, config, (), channel)
+        };
+
+        (peer(0), peer(1), network)
+    }
+
+    /// Runs both peers until they've confirmed `frames` frames. `b` starts `b_start` steps after `a`.
+    fn run(
+        a: &mut Executor,
+        b: &mut Executor,
+        network: &Rc<RefCell<Network>>,
+        frames: u64,
+        b_start: u64,
+    ) {
+        let mut step = 0;
+        while a.confirmed_frame() < frames || b.confirmed_frame() < frames {
+            assert!(step < frames * 10, "Peers never confirmed all frames");
+
+            for (peer, salt) in [(&mut *a, 7u64), (&mut *b, 13)] {
+                if salt == 13 && step < b_start {
+                    continue;
+                }
+                match peer.current_frame() < frames {
+                    true => {
+                        let input = ((step * salt) % 5) as u8;
+                        peer.tick(input);
+                    }
+                    false => {
+                        peer.poll();
+                    }
+                }
+            }
+
+            network.borrow_mut().time += 1;
+            step += 1;
+        }
+    }
+
+    #[test]
+    fn rollback_peers_agree_over_lossy_channel() {
+        let (mut a, mut b, network) = peers(3, 4, 1);
+        run(&mut a, &mut b, &network, 120, 0);
+
+        assert_eq!(120, a.sim().frame);
+        assert_eq!(a.sim(), b.sim());
+        assert!(network.borrow().links[0].lost > 0);
+        assert!(a.stats().rollbacks > 0 && b.stats().rollbacks > 0);
+        assert!(a.stats().resimulated_frames <= a.stats().rollbacks * 8);
+    }
+
+    #[test]
+    fn rollback_input_delay_avoids_rollbacks() {
+        // With no loss + a delay longer than the latency, input always arrives before it's needed
+        let (mut a, mut b, network) = peers(2, 0, 3);
+        run(&mut a, &mut b, &network, 60, 0);
+
+        assert_eq!(a.sim(), b.sim());
+        assert_eq!(0, a.stats().rollbacks);
+        assert_eq!(0, b.stats().rollbacks);
+    }
+
+    #[test]
+    fn rollback_frame_advantage_sync() {
+        // b starts late, so a should wait for it to catch up instead of running ahead
+        let (mut a, mut b, network) = peers(1, 0, 2);
+        run(&mut a, &mut b, &network, 200, 6);
+
+        assert_eq!(a.sim(), b.sim());
+        assert!(a.stats().waited_frames > b.stats().waited_frames);
+        assert!(a.frame_advantage().abs() <= 1);
+    }
+
+    #[test]
+    fn rollback_handles_invalid_packets() {
+        let (mut a, _, network) = peers(0, 0, 2);
+        network.borrow_mut().links[1].in_flight.push_back((
+            0,
+            InputPacket {
+                start_frame: u64::MAX - 1,
+                inputs: vec![1, 2, 3],
+                ack_frame: u64::MAX,
+                frame: u64::MAX,
+                advantage: i64::MIN,
+            },
+        ));
+
+        // Nothing overflows, and none of the inputs are confirmed
+        for _ in 0..4 {
+            a.tick(0);
+        }
+        assert_eq!(4, a.current_frame());
+        assert_eq!(0, a.confirmed_frame());
+    }
+}
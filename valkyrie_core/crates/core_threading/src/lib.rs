@@ -1,7 +1,48 @@
+// Platform agnostic threading. Platform crates provide the actual worker pools by implementing `ThreadPool`.
+
+/// A unit of work run by a `ThreadPool`. Jobs may borrow from the caller, as pools wait for all jobs to finish before returning.
+pub type Job<'a> = &'a mut (dyn FnMut() + Send);
+
+/// A pool of threads that runs jobs.
+pub trait ThreadPool {
+    /// The number of jobs that may run at the same time.
+    fn thread_count(&self) -> usize;
+
+    /// Runs all jobs, returning once every job has finished. The order jobs run in is not defined.
+    /// If a job panics the panic is resumed on the calling thread after all jobs have finished.
+    fn execute(&self, jobs: &mut [Job]);
+}
+
+/// Runs jobs one after another on the calling thread. Useful for platforms without threads, or when debugging.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct SerialPool;
+
+impl ThreadPool for SerialPool {
+    fn thread_count(&self) -> usize {
+        1
+    }
+
+    fn execute(&self, jobs: &mut [Job]) {
+        for job in jobs.iter_mut() {
+            job();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
-    fn it_works() {
-        assert_eq!(2 + 2, 4);
+    fn serial_pool_runs_jobs_in_order() {
+        let mut values = vec![];
+        let mut a = || values.push(1);
+        let mut total = 0;
+        let mut b = || total += 2;
+
+        SerialPool.execute(&mut [&mut a, &mut b]);
+        assert_eq!(vec![1], values);
+        assert_eq!(2, total);
+        assert_eq!(1, SerialPool.thread_count());
     }
 }
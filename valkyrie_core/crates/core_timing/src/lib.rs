@@ -1,32 +1,114 @@
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
 use std::time::Instant;
 
 pub use std::time::Duration;
 
-/// Converts a hertz to a duration
-pub fn hz_to_duration(hz: u32) -> Duration {
-    Duration::from_secs(1) / hz
+/// A source of time. Allows swapping the real clock out for a manual one, so timing can be stepped deterministically.
+pub trait Clock {
+    /// Returns the time since the clock was created. Must never decrease.
+    fn now(&self) -> Duration;
 }
 
-/// Stopwatch
-pub struct Stopwatch {
+/// Clock that reads the system's monotonic time.
+#[derive(Copy, Clone, Debug)]
+pub struct RealClock {
     start: Instant,
 }
 
-impl Stopwatch {
+impl RealClock {
     pub fn new() -> Self {
         Self {
             start: Instant::now(),
         }
     }
+}
+
+impl Default for RealClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for RealClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when advanced. Clones share the same time, so a handle can be kept
+/// to advance a clock that was moved elsewhere.
+#[derive(Clone, Debug, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by the given duration.
+    pub fn advance(&self, duration: Duration) {
+        self.nanos
+            .fetch_add(duration.as_nanos() as u64, Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::SeqCst))
+    }
+}
+
+/// Converts a hertz to a duration
+pub fn hz_to_duration(hz: u32) -> Duration {
+    Duration::from_secs(1) / hz
+}
+
+/// Stopwatch
+pub struct Stopwatch<C = RealClock> {
+    clock: C,
+    start: Duration,
+}
+
+impl Stopwatch<RealClock> {
+    pub fn new() -> Self {
+        Self::with_clock(RealClock::new())
+    }
+}
+
+impl Default for Stopwatch<RealClock> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C> Stopwatch<C>
+where
+    C: Clock,
+{
+    /// Creates a new stopwatch that reads the given clock.
+    pub fn with_clock(clock: C) -> Self {
+        let start = clock.now();
+        Self { clock, start }
+    }
+
+    /// Returns the clock the stopwatch reads.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
 
     /// Peeks the duration since the last stop
     pub fn peek(&self) -> Duration {
-        Instant::now() - self.start
+        self.clock.now() - self.start
     }
 
     /// Returns the elapsed time since the stopwatch was last checked
     pub fn elapsed(&mut self) -> Duration {
-        let now = Instant::now();
+        let now = self.clock.now();
         let diff = now - self.start;
         self.start = now;
 
@@ -36,8 +118,23 @@ impl Stopwatch {
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
         assert_eq!(2 + 2, 4);
     }
+
+    #[test]
+    fn stopwatch_manual_clock() {
+        let clock = ManualClock::new();
+        let mut stopwatch = Stopwatch::with_clock(clock.clone());
+        assert_eq!(Duration::from_secs(0), stopwatch.elapsed());
+
+        clock.advance(Duration::from_millis(5));
+        clock.advance(Duration::from_millis(10));
+        assert_eq!(Duration::from_millis(15), stopwatch.peek());
+        assert_eq!(Duration::from_millis(15), stopwatch.elapsed());
+        assert_eq!(Duration::from_secs(0), stopwatch.peek());
+    }
 }
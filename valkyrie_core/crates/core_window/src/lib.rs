@@ -1,8 +1,15 @@
-use core_simulation::{Simulation, SimulationExecutor};
+use core_simulation::{Interpolate, Simulation, SimulationExecutor};
 
 /// A renderable simulation
-pub trait Renderable {
-    fn render(&self, renderer: &mut core_renderer::Renderer);
+pub trait Renderable: Interpolate {
+    /// Renders the simulation. `previous` is the state before the last tick, and `alpha` how far it is between
+    /// that tick and the next one, so fixed timestep simulations can interpolate between states.
+    fn render(
+        &self,
+        previous: &Self::RenderState,
+        alpha: f32,
+        renderer: &mut core_renderer::Renderer,
+    );
 }
 
 ///  Implementation of a window
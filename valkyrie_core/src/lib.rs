@@ -31,6 +31,7 @@ pub mod file_io {
 /// Threading.
 pub mod threading {
     pub use core_threading::*;
+    pub use platform_threading::WorkerPool;
 }
 
 /// Timing functionality